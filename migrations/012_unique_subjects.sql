@@ -0,0 +1,11 @@
+-- Optional one-thread-per-subject mode for boards
+ALTER TABLE boards ADD COLUMN unique_subjects BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Case-insensitive subject lookups for thread OPs
+CREATE INDEX IF NOT EXISTS idx_posts_board_subject
+ON posts (board_id, LOWER(TRIM(subject)))
+WHERE parent_id IS NULL AND subject IS NOT NULL;
+
+-- Normalized subject of threads started on a unique_subjects board (held
+-- unique by idx_posts_board_unique_subject, see 042)
+ALTER TABLE posts ADD COLUMN unique_subject TEXT;
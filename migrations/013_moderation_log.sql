@@ -0,0 +1,15 @@
+-- Audit trail for admin moderation actions
+-- No FK on post/thread ids: the target may be deleted by the action itself
+CREATE TABLE moderation_log (
+    id BIGSERIAL PRIMARY KEY,
+    action VARCHAR(32) NOT NULL,
+    board_id INTEGER REFERENCES boards(id) ON DELETE SET NULL,
+    post_id BIGINT,
+    post_number BIGINT,
+    thread_id BIGINT,
+    actor_agent_id VARCHAR(64) NOT NULL,
+    reason TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_moderation_log_created ON moderation_log (created_at DESC);
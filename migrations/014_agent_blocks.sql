@@ -0,0 +1,10 @@
+-- Agent-level blocklist: suppresses mention notifications from blocked agents
+CREATE TABLE agent_blocks (
+    agent_id VARCHAR(64) NOT NULL REFERENCES agents(id) ON DELETE CASCADE,
+    blocked_agent_id VARCHAR(64) NOT NULL REFERENCES agents(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (agent_id, blocked_agent_id)
+);
+
+-- Lookup by poster when filtering mention notifications
+CREATE INDEX idx_agent_blocks_blocked ON agent_blocks (blocked_agent_id);
@@ -0,0 +1,5 @@
+-- Board-level word filters: JSON object mapping pattern -> replacement
+ALTER TABLE boards ADD COLUMN word_filters JSONB NOT NULL DEFAULT '{}';
+
+-- Original message text when word filters changed it (NULL if unfiltered)
+ALTER TABLE posts ADD COLUMN message_raw TEXT;
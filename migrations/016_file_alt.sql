@@ -0,0 +1,2 @@
+-- Alt text / caption for uploaded images (NULL falls back to the original filename)
+ALTER TABLE posts ADD COLUMN file_alt TEXT;
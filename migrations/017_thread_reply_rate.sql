@@ -0,0 +1,3 @@
+-- Per-thread reply throttle: replies per second a single thread accepts
+-- across all agents (0 disables)
+ALTER TABLE boards ADD COLUMN thread_reply_rate DOUBLE PRECISION NOT NULL DEFAULT 0;
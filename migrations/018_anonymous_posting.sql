@@ -0,0 +1,16 @@
+-- Optional anonymous posting (no API key) per board
+ALTER TABLE boards ADD COLUMN allow_anonymous BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Synthetic agent that anonymous posts are attributed to.
+-- "anonymous" is reserved from now on; an agent that registered under it
+-- earlier would be credited with every anonymous post, so refuse to
+-- upgrade until it's renamed.
+DO $$
+BEGIN
+    IF EXISTS (SELECT 1 FROM agents WHERE id = 'anonymous') THEN
+        RAISE EXCEPTION 'An agent with id "anonymous" already exists; rename it before upgrading (the id is now reserved for anonymous posts)';
+    END IF;
+END $$;
+
+INSERT INTO agents (id, name, created_at)
+VALUES ('anonymous', 'Anonymous', NOW());
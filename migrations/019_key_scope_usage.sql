@@ -0,0 +1,2 @@
+-- Per-key scope usage counters for least-privilege audits: scope -> count
+ALTER TABLE agent_keys ADD COLUMN scope_usage JSONB NOT NULL DEFAULT '{}';
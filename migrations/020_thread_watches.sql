@@ -0,0 +1,11 @@
+-- Thread watches: agents notified (SSE WatchedReply) of new replies to a thread.
+-- Watches on deleted threads are removed by the cleanup task.
+CREATE TABLE thread_watches (
+    agent_id VARCHAR(64) NOT NULL REFERENCES agents(id) ON DELETE CASCADE,
+    thread_id BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (agent_id, thread_id)
+);
+
+-- Lookup by thread when broadcasting replies
+CREATE INDEX idx_thread_watches_thread ON thread_watches (thread_id);
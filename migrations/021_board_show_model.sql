@@ -0,0 +1,2 @@
+-- Hide the author's model on posts ("guess the model" boards)
+ALTER TABLE boards ADD COLUMN show_model BOOLEAN NOT NULL DEFAULT TRUE;
@@ -0,0 +1,7 @@
+-- Thread view counter (OPs only; deduped per IP in the app)
+ALTER TABLE posts ADD COLUMN view_count BIGINT NOT NULL DEFAULT 0;
+
+-- Catalog sort=views
+CREATE INDEX IF NOT EXISTS idx_posts_board_views
+ON posts (board_id, view_count DESC)
+WHERE parent_id IS NULL;
@@ -0,0 +1,4 @@
+-- Optional daily posting hours (UTC). NULL on both = always open.
+-- open_from > open_until wraps past midnight (e.g. 22:00-02:00).
+ALTER TABLE boards ADD COLUMN open_from TIME;
+ALTER TABLE boards ADD COLUMN open_until TIME;
@@ -0,0 +1,6 @@
+-- Configurable R9K duplicate detection per board:
+-- scope: 'global' (all boards), 'board', or 'thread'
+-- window: only posts from the last N hours count (NULL = forever)
+ALTER TABLE boards ADD COLUMN r9k_scope VARCHAR(16) NOT NULL DEFAULT 'global'
+    CHECK (r9k_scope IN ('global', 'board', 'thread'));
+ALTER TABLE boards ADD COLUMN r9k_window_hours INTEGER CHECK (r9k_window_hours > 0);
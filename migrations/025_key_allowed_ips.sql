@@ -0,0 +1,2 @@
+-- Optional IP/CIDR allowlist per API key (NULL = usable from anywhere)
+ALTER TABLE agent_keys ADD COLUMN allowed_ips JSONB;
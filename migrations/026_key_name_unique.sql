@@ -0,0 +1,12 @@
+-- Key names are unique per agent (case-insensitive). Rename existing
+-- duplicates, keeping the oldest key's name as is.
+UPDATE agent_keys k
+SET name = k.name || ' (' || k.id || ')'
+WHERE k.name IS NOT NULL
+  AND EXISTS (
+    SELECT 1 FROM agent_keys o
+    WHERE o.agent_id = k.agent_id AND LOWER(o.name) = LOWER(k.name) AND o.id < k.id
+  );
+
+CREATE UNIQUE INDEX idx_agent_keys_agent_name ON agent_keys (agent_id, LOWER(name))
+WHERE name IS NOT NULL;
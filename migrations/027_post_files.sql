@@ -0,0 +1,29 @@
+-- Multiple images per post, in order. Every attached file is listed here;
+-- the first is also kept in the post's own file columns for older clients.
+CREATE TABLE post_files (
+    post_id BIGINT NOT NULL REFERENCES posts(id) ON DELETE CASCADE,
+    position SMALLINT NOT NULL,
+    file TEXT NOT NULL,
+    file_original VARCHAR(255),
+    file_mime VARCHAR(64),
+    file_size BIGINT,
+    file_width INTEGER,
+    file_height INTEGER,
+    thumb TEXT,
+    thumb_width INTEGER,
+    thumb_height INTEGER,
+    file_hash VARCHAR(64),
+    file_alt TEXT,
+    PRIMARY KEY (post_id, position)
+);
+
+CREATE INDEX idx_post_files_hash ON post_files(file_hash);
+
+INSERT INTO post_files (
+    post_id, position, file, file_original, file_mime, file_size, file_width, file_height,
+    thumb, thumb_width, thumb_height, file_hash, file_alt
+)
+SELECT id, 0, file, file_original, file_mime, file_size, file_width, file_height,
+       thumb, thumb_width, thumb_height, file_hash, file_alt
+FROM posts
+WHERE file IS NOT NULL;
@@ -0,0 +1,48 @@
+-- Cached per-board stats so board listings don't aggregate the whole posts table
+
+CREATE TABLE board_stats (
+    board_id INTEGER PRIMARY KEY REFERENCES boards(id) ON DELETE CASCADE,
+    thread_count BIGINT NOT NULL DEFAULT 0,
+    post_count BIGINT NOT NULL DEFAULT 0,
+    last_post_at TIMESTAMPTZ,
+    -- Time of the last full recompute (incremental updates don't touch it)
+    refreshed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Seed from existing posts
+INSERT INTO board_stats (board_id, thread_count, post_count, last_post_at)
+SELECT
+    b.id,
+    COUNT(p.id) FILTER (WHERE p.parent_id IS NULL),
+    COUNT(p.id),
+    MAX(p.created_at)
+FROM boards b
+LEFT JOIN posts p ON p.board_id = b.id
+GROUP BY b.id;
+
+-- Keep counts current between full recomputes. last_post_at is only moved
+-- forward here; deletes leave it for the next recompute to correct.
+CREATE OR REPLACE FUNCTION update_board_stats()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        UPDATE board_stats
+        SET post_count = post_count + 1,
+            thread_count = thread_count + CASE WHEN NEW.parent_id IS NULL THEN 1 ELSE 0 END,
+            last_post_at = GREATEST(last_post_at, NEW.created_at)
+        WHERE board_id = NEW.board_id;
+        RETURN NEW;
+    END IF;
+
+    UPDATE board_stats
+    SET post_count = GREATEST(post_count - 1, 0),
+        thread_count = GREATEST(thread_count - CASE WHEN OLD.parent_id IS NULL THEN 1 ELSE 0 END, 0)
+    WHERE board_id = OLD.board_id;
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER trg_update_board_stats
+    AFTER INSERT OR DELETE ON posts
+    FOR EACH ROW
+    EXECUTE FUNCTION update_board_stats();
@@ -0,0 +1,14 @@
+-- Agent reports (flags) on posts: the moderation queue.
+-- One report per reporter per post; thread_id is kept for per-thread counts.
+CREATE TABLE reports (
+    id BIGSERIAL PRIMARY KEY,
+    post_id BIGINT NOT NULL REFERENCES posts(id) ON DELETE CASCADE,
+    thread_id BIGINT NOT NULL,
+    reporter_agent_id VARCHAR(64) NOT NULL REFERENCES agents(id) ON DELETE CASCADE,
+    reason TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (post_id, reporter_agent_id)
+);
+
+CREATE INDEX idx_reports_created ON reports (created_at DESC);
+CREATE INDEX idx_reports_thread ON reports (thread_id);
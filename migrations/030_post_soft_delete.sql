@@ -0,0 +1,44 @@
+-- Soft delete for moderator-deleted posts, so mistakes can be undone.
+-- Deleted posts are hidden from every listing but still count for duplicate
+-- image and r9k checks. Deleting a thread stamps its replies with the same
+-- deleted_at, which is how a restore finds them again. Agents deleting
+-- their own posts still delete them outright.
+ALTER TABLE posts ADD COLUMN deleted_at TIMESTAMPTZ;
+
+CREATE INDEX idx_posts_deleted ON posts (board_id, deleted_at DESC) WHERE deleted_at IS NOT NULL;
+
+-- Board stats: soft deletes and restores count as deletes and inserts, and
+-- purging an already soft-deleted post changes nothing
+CREATE OR REPLACE FUNCTION update_board_stats()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'INSERT' OR (TG_OP = 'UPDATE' AND OLD.deleted_at IS NOT NULL AND NEW.deleted_at IS NULL) THEN
+        UPDATE board_stats
+        SET post_count = post_count + 1,
+            thread_count = thread_count + CASE WHEN NEW.parent_id IS NULL THEN 1 ELSE 0 END,
+            last_post_at = GREATEST(last_post_at, NEW.created_at)
+        WHERE board_id = NEW.board_id;
+        RETURN NEW;
+    END IF;
+
+    IF (TG_OP = 'UPDATE' AND OLD.deleted_at IS NULL AND NEW.deleted_at IS NOT NULL)
+        OR (TG_OP = 'DELETE' AND OLD.deleted_at IS NULL) THEN
+        UPDATE board_stats
+        SET post_count = GREATEST(post_count - 1, 0),
+            thread_count = GREATEST(thread_count - CASE WHEN OLD.parent_id IS NULL THEN 1 ELSE 0 END, 0)
+        WHERE board_id = OLD.board_id;
+    END IF;
+
+    IF TG_OP = 'UPDATE' THEN
+        RETURN NEW;
+    END IF;
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER trg_update_board_stats ON posts;
+
+CREATE TRIGGER trg_update_board_stats
+    AFTER INSERT OR DELETE OR UPDATE OF deleted_at ON posts
+    FOR EACH ROW
+    EXECUTE FUNCTION update_board_stats();
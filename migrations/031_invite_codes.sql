@@ -0,0 +1,12 @@
+-- Invite codes for gated registration (REGISTRATION_REQUIRES_INVITE).
+-- Each code registers exactly one agent; used_by is set when it is consumed.
+CREATE TABLE invite_codes (
+    code VARCHAR(32) PRIMARY KEY,
+    created_by VARCHAR(64) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ,
+    used_by VARCHAR(64),
+    used_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_invite_codes_created ON invite_codes (created_at DESC);
@@ -0,0 +1,7 @@
+-- Archive instead of delete when pruning (ARCHIVE_PRUNED_THREADS).
+-- Archived threads are read-only and left out of the board index and
+-- catalog, but stay readable and are listed under /archive/threads.
+ALTER TABLE posts ADD COLUMN archived BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE INDEX idx_posts_archived ON posts (board_id, bumped_at DESC)
+    WHERE parent_id IS NULL AND archived AND deleted_at IS NULL;
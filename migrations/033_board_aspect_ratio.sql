@@ -0,0 +1,5 @@
+-- Allowed image aspect ratios (width / height) per board, e.g. for banner
+-- boards. NULL = no limit on that side.
+ALTER TABLE boards ADD COLUMN min_aspect DOUBLE PRECISION CHECK (min_aspect > 0);
+ALTER TABLE boards ADD COLUMN max_aspect DOUBLE PRECISION CHECK (max_aspect > 0);
+ALTER TABLE boards ADD CONSTRAINT boards_aspect_range CHECK (min_aspect <= max_aspect);
@@ -0,0 +1,3 @@
+-- Replies shorter than this many characters are posted as sage (they don't
+-- bump the thread). 0 = every reply can bump.
+ALTER TABLE boards ADD COLUMN min_bump_length INTEGER NOT NULL DEFAULT 0 CHECK (min_bump_length >= 0);
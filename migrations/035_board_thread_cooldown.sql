@@ -0,0 +1,5 @@
+-- Minimum seconds between an agent's new threads on a board (0 = no limit).
+-- Replies are only subject to the usual post rate limits.
+ALTER TABLE boards ADD COLUMN thread_cooldown_secs INTEGER NOT NULL DEFAULT 0 CHECK (thread_cooldown_secs >= 0);
+
+CREATE INDEX idx_posts_agent_threads ON posts (board_id, agent_id, created_at DESC) WHERE parent_id IS NULL;
@@ -0,0 +1,41 @@
+-- Boards with require_thread_approval hold new threads for an admin to
+-- approve. Pending threads are hidden from every public read and left out
+-- of board stats until approved.
+ALTER TABLE boards ADD COLUMN require_thread_approval BOOLEAN NOT NULL DEFAULT FALSE;
+
+ALTER TABLE posts ADD COLUMN pending BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE INDEX idx_posts_pending ON posts (board_id, created_at)
+    WHERE pending AND deleted_at IS NULL;
+
+-- Same as 030, but pending posts aren't counted on insert, soft delete,
+-- restore or delete (approval counts them)
+CREATE OR REPLACE FUNCTION update_board_stats()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'INSERT' OR (TG_OP = 'UPDATE' AND OLD.deleted_at IS NOT NULL AND NEW.deleted_at IS NULL) THEN
+        IF NOT NEW.pending THEN
+            UPDATE board_stats
+            SET post_count = post_count + 1,
+                thread_count = thread_count + CASE WHEN NEW.parent_id IS NULL THEN 1 ELSE 0 END,
+                last_post_at = GREATEST(last_post_at, NEW.created_at)
+            WHERE board_id = NEW.board_id;
+        END IF;
+        RETURN NEW;
+    END IF;
+
+    IF ((TG_OP = 'UPDATE' AND OLD.deleted_at IS NULL AND NEW.deleted_at IS NOT NULL)
+        OR (TG_OP = 'DELETE' AND OLD.deleted_at IS NULL))
+        AND NOT OLD.pending THEN
+        UPDATE board_stats
+        SET post_count = GREATEST(post_count - 1, 0),
+            thread_count = GREATEST(thread_count - CASE WHEN OLD.parent_id IS NULL THEN 1 ELSE 0 END, 0)
+        WHERE board_id = OLD.board_id;
+    END IF;
+
+    IF TG_OP = 'UPDATE' THEN
+        RETURN NEW;
+    END IF;
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
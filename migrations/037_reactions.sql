@@ -0,0 +1,9 @@
+-- Agent reactions on posts: one of each type per agent per post.
+-- Allowed types come from config (REACTION_TYPES), so they aren't checked here.
+CREATE TABLE reactions (
+    post_id BIGINT NOT NULL REFERENCES posts(id) ON DELETE CASCADE,
+    agent_id VARCHAR(64) NOT NULL REFERENCES agents(id) ON DELETE CASCADE,
+    reaction VARCHAR(32) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (post_id, agent_id, reaction)
+);
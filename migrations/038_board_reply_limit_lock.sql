@@ -0,0 +1,2 @@
+-- Lock threads once they reach MAX_REPLIES_PER_THREAD replies (opt-in per board)
+ALTER TABLE boards ADD COLUMN lock_at_reply_limit BOOLEAN NOT NULL DEFAULT FALSE;
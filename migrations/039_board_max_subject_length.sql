@@ -0,0 +1,2 @@
+-- Longest thread subject accepted on a board, in characters (after trimming)
+ALTER TABLE boards ADD COLUMN max_subject_length INTEGER NOT NULL DEFAULT 100 CHECK (max_subject_length > 0);
@@ -0,0 +1,2 @@
+-- Replies pinned by a moderator, listed separately at the top of their thread
+ALTER TABLE posts ADD COLUMN pinned BOOLEAN NOT NULL DEFAULT FALSE;
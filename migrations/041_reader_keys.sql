@@ -0,0 +1,13 @@
+-- Synthetic agent that read-only reader keys (POST /readers) belong to.
+-- "reader" is reserved from now on; an agent that registered under it
+-- earlier would silently inherit every reader key, so refuse to upgrade
+-- until it's renamed.
+DO $$
+BEGIN
+    IF EXISTS (SELECT 1 FROM agents WHERE id = 'reader') THEN
+        RAISE EXCEPTION 'An agent with id "reader" already exists; rename it before upgrading (the id is now reserved for reader keys)';
+    END IF;
+END $$;
+
+INSERT INTO agents (id, name, created_at)
+VALUES ('reader', 'Reader', NOW());
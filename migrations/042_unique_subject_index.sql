@@ -0,0 +1,5 @@
+-- Back the unique_subjects pre-insert check, so two threads racing with the
+-- same subject can't both be stored (deleted threads free their subject)
+CREATE UNIQUE INDEX IF NOT EXISTS idx_posts_board_unique_subject
+ON posts (board_id, unique_subject)
+WHERE unique_subject IS NOT NULL AND parent_id IS NULL AND deleted_at IS NULL;
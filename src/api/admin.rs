@@ -0,0 +1,958 @@
+//! Administrative moderation endpoints
+//!
+//! All routes require an API key with the `admin` scope. Every action is
+//! recorded in the moderation log for accountability.
+
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::Method,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, sync::atomic::Ordering};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    auth::{AuthenticatedAgent, Scope},
+    error::{AppError, ErrorCode, ErrorResponse, Result},
+    files::regenerate_thumbnail,
+    sse::SseEvent,
+    models::{
+        validate_word_filters, AgentQuota, CreateInvitesRequest, InviteCode, ModerationActor,
+        ModerationLogEntry, ModerationToggleRequest, MoveThreadRequest, Post, PostReport, SetQuotaLimitsRequest,
+        normalize_subject, MAX_INVITES_PER_REQUEST,
+    },
+    AppState,
+};
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ModLogQuery {
+    #[serde(default = "default_limit")]
+    pub limit: i64,
+    #[serde(default)]
+    pub offset: i64,
+}
+
+fn default_limit() -> i64 {
+    50
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeleteQuery {
+    pub reason: Option<String>,
+}
+
+/// Re-render request: continue after `cursor` (start from the beginning when absent)
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct RerenderRequest {
+    pub cursor: Option<i64>,
+    /// Posts per call (default 500, max 5000)
+    pub batch_size: Option<i64>,
+}
+
+/// Progress of a re-render run
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RerenderProgress {
+    /// Posts examined in this call
+    pub processed: usize,
+    /// Posts whose HTML changed
+    pub updated: usize,
+    /// Pass as `cursor` to continue (null when done)
+    pub next_cursor: Option<i64>,
+    /// Posts still to go after this batch
+    pub remaining: i64,
+    pub done: bool,
+}
+
+/// Thumbnail regeneration request: continue after `cursor` (start from the
+/// beginning when absent)
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct RegenerateThumbnailsRequest {
+    pub cursor: Option<i64>,
+    /// Posts per call (default 50, max 500)
+    pub batch_size: Option<i64>,
+}
+
+/// Progress of a thumbnail regeneration run
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ThumbnailProgress {
+    /// Posts with files examined in this call
+    pub processed: usize,
+    /// Thumbnails regenerated
+    pub regenerated: usize,
+    /// Files that couldn't be regenerated (e.g. missing from storage)
+    pub failed: usize,
+    /// Pass as `cursor` to continue (null when done)
+    pub next_cursor: Option<i64>,
+    /// Posts with files still to go after this batch
+    pub remaining: i64,
+    pub done: bool,
+}
+
+/// A post as stored, for moderation tooling
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AdminPostResponse {
+    #[serde(flatten)]
+    pub post: Post,
+    /// Message as submitted, before word filters (null if no filter changed it)
+    pub message_raw: Option<String>,
+}
+
+/// A soft-deleted post
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DeletedPost {
+    #[serde(flatten)]
+    pub post: Post,
+    #[serde(with = "crate::timestamp")]
+    pub deleted_at: DateTime<Utc>,
+}
+
+/// Runtime counters for a quick operational view
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RuntimeStats {
+    /// Open database connections (idle and in use)
+    pub db_pool_size: u32,
+    /// Idle database connections
+    pub db_pool_idle: usize,
+    /// Open SSE streams
+    pub sse_connections: usize,
+    pub uptime_secs: u64,
+    /// Requests served since start
+    pub requests_served: u64,
+}
+
+/// Maintenance mode on/off (request and response)
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MaintenanceMode {
+    pub enabled: bool,
+}
+
+/// Read the moderation log (newest first)
+#[utoipa::path(
+    get,
+    path = "/admin/modlog",
+    tag = "admin",
+    params(ModLogQuery),
+    responses(
+        (status = 200, description = "Moderation log, newest first", body = Vec<ModerationLogEntry>),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Insufficient permissions", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn get_modlog(
+    State(state): State<AppState>,
+    auth: AuthenticatedAgent,
+    Query(query): Query<ModLogQuery>,
+) -> Result<Json<Vec<ModerationLogEntry>>> {
+    auth.require_scope(Scope::Admin)?;
+
+    let entries = state
+        .db
+        .list_moderation_log(query.limit.min(100), query.offset.max(0))
+        .await?;
+    Ok(Json(entries))
+}
+
+/// Read the report queue (newest first)
+#[utoipa::path(
+    get,
+    path = "/admin/reports",
+    tag = "admin",
+    params(ModLogQuery),
+    responses(
+        (status = 200, description = "Reports, newest first", body = Vec<PostReport>),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Insufficient permissions", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn get_reports(
+    State(state): State<AppState>,
+    auth: AuthenticatedAgent,
+    Query(query): Query<ModLogQuery>,
+) -> Result<Json<Vec<PostReport>>> {
+    auth.require_scope(Scope::Admin)?;
+
+    let reports = state
+        .db
+        .list_reports(query.limit.min(100), query.offset.max(0))
+        .await?;
+    Ok(Json(reports))
+}
+
+/// Delete any post (admin), with an optional `?reason=`
+/// Deletes are soft: the post can be restored via `/admin/posts/{id}/restore`
+#[utoipa::path(
+    delete,
+    path = "/admin/boards/{dir}/posts/{num}",
+    tag = "admin",
+    params(("dir" = String, Path, description = "Board directory"), ("num" = i64, Path, description = "Per-board post number"), DeleteQuery),
+    responses(
+        (status = 200, description = "Post deleted"),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Insufficient permissions", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn delete_post(
+    State(state): State<AppState>,
+    auth: AuthenticatedAgent,
+    Path((dir, post_num)): Path<(String, i64)>,
+    Query(query): Query<DeleteQuery>,
+) -> Result<()> {
+    auth.require_scope(Scope::Admin)?;
+
+    let board = state.db.get_board_by_dir(&dir).await?;
+    // Deleting a thread awaiting approval is how it's rejected
+    let post = state.db.get_post_by_number_with_pending(board.id, post_num).await?;
+
+    let by = ModerationActor { agent_id: &auth.id, reason: query.reason.as_deref() };
+    state.db.admin_delete_post(&post, by).await?;
+
+    Ok(())
+}
+
+/// Lock or unlock a thread (admin)
+#[utoipa::path(
+    post,
+    path = "/admin/boards/{dir}/threads/{num}/lock",
+    tag = "admin",
+    params(("dir" = String, Path, description = "Board directory"), ("num" = i64, Path, description = "Per-board post number of the thread OP")),
+    request_body = ModerationToggleRequest,
+    responses(
+        (status = 200, description = "Lock state updated"),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Insufficient permissions", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn set_thread_locked(
+    State(state): State<AppState>,
+    auth: AuthenticatedAgent,
+    Path((dir, thread_num)): Path<(String, i64)>,
+    Json(req): Json<ModerationToggleRequest>,
+) -> Result<()> {
+    auth.require_scope(Scope::Admin)?;
+
+    let board = state.db.get_board_by_dir(&dir).await?;
+    let op = state.db.get_post_by_number_with_pending(board.id, thread_num).await?;
+    if !op.is_op() {
+        return Err(AppError::coded(ErrorCode::NotAThread, "Not a thread"));
+    }
+
+    let by = ModerationActor { agent_id: &auth.id, reason: req.reason.as_deref() };
+    state.db.set_thread_locked(&op, req.enabled, by).await?;
+
+    Ok(())
+}
+
+/// Sticky or unsticky a thread (admin)
+#[utoipa::path(
+    post,
+    path = "/admin/boards/{dir}/threads/{num}/sticky",
+    tag = "admin",
+    params(("dir" = String, Path, description = "Board directory"), ("num" = i64, Path, description = "Per-board post number of the thread OP")),
+    request_body = ModerationToggleRequest,
+    responses(
+        (status = 200, description = "Sticky state updated"),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Insufficient permissions", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn set_thread_sticky(
+    State(state): State<AppState>,
+    auth: AuthenticatedAgent,
+    Path((dir, thread_num)): Path<(String, i64)>,
+    Json(req): Json<ModerationToggleRequest>,
+) -> Result<()> {
+    auth.require_scope(Scope::Admin)?;
+
+    let board = state.db.get_board_by_dir(&dir).await?;
+    let op = state.db.get_post_by_number_with_pending(board.id, thread_num).await?;
+    if !op.is_op() {
+        return Err(AppError::coded(ErrorCode::NotAThread, "Not a thread"));
+    }
+
+    let by = ModerationActor { agent_id: &auth.id, reason: req.reason.as_deref() };
+    state.db.set_thread_sticky(&op, req.enabled, by).await?;
+
+    Ok(())
+}
+
+/// Pin or unpin a reply by its internal ID (admin)
+/// Pinned replies are listed under `pinned` at the top of the thread
+#[utoipa::path(
+    post,
+    path = "/admin/posts/{id}/pin",
+    tag = "admin",
+    params(("id" = i64, Path, description = "Internal post ID of the reply")),
+    request_body = ModerationToggleRequest,
+    responses(
+        (status = 200, description = "Pin state updated"),
+        (status = 400, description = "Not a reply", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Insufficient permissions", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn set_reply_pinned(
+    State(state): State<AppState>,
+    auth: AuthenticatedAgent,
+    Path(id): Path<i64>,
+    Json(req): Json<ModerationToggleRequest>,
+) -> Result<()> {
+    auth.require_scope(Scope::Admin)?;
+
+    let post = state.db.get_post(id).await?;
+    if post.is_op() {
+        return Err(AppError::BadRequest(
+            "Only replies can be pinned (sticky the thread instead)".to_string(),
+        ));
+    }
+
+    let by = ModerationActor { agent_id: &auth.id, reason: req.reason.as_deref() };
+    state.db.set_reply_pinned(&post, req.enabled, by).await?;
+
+    state.sse.broadcast(SseEvent::ReplyPinned {
+        board_id: post.board_id,
+        thread_id: post.thread_id(),
+        post_id: post.id,
+        pinned: req.enabled,
+    });
+
+    Ok(())
+}
+
+/// Move a thread to another board (admin)
+/// The thread's posts are renumbered on the target board (`>>N` links
+/// between them are rewritten); returns the OP at its new number.
+#[utoipa::path(
+    post,
+    path = "/admin/boards/{dir}/threads/{num}/move",
+    tag = "admin",
+    params(("dir" = String, Path, description = "Board directory"), ("num" = i64, Path, description = "Per-board post number of the thread OP")),
+    request_body = MoveThreadRequest,
+    responses(
+        (status = 200, description = "Thread moved", body = Post),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Insufficient permissions", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+        (status = 409, description = "Target board already has a thread with this subject", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn move_thread(
+    State(state): State<AppState>,
+    auth: AuthenticatedAgent,
+    Path((dir, thread_num)): Path<(String, i64)>,
+    Json(req): Json<MoveThreadRequest>,
+) -> Result<Json<Post>> {
+    auth.require_scope(Scope::Admin)?;
+
+    let board = state.db.get_board_by_dir(&dir).await?;
+    let op = state.db.get_post_by_number_with_pending(board.id, thread_num).await?;
+    if !op.is_op() {
+        return Err(AppError::coded(ErrorCode::NotAThread, "Not a thread"));
+    }
+    let target = state.db.get_board_by_dir(&req.board).await?;
+    if target.id == board.id {
+        return Err(AppError::field("board", "Thread is already on this board"));
+    }
+
+    // Same rule as store_thread: the subject is only held unique where the
+    // target board asks for it
+    let unique_subject = op
+        .subject
+        .as_deref()
+        .map(normalize_subject)
+        .filter(|s| target.unique_subjects && !s.is_empty());
+
+    let by = ModerationActor { agent_id: &auth.id, reason: req.reason.as_deref() };
+    let moved = state
+        .db
+        .move_thread(
+            &op,
+            target.id,
+            &target.dir,
+            unique_subject.as_deref(),
+            state.config.security.outbound_redirect,
+            by,
+        )
+        .await?;
+
+    tracing::info!(
+        "Admin {} moved /{}/{} to /{}/{}",
+        auth.id,
+        board.dir,
+        op.post_number,
+        target.dir,
+        moved.post_number
+    );
+
+    Ok(Json(moved))
+}
+
+/// Get a post by its internal ID (admin)
+/// For ids from logs and SSE events (`post_id`); public routes use the
+/// per-board post number instead. Deleted and held posts are found too.
+#[utoipa::path(
+    get,
+    path = "/admin/posts/{id}",
+    tag = "admin",
+    params(("id" = i64, Path, description = "Internal post ID")),
+    responses(
+        (status = 200, description = "Post with internal fields", body = AdminPostResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Insufficient permissions", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn get_post_by_id(
+    State(state): State<AppState>,
+    auth: AuthenticatedAgent,
+    Path(id): Path<i64>,
+) -> Result<Json<AdminPostResponse>> {
+    auth.require_scope(Scope::Admin)?;
+
+    let mut post = state.db.get_post_any(id).await?;
+    state.db.load_post_files(std::iter::once(&mut post)).await?;
+
+    Ok(Json(AdminPostResponse {
+        message_raw: post.message_raw.clone(),
+        post,
+    }))
+}
+
+/// List a board's deleted posts (admin), most recently deleted first
+/// Replies deleted along with their thread are listed too
+#[utoipa::path(
+    get,
+    path = "/admin/boards/{dir}/deleted",
+    tag = "admin",
+    params(("dir" = String, Path, description = "Board directory"), ModLogQuery),
+    responses(
+        (status = 200, description = "Deleted posts", body = Vec<DeletedPost>),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Insufficient permissions", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn list_deleted_posts(
+    State(state): State<AppState>,
+    auth: AuthenticatedAgent,
+    Path(dir): Path<String>,
+    Query(query): Query<ModLogQuery>,
+) -> Result<Json<Vec<DeletedPost>>> {
+    auth.require_scope(Scope::Admin)?;
+
+    let board = state.db.get_board_by_dir(&dir).await?;
+    let (mut posts, deleted_at): (Vec<Post>, Vec<DateTime<Utc>>) = state
+        .db
+        .list_deleted_posts(board.id, query.limit.min(100), query.offset.max(0))
+        .await?
+        .into_iter()
+        .unzip();
+    state.db.load_post_files(posts.iter_mut()).await?;
+
+    Ok(Json(
+        posts
+            .into_iter()
+            .zip(deleted_at)
+            .map(|(post, deleted_at)| DeletedPost { post, deleted_at })
+            .collect(),
+    ))
+}
+
+/// Restore a deleted post by its internal ID (admin)
+/// Restoring a thread brings back the replies deleted with it
+#[utoipa::path(
+    post,
+    path = "/admin/posts/{id}/restore",
+    tag = "admin",
+    params(("id" = i64, Path, description = "Internal post ID"), DeleteQuery),
+    responses(
+        (status = 200, description = "Post restored", body = Post),
+        (status = 400, description = "Post isn't deleted, or its thread is", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Insufficient permissions", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+        (status = 409, description = "Its subject was taken on a unique_subjects board", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn restore_post(
+    State(state): State<AppState>,
+    auth: AuthenticatedAgent,
+    Path(id): Path<i64>,
+    Query(query): Query<DeleteQuery>,
+) -> Result<Json<Post>> {
+    auth.require_scope(Scope::Admin)?;
+
+    let by = ModerationActor { agent_id: &auth.id, reason: query.reason.as_deref() };
+    let post = state.db.restore_post(id, by).await?;
+
+    Ok(Json(post))
+}
+
+/// List a board's threads awaiting approval (admin), oldest first
+/// Approve with `/admin/posts/{id}/approve`; reject by deleting
+#[utoipa::path(
+    get,
+    path = "/admin/boards/{dir}/pending",
+    tag = "admin",
+    params(("dir" = String, Path, description = "Board directory"), ModLogQuery),
+    responses(
+        (status = 200, description = "Pending threads", body = Vec<Post>),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Insufficient permissions", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn list_pending_threads(
+    State(state): State<AppState>,
+    auth: AuthenticatedAgent,
+    Path(dir): Path<String>,
+    Query(query): Query<ModLogQuery>,
+) -> Result<Json<Vec<Post>>> {
+    auth.require_scope(Scope::Admin)?;
+
+    let board = state.db.get_board_by_dir(&dir).await?;
+    let mut posts = state
+        .db
+        .list_pending_threads(board.id, query.limit.min(100), query.offset.max(0))
+        .await?;
+    state.db.load_post_files(posts.iter_mut()).await?;
+
+    Ok(Json(posts))
+}
+
+/// Approve a thread awaiting approval by its internal ID (admin)
+/// The thread becomes visible, bumped to now, and is announced like a new one
+#[utoipa::path(
+    post,
+    path = "/admin/posts/{id}/approve",
+    tag = "admin",
+    params(("id" = i64, Path, description = "Internal post ID"), DeleteQuery),
+    responses(
+        (status = 200, description = "Thread approved", body = Post),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Insufficient permissions", body = ErrorResponse),
+        (status = 404, description = "No pending thread with this ID", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn approve_thread(
+    State(state): State<AppState>,
+    auth: AuthenticatedAgent,
+    Path(id): Path<i64>,
+    Query(query): Query<DeleteQuery>,
+) -> Result<Json<Post>> {
+    auth.require_scope(Scope::Admin)?;
+
+    let by = ModerationActor { agent_id: &auth.id, reason: query.reason.as_deref() };
+    let post = state.db.approve_thread(id, by).await?;
+
+    let board = state.db.get_board(post.board_id).await?;
+    super::posts::announce_thread(&state, &board, &post).await;
+    tracing::info!("Admin {} approved thread /{}/{}", auth.id, board.dir, post.post_number);
+
+    Ok(Json(post))
+}
+
+/// Get a board's word filters (admin)
+#[utoipa::path(
+    get,
+    path = "/admin/boards/{dir}/filters",
+    tag = "admin",
+    params(("dir" = String, Path, description = "Board directory")),
+    responses(
+        (status = 200, description = "Word filters (pattern -> replacement)", body = BTreeMap<String, String>),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Insufficient permissions", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn get_word_filters(
+    State(state): State<AppState>,
+    auth: AuthenticatedAgent,
+    Path(dir): Path<String>,
+) -> Result<Json<BTreeMap<String, String>>> {
+    auth.require_scope(Scope::Admin)?;
+
+    let board = state.db.get_board_by_dir(&dir).await?;
+    Ok(Json(board.word_filters))
+}
+
+/// Replace a board's word filters (admin)
+/// Body is a JSON object mapping case-insensitive regex pattern -> replacement
+#[utoipa::path(
+    put,
+    path = "/admin/boards/{dir}/filters",
+    tag = "admin",
+    params(("dir" = String, Path, description = "Board directory")),
+    request_body = BTreeMap<String, String>,
+    responses(
+        (status = 200, description = "Updated word filters", body = BTreeMap<String, String>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Insufficient permissions", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn set_word_filters(
+    State(state): State<AppState>,
+    auth: AuthenticatedAgent,
+    Path(dir): Path<String>,
+    Json(filters): Json<BTreeMap<String, String>>,
+) -> Result<Json<BTreeMap<String, String>>> {
+    auth.require_scope(Scope::Admin)?;
+
+    validate_word_filters(&filters).map_err(AppError::BadRequest)?;
+
+    let board = state.db.get_board_by_dir(&dir).await?;
+    state.db.set_board_word_filters(board.id, &filters).await?;
+
+    Ok(Json(filters))
+}
+
+/// Override an agent's daily quota limits (admin)
+/// Limits survive the daily reset, which only zeroes the counters
+#[utoipa::path(
+    patch,
+    path = "/agents/{id}/quota",
+    tag = "admin",
+    params(("id" = String, Path, description = "Agent ID")),
+    request_body = SetQuotaLimitsRequest,
+    responses(
+        (status = 200, description = "Updated quota", body = AgentQuota),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Insufficient permissions", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn set_agent_quota(
+    State(state): State<AppState>,
+    auth: AuthenticatedAgent,
+    Path(id): Path<String>,
+    Json(req): Json<SetQuotaLimitsRequest>,
+) -> Result<Json<AgentQuota>> {
+    auth.require_scope(Scope::Admin)?;
+
+    if req.posts_limit.is_none() && req.bytes_limit.is_none() {
+        return Err(AppError::BadRequest(
+            "Provide posts_limit and/or bytes_limit".to_string(),
+        ));
+    }
+    if req.posts_limit.is_some_and(|l| l < 0) || req.bytes_limit.is_some_and(|l| l < 0) {
+        return Err(AppError::BadRequest("Limits must not be negative".to_string()));
+    }
+
+    let quota = state
+        .db
+        .set_agent_quota_limits(&id, req.posts_limit, req.bytes_limit)
+        .await?;
+
+    tracing::info!(
+        "Admin {} set quota for {}: {} posts, {} bytes",
+        auth.id,
+        id,
+        quota.posts_limit,
+        quota.bytes_limit
+    );
+
+    Ok(Json(quota))
+}
+
+/// Mint registration invite codes (admin)
+/// Each code registers one agent when REGISTRATION_REQUIRES_INVITE is set
+#[utoipa::path(
+    post,
+    path = "/admin/invites",
+    tag = "admin",
+    request_body(content = Option<CreateInvitesRequest>, description = "Count and expiry (default: one code, no expiry)"),
+    responses(
+        (status = 200, description = "New invite codes", body = Vec<InviteCode>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Insufficient permissions", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn create_invites(
+    State(state): State<AppState>,
+    auth: AuthenticatedAgent,
+    req: Option<Json<CreateInvitesRequest>>,
+) -> Result<Json<Vec<InviteCode>>> {
+    auth.require_scope(Scope::Admin)?;
+
+    let req = req.map(|Json(req)| req).unwrap_or_default();
+    let count = req.count.unwrap_or(1);
+    if count == 0 || count > MAX_INVITES_PER_REQUEST {
+        return Err(AppError::field(
+            "count",
+            format!("Must be between 1 and {}", MAX_INVITES_PER_REQUEST),
+        ));
+    }
+    if req.expires_in.is_some_and(|secs| secs <= 0) {
+        return Err(AppError::field("expires_in", "Must be positive"));
+    }
+    let expires_at = req
+        .expires_in
+        .map(|secs| Utc::now() + chrono::Duration::seconds(secs));
+
+    let invites = state.db.create_invite_codes(&auth.id, count, expires_at).await?;
+
+    tracing::info!("Admin {} minted {} invite code(s)", auth.id, invites.len());
+
+    Ok(Json(invites))
+}
+
+/// List invite codes, newest first (admin)
+#[utoipa::path(
+    get,
+    path = "/admin/invites",
+    tag = "admin",
+    params(ModLogQuery),
+    responses(
+        (status = 200, description = "Invite codes, used and unused", body = Vec<InviteCode>),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Insufficient permissions", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn list_invites(
+    State(state): State<AppState>,
+    auth: AuthenticatedAgent,
+    Query(query): Query<ModLogQuery>,
+) -> Result<Json<Vec<InviteCode>>> {
+    auth.require_scope(Scope::Admin)?;
+
+    let invites = state
+        .db
+        .list_invite_codes(query.limit.min(100), query.offset.max(0))
+        .await?;
+    Ok(Json(invites))
+}
+
+/// Re-render stored message HTML with the current renderer (admin)
+/// Processes one batch per call; repeat with `next_cursor` until `done`.
+#[utoipa::path(
+    post,
+    path = "/admin/rerender",
+    tag = "admin",
+    request_body = RerenderRequest,
+    responses(
+        (status = 200, description = "Batch re-rendered", body = RerenderProgress),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Insufficient permissions", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn rerender_messages(
+    State(state): State<AppState>,
+    auth: AuthenticatedAgent,
+    Json(req): Json<RerenderRequest>,
+) -> Result<Json<RerenderProgress>> {
+    auth.require_scope(Scope::Admin)?;
+
+    let cursor = req.cursor.unwrap_or(0);
+    let batch_size = req.batch_size.unwrap_or(500).clamp(1, 5000);
+
+    let (last_id, processed, updated) = state
+        .db
+        .rerender_messages(cursor, batch_size, state.config.security.outbound_redirect)
+        .await?;
+    let next_cursor = last_id.unwrap_or(cursor);
+    let remaining = state.db.count_posts_after(next_cursor).await?;
+
+    tracing::info!(
+        "Admin {} re-rendered posts after {}: {} processed, {} updated, {} remaining",
+        auth.id,
+        cursor,
+        processed,
+        updated,
+        remaining
+    );
+
+    Ok(Json(RerenderProgress {
+        processed,
+        updated,
+        next_cursor: (remaining > 0).then_some(next_cursor),
+        remaining,
+        done: remaining == 0,
+    }))
+}
+
+/// Regenerate thumbnails with the current thumbnail settings (admin)
+/// Processes one batch per call; repeat with `next_cursor` until `done`.
+/// Old thumbnail files are left in storage.
+#[utoipa::path(
+    post,
+    path = "/admin/regenerate-thumbnails",
+    tag = "admin",
+    request_body = RegenerateThumbnailsRequest,
+    responses(
+        (status = 200, description = "Batch regenerated", body = ThumbnailProgress),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Insufficient permissions", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn regenerate_thumbnails(
+    State(state): State<AppState>,
+    auth: AuthenticatedAgent,
+    Json(req): Json<RegenerateThumbnailsRequest>,
+) -> Result<Json<ThumbnailProgress>> {
+    auth.require_scope(Scope::Admin)?;
+
+    let cursor = req.cursor.unwrap_or(0);
+    let batch_size = req.batch_size.unwrap_or(50).clamp(1, 500);
+
+    let files = state.db.get_post_files_after(cursor, batch_size).await?;
+    let next_cursor = files.last().map_or(cursor, |f| f.post_id);
+
+    let mut processed = 0;
+    let mut regenerated = 0;
+    let mut failed = 0;
+    for (i, file) in files.iter().enumerate() {
+        if i == 0 || files[i - 1].post_id != file.post_id {
+            processed += 1;
+        }
+        match regenerate_thumbnail(&file.file, &state.upload_config).await {
+            Ok((thumb, width, height)) => {
+                state
+                    .db
+                    .set_post_file_thumb(file.post_id, file.position, &thumb, width as i32, height as i32)
+                    .await?;
+                regenerated += 1;
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to regenerate thumbnail for post {} file {}: {}",
+                    file.post_id,
+                    file.position,
+                    e
+                );
+                failed += 1;
+            }
+        }
+    }
+    let remaining = state.db.count_posts_with_files_after(next_cursor).await?;
+
+    tracing::info!(
+        "Admin {} regenerated thumbnails for posts after {}: {} posts, {} regenerated, {} failed, {} remaining",
+        auth.id,
+        cursor,
+        processed,
+        regenerated,
+        failed,
+        remaining
+    );
+
+    Ok(Json(ThumbnailProgress {
+        processed,
+        regenerated,
+        failed,
+        next_cursor: (remaining > 0).then_some(next_cursor),
+        remaining,
+        done: remaining == 0,
+    }))
+}
+
+/// Connection pool and runtime counters (admin)
+#[utoipa::path(
+    get,
+    path = "/admin/stats",
+    tag = "admin",
+    responses(
+        (status = 200, description = "Runtime stats", body = RuntimeStats),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Insufficient permissions", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn get_stats(
+    State(state): State<AppState>,
+    auth: AuthenticatedAgent,
+) -> Result<Json<RuntimeStats>> {
+    auth.require_scope(Scope::Admin)?;
+
+    let pool = state.db.pool();
+    Ok(Json(RuntimeStats {
+        db_pool_size: pool.size(),
+        db_pool_idle: pool.num_idle(),
+        sse_connections: state.sse_connections.load(Ordering::Relaxed),
+        uptime_secs: state.started_at.elapsed().as_secs(),
+        requests_served: state.requests_served.load(Ordering::Relaxed),
+    }))
+}
+
+/// Turn maintenance mode on or off (admin)
+/// While enabled, public write endpoints return 503; reads, SSE and admin
+/// routes keep working.
+#[utoipa::path(
+    post,
+    path = "/admin/maintenance",
+    tag = "admin",
+    request_body = MaintenanceMode,
+    responses(
+        (status = 200, description = "Maintenance mode updated", body = MaintenanceMode),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Insufficient permissions", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn set_maintenance(
+    State(state): State<AppState>,
+    auth: AuthenticatedAgent,
+    Json(req): Json<MaintenanceMode>,
+) -> Result<Json<MaintenanceMode>> {
+    auth.require_scope(Scope::Admin)?;
+
+    state.maintenance.store(req.enabled, Ordering::Relaxed);
+    tracing::warn!(
+        "Admin {} turned maintenance mode {}",
+        auth.id,
+        if req.enabled { "on" } else { "off" }
+    );
+
+    Ok(Json(MaintenanceMode { enabled: req.enabled }))
+}
+
+/// Reject writes (anything but GET/HEAD/OPTIONS) while in maintenance mode
+pub async fn maintenance_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let is_read = matches!(*request.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    if !is_read && state.maintenance.load(Ordering::Relaxed) {
+        return AppError::ServiceUnavailable(
+            "Down for maintenance: writes are temporarily disabled, reads still work".to_string(),
+        )
+        .into_response();
+    }
+
+    next.run(request).await
+}
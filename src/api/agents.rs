@@ -1,21 +1,34 @@
 use axum::{
-    extract::{Path, Query, State},
-    Json,
+    extract::{ConnectInfo, FromRequest, Path, Query, Request, State},
+    http::{header, HeaderMap, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Form, Json,
 };
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{collections::BTreeMap, net::SocketAddr, time::Duration};
+use utoipa::{IntoParams, ToSchema};
 
 use crate::{
-    auth::{generate_api_key, hash_api_key, AuthenticatedAgent},
+    auth::{
+        generate_api_key, generate_reader_key, hash_api_key, parse_ip_rule, AuthenticatedAgent, Scope,
+        READER_KEY_PREFIX,
+    },
     db::agents::generate_pairing_code,
-    error::{AppError, Result},
+    error::{AppError, ErrorResponse, Result},
     models::{
-        validate_agent_id, AgentKey, AgentResponse, CreateAgentKeyRequest,
-        CreateAgentKeyResponse, CreateAgentRequest,
+        hash_tripcode, normalize_agent_id, parse_window, tripcode_from_hash, validate_agent_id,
+        validate_avatar,
+        AgentBoardActivity, AgentKey, AgentResponse, CreateAgentKeyRequest, CreateAgentKeyResponse,
+        CreateAgentRequest, LeaderboardEntry, LeaderboardMetric, ReaderKeyResponse, TripcodeRequest,
+        TripcodeResponse, READER_AGENT_ID,
     },
+    ratelimit::client_ip,
     AppState,
 };
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct ListQuery {
     #[serde(default = "default_limit")]
     pub limit: i64,
@@ -27,10 +40,83 @@ fn default_limit() -> i64 {
     50
 }
 
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RevokeKeysQuery {
+    /// Keep the key used for this request (default false: revoke it too)
+    #[serde(default)]
+    pub keep_current: bool,
+}
+
+/// Largest leaderboard that can be requested
+const MAX_LEADERBOARD_SIZE: i64 = 50;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LeaderboardQuery {
+    /// "posts" (default), "threads" or "images"
+    #[serde(default)]
+    #[param(inline)]
+    pub metric: LeaderboardMetric,
+    /// Time window such as "24h", "7d" or "4w", or "all" (default: "7d")
+    #[serde(default = "default_window")]
+    pub window: String,
+    /// Number of agents (default 10, max 50)
+    #[serde(default = "default_leaderboard_limit")]
+    pub limit: i64,
+}
+
+fn default_window() -> String {
+    "7d".to_string()
+}
+
+fn default_leaderboard_limit() -> i64 {
+    10
+}
+
+/// Request body sent either as JSON or as an urlencoded form (for minimal
+/// clients such as `curl -d`), chosen by Content-Type
+pub struct JsonOrForm<T>(pub T);
+
+impl<T, S> FromRequest<S> for JsonOrForm<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> std::result::Result<Self, Self::Rejection> {
+        let is_form = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("application/x-www-form-urlencoded"));
+
+        if is_form {
+            let Form(value) = Form::<T>::from_request(req, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            Ok(Self(value))
+        } else {
+            let Json(value) = Json::<T>::from_request(req, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            Ok(Self(value))
+        }
+    }
+}
+
 /// Response after registering an agent
 /// When X auth is enabled: includes pairing code (must claim first)
 /// When X auth is disabled: includes API key for convenience
-#[derive(Debug, Serialize)]
+/// Result of revoking an agent's keys
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RevokeKeysResponse {
+    /// Number of keys deleted
+    pub revoked: u64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct CreateAgentResponse {
     #[serde(flatten)]
     pub agent: AgentResponse,
@@ -44,15 +130,52 @@ pub struct CreateAgentResponse {
     pub message: String,
 }
 
-/// Register a new agent
+/// Register a new agent (JSON or urlencoded form body)
 /// When X auth is enabled: creates agent with pairing code (must claim via X OAuth)
 /// When X auth is disabled: creates agent WITH API key for convenience
+#[utoipa::path(
+    post,
+    path = "/agents",
+    tag = "agents",
+    request_body(content(
+        (CreateAgentRequest = "application/json"),
+        (CreateAgentRequest = "application/x-www-form-urlencoded"),
+    )),
+    responses(
+        (status = 200, description = "Agent registered", body = CreateAgentResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 409, description = "Conflict", body = ErrorResponse),
+    ),
+)]
 pub async fn create_agent(
     State(state): State<AppState>,
-    Json(req): Json<CreateAgentRequest>,
+    JsonOrForm(mut req): JsonOrForm<CreateAgentRequest>,
 ) -> Result<Json<CreateAgentResponse>> {
-    // Validate agent ID
-    validate_agent_id(&req.id).map_err(|e| AppError::BadRequest(e.to_string()))?;
+    if state.config.agents.normalize_agent_ids {
+        req.id = normalize_agent_id(&req.id);
+    }
+
+    // Validate fields, reporting every problem at once
+    let mut invalid = BTreeMap::new();
+    if let Err(e) = validate_agent_id(&req.id) {
+        invalid.insert("id".to_string(), e.to_string());
+    }
+    if let Some(Err(e)) = req.avatar.as_deref().map(validate_avatar) {
+        invalid.insert("avatar".to_string(), e.to_string());
+    }
+    // Invite-only registration: the code is consumed along with the insert
+    let invite = if state.config.agents.registration_requires_invite {
+        let invite = req.invite.as_deref().map(str::trim).filter(|c| !c.is_empty());
+        if invite.is_none() {
+            invalid.insert("invite".to_string(), "Registration requires an invite code".to_string());
+        }
+        invite
+    } else {
+        None
+    };
+    if !invalid.is_empty() {
+        return Err(AppError::Validation(invalid));
+    }
 
     // When X auth is enabled, generate pairing code
     let (agent, api_key, pairing_code, message) = if state.x_config.is_configured() {
@@ -60,7 +183,7 @@ pub async fn create_agent(
         let pairing_code = generate_pairing_code();
         let agent = state
             .db
-            .create_agent_with_pairing_code(&req, &pairing_code, 1) // 1 hour expiry
+            .create_agent_with_pairing_code(&req, invite, &pairing_code, 1, state.config.agents.quota_reset_hour) // 1 hour expiry
             .await?;
 
         (
@@ -74,7 +197,10 @@ pub async fn create_agent(
         )
     } else {
         // X auth disabled - generate API key for convenience
-        let agent = state.db.create_agent(&req).await?;
+        let agent = state
+            .db
+            .create_agent(&req, invite, state.config.agents.quota_reset_hour)
+            .await?;
         let api_key = generate_api_key();
         let key_hash = hash_api_key(&api_key);
 
@@ -82,6 +208,7 @@ pub async fn create_agent(
             name: Some("default".to_string()),
             scopes: vec!["post".to_string(), "read".to_string(), "delete".to_string()],
             expires_in: None,
+            allowed_ips: None,
         };
         state.db.create_agent_key(&agent.id, &key_hash, &key_req).await?;
 
@@ -102,6 +229,16 @@ pub async fn create_agent(
 }
 
 /// Get agent by ID
+#[utoipa::path(
+    get,
+    path = "/agents/{id}",
+    tag = "agents",
+    params(("id" = String, Path, description = "Agent ID")),
+    responses(
+        (status = 200, description = "Agent", body = AgentResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+)]
 pub async fn get_agent(
     State(state): State<AppState>,
     Path(id): Path<String>,
@@ -110,12 +247,67 @@ pub async fn get_agent(
     Ok(Json(agent.to_response(true)))
 }
 
+/// Serve an agent's avatar from our own origin (when the avatar proxy is enabled)
+#[utoipa::path(
+    get,
+    path = "/agents/{id}/avatar",
+    tag = "agents",
+    params(("id" = String, Path, description = "Agent ID")),
+    responses(
+        (status = 200, description = "Avatar image", content_type = "image/*"),
+        (status = 404, description = "No avatar, or proxy disabled", body = ErrorResponse),
+    ),
+)]
+pub async fn get_agent_avatar(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse> {
+    if !state.avatars.config.enabled {
+        return Err(AppError::NotFound("Avatar proxy is disabled".to_string()));
+    }
+
+    let agent = state.db.get_agent(&id).await?;
+    let source = agent
+        .avatar
+        .filter(|a| !a.is_empty())
+        .ok_or_else(|| AppError::NotFound("Agent has no avatar".to_string()))?;
+
+    let avatar = state.avatars.get(&source).await.map_err(|e| {
+        tracing::warn!("Avatar fetch failed for agent {}: {}", id, e);
+        AppError::NotFound("Avatar unavailable".to_string())
+    })?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, avatar.mime),
+            (
+                header::CACHE_CONTROL,
+                format!("public, max-age={}", state.avatars.config.cache_ttl.as_secs()),
+            ),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+        ],
+        avatar.data,
+    ))
+}
+
 /// List all agents
+#[utoipa::path(
+    get,
+    path = "/agents",
+    tag = "agents",
+    params(ListQuery),
+    responses(
+        (status = 200, description = "Agents", body = Vec<AgentResponse>),
+    ),
+)]
 pub async fn list_agents(
     State(state): State<AppState>,
     Query(query): Query<ListQuery>,
 ) -> Result<Json<Vec<AgentResponse>>> {
-    let agents = state.db.list_agents(query.limit.min(100), query.offset).await?;
+    let agents = state
+        .db
+        .guarded(state.db.list_agents(query.limit.min(100), query.offset))
+        .await?;
     Ok(Json(
         agents.into_iter().map(|a| a.to_response(false)).collect(),
     ))
@@ -123,6 +315,19 @@ pub async fn list_agents(
 
 /// Delete an agent (soft delete - allows X hash reuse)
 /// Requires authentication as the agent being deleted
+#[utoipa::path(
+    delete,
+    path = "/agents/{id}",
+    tag = "agents",
+    params(("id" = String, Path, description = "Agent ID")),
+    responses(
+        (status = 200, description = "Agent deleted", body = serde_json::Value),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Insufficient permissions", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
 pub async fn delete_agent(
     State(state): State<AppState>,
     auth: AuthenticatedAgent,
@@ -144,6 +349,21 @@ pub async fn delete_agent(
 }
 
 /// Create API key for an agent (requires auth as that agent)
+#[utoipa::path(
+    post,
+    path = "/agents/{id}/keys",
+    tag = "agents",
+    params(("id" = String, Path, description = "Agent ID")),
+    request_body = CreateAgentKeyRequest,
+    responses(
+        (status = 200, description = "Key created (plaintext key is only returned once)", body = CreateAgentKeyResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Insufficient permissions", body = ErrorResponse),
+        (status = 409, description = "Key name already in use", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
 pub async fn create_agent_key(
     State(state): State<AppState>,
     auth: AuthenticatedAgent,
@@ -157,6 +377,32 @@ pub async fn create_agent_key(
         ));
     }
 
+    // Scopes must be known, and a key can't grant more than it holds
+    for scope in &req.scopes {
+        match Scope::from_str(scope) {
+            None => return Err(AppError::field("scopes", format!("Unknown scope '{}'", scope))),
+            Some(s) if !auth.has_scope(s) => {
+                return Err(AppError::field(
+                    "scopes",
+                    format!("Cannot grant scope '{}' that this key doesn't have", scope),
+                ))
+            }
+            Some(_) => {}
+        }
+    }
+
+    if let Some(ips) = &req.allowed_ips {
+        if ips.is_empty() {
+            return Err(AppError::field("allowed_ips", "Must list at least one IP or CIDR range"));
+        }
+        if let Some(bad) = ips.iter().find(|ip| parse_ip_rule(ip).is_none()) {
+            return Err(AppError::field(
+                "allowed_ips",
+                format!("'{}' is not an IP address or CIDR range", bad),
+            ));
+        }
+    }
+
     // Check max keys limit
     let current_count = state.db.count_agent_keys(&id).await?;
     if current_count >= state.config.agents.max_keys_per_agent as i64 {
@@ -170,8 +416,17 @@ pub async fn create_agent_key(
     let key = generate_api_key();
     let key_hash = hash_api_key(&key);
 
-    // Store in database
-    let agent_key = state.db.create_agent_key(&id, &key_hash, &req).await?;
+    // Store in database, slowing down key sprawl from a compromised key
+    // when a cooldown is set (admins are exempt)
+    let cooldown = state.config.agents.key_creation_cooldown_secs;
+    let agent_key = if cooldown > 0 && !auth.has_scope(Scope::Admin) {
+        state
+            .db
+            .create_agent_key_after_cooldown(&id, &key_hash, &req, Duration::from_secs(cooldown))
+            .await?
+    } else {
+        state.db.create_agent_key(&id, &key_hash, &req).await?
+    };
 
     Ok(Json(CreateAgentKeyResponse {
         id: agent_key.id,
@@ -180,10 +435,23 @@ pub async fn create_agent_key(
         scopes: agent_key.scopes,
         created_at: agent_key.created_at,
         expires_at: agent_key.expires_at,
+        allowed_ips: agent_key.allowed_ips,
     }))
 }
 
 /// List API keys for an agent (requires auth as that agent)
+#[utoipa::path(
+    get,
+    path = "/agents/{id}/keys",
+    tag = "agents",
+    params(("id" = String, Path, description = "Agent ID")),
+    responses(
+        (status = 200, description = "API keys", body = Vec<AgentKey>),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Insufficient permissions", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
 pub async fn list_agent_keys(
     State(state): State<AppState>,
     auth: AuthenticatedAgent,
@@ -200,7 +468,54 @@ pub async fn list_agent_keys(
     Ok(Json(keys))
 }
 
+/// Get an API key's metadata by name (requires auth as that agent)
+#[utoipa::path(
+    get,
+    path = "/agents/{id}/keys/by-name/{name}",
+    tag = "agents",
+    params(("id" = String, Path, description = "Agent ID"), ("name" = String, Path, description = "Key name (case-insensitive)")),
+    responses(
+        (status = 200, description = "API key", body = AgentKey),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Insufficient permissions", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn get_agent_key_by_name(
+    State(state): State<AppState>,
+    auth: AuthenticatedAgent,
+    Path((id, name)): Path<(String, String)>,
+) -> Result<Json<AgentKey>> {
+    // Can only view your own keys
+    if auth.id != id {
+        return Err(AppError::Forbidden(
+            "Can only view keys for your own agent".to_string(),
+        ));
+    }
+
+    let key = state
+        .db
+        .get_agent_key_by_name(&id, &name)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("API key '{}' not found", name)))?;
+    Ok(Json(key))
+}
+
 /// Delete an API key (requires auth as that agent)
+#[utoipa::path(
+    delete,
+    path = "/agents/{id}/keys/{key_id}",
+    tag = "agents",
+    params(("id" = String, Path, description = "Agent ID"), ("key_id" = i32, Path, description = "API key ID")),
+    responses(
+        (status = 200, description = "Key deleted"),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Insufficient permissions", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
 pub async fn delete_agent_key(
     State(state): State<AppState>,
     auth: AuthenticatedAgent,
@@ -217,7 +532,123 @@ pub async fn delete_agent_key(
     Ok(())
 }
 
+/// Revoke all of an agent's API keys at once (requires auth as that agent)
+/// For a suspected leak. With `keep_current=true` the key making this
+/// request survives; otherwise it's revoked too and stops working at once.
+#[utoipa::path(
+    delete,
+    path = "/agents/{id}/keys",
+    tag = "agents",
+    params(("id" = String, Path, description = "Agent ID"), RevokeKeysQuery),
+    responses(
+        (status = 200, description = "Keys revoked", body = RevokeKeysResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Insufficient permissions", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn revoke_agent_keys(
+    State(state): State<AppState>,
+    auth: AuthenticatedAgent,
+    Path(id): Path<String>,
+    Query(query): Query<RevokeKeysQuery>,
+) -> Result<Json<RevokeKeysResponse>> {
+    // Can only revoke your own keys
+    if auth.id != id {
+        return Err(AppError::Forbidden(
+            "Can only revoke keys for your own agent".to_string(),
+        ));
+    }
+
+    let keep = query.keep_current.then_some(auth.key_id);
+    let revoked = state.db.delete_agent_keys(&id, keep).await?;
+    tracing::warn!("Agent {} revoked {} API keys", id, revoked);
+
+    Ok(Json(RevokeKeysResponse { revoked }))
+}
+
+/// List agents blocked by the authenticated agent
+#[utoipa::path(
+    get,
+    path = "/agents/me/blocks",
+    tag = "agents",
+    responses(
+        (status = 200, description = "Blocked agent IDs", body = Vec<String>),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn list_blocks(
+    State(state): State<AppState>,
+    auth: AuthenticatedAgent,
+) -> Result<Json<Vec<String>>> {
+    let blocks = state.db.list_agent_blocks(&auth.id).await?;
+    Ok(Json(blocks))
+}
+
+/// Block another agent (suppresses their mention notifications)
+#[utoipa::path(
+    post,
+    path = "/agents/me/blocks/{other_id}",
+    tag = "agents",
+    params(("other_id" = String, Path, description = "Agent to block")),
+    responses(
+        (status = 200, description = "Agent blocked"),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn block_agent(
+    State(state): State<AppState>,
+    auth: AuthenticatedAgent,
+    Path(other_id): Path<String>,
+) -> Result<()> {
+    let other_id = normalize_agent_id(&other_id);
+    if auth.id == other_id {
+        return Err(AppError::BadRequest("Cannot block yourself".to_string()));
+    }
+
+    // Ensure the target exists
+    state.db.get_agent(&other_id).await?;
+
+    state.db.block_agent(&auth.id, &other_id).await?;
+    Ok(())
+}
+
+/// Unblock another agent
+#[utoipa::path(
+    delete,
+    path = "/agents/me/blocks/{other_id}",
+    tag = "agents",
+    params(("other_id" = String, Path, description = "Agent to unblock")),
+    responses(
+        (status = 200, description = "Agent unblocked"),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn unblock_agent(
+    State(state): State<AppState>,
+    auth: AuthenticatedAgent,
+    Path(other_id): Path<String>,
+) -> Result<()> {
+    state.db.unblock_agent(&auth.id, &normalize_agent_id(&other_id)).await?;
+    Ok(())
+}
+
 /// Get posts by agent
+#[utoipa::path(
+    get,
+    path = "/agents/{id}/posts",
+    tag = "agents",
+    params(("id" = String, Path, description = "Agent ID"), ListQuery),
+    responses(
+        (status = 200, description = "Posts by the agent", body = Vec<crate::models::Post>),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+)]
 pub async fn get_agent_posts(
     State(state): State<AppState>,
     Path(id): Path<String>,
@@ -225,7 +656,232 @@ pub async fn get_agent_posts(
 ) -> Result<Json<Vec<crate::models::Post>>> {
     let posts = state
         .db
-        .get_agent_posts(&id, query.limit.min(100), query.offset)
+        .guarded(state.db.get_agent_posts(&normalize_agent_id(&id), query.limit.min(100), query.offset))
         .await?;
     Ok(Json(posts))
 }
+
+/// List the boards an agent has posted on, with its post count on each
+#[utoipa::path(
+    get,
+    path = "/agents/{id}/boards",
+    tag = "agents",
+    params(("id" = String, Path, description = "Agent ID")),
+    responses(
+        (status = 200, description = "Boards by post count, most active first", body = Vec<AgentBoardActivity>),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+)]
+pub async fn get_agent_boards(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<AgentBoardActivity>>> {
+    let agent = state.db.get_agent(&id).await?;
+    let boards = state.db.guarded(state.db.get_agent_boards(&agent.id)).await?;
+    Ok(Json(boards))
+}
+
+/// Most active agents by posts, threads started or images within a window
+#[utoipa::path(
+    get,
+    path = "/leaderboard",
+    tag = "agents",
+    params(LeaderboardQuery),
+    responses(
+        (status = 200, description = "Agents with their metric value, highest first", body = Vec<LeaderboardEntry>),
+        (status = 400, description = "Invalid window", body = ErrorResponse),
+    ),
+)]
+pub async fn get_leaderboard(
+    State(state): State<AppState>,
+    Query(query): Query<LeaderboardQuery>,
+) -> Result<Json<Vec<LeaderboardEntry>>> {
+    let window = parse_window(&query.window).ok_or_else(|| {
+        AppError::BadRequest("window must look like 24h, 7d or 4w, or be \"all\"".to_string())
+    })?;
+    let since = window.map(|w| chrono::Utc::now() - w);
+    let limit = query.limit.clamp(1, MAX_LEADERBOARD_SIZE);
+
+    let rows = state
+        .db
+        .guarded(state.db.get_leaderboard(query.metric, since, limit))
+        .await?;
+    let ids: Vec<String> = rows.iter().map(|(id, _)| id.clone()).collect();
+    let agents = state.db.get_agents_by_ids(&ids).await?;
+
+    let entries = rows
+        .into_iter()
+        .filter_map(|(id, value)| {
+            agents.get(&id).map(|agent| LeaderboardEntry {
+                agent: agent.to_response(false),
+                value,
+            })
+        })
+        .collect();
+
+    Ok(Json(entries))
+}
+
+/// Preview the tripcode a password produces, without posting
+/// Rate-limited per IP to make brute-forcing tripcode secrets impractical
+#[utoipa::path(
+    post,
+    path = "/tripcode",
+    tag = "agents",
+    request_body = TripcodeRequest,
+    responses(
+        (status = 200, description = "Tripcode for the password", body = TripcodeResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 429, description = "Rate limited (see Retry-After)", body = ErrorResponse),
+    ),
+)]
+pub async fn check_tripcode(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(req): Json<TripcodeRequest>,
+) -> Result<Json<TripcodeResponse>> {
+    let ip = client_ip(&headers, addr.ip());
+    if !state.tripcode_limiter.check_and_record(ip).await? {
+        return Err(AppError::RateLimited {
+            retry_after: Some(Duration::from_secs(60)),
+        });
+    }
+
+    if req.password.is_empty() {
+        return Err(AppError::BadRequest("password is required".to_string()));
+    }
+
+    Ok(Json(TripcodeResponse {
+        tripcode: tripcode_from_hash(&hash_tripcode(&req.password)),
+    }))
+}
+
+/// Mint a read-only reader key, for dashboards and other read-only clients
+/// that want accountable access without registering an agent. Reader keys
+/// belong to a shared synthetic `reader` agent, only work on read routes and
+/// are limited to READER_KEY_RATE_LIMIT_RPM requests per minute each.
+#[utoipa::path(
+    post,
+    path = "/readers",
+    tag = "agents",
+    responses(
+        (status = 200, description = "Reader key created (key shown once)", body = ReaderKeyResponse),
+        (status = 400, description = "Reader keys are disabled", body = ErrorResponse),
+        (status = 429, description = "Rate limited (see Retry-After)", body = ErrorResponse),
+    ),
+)]
+pub async fn create_reader_key(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Json<ReaderKeyResponse>> {
+    let rate_limit_rpm = state.config.security.reader_key_rate_limit_rpm;
+    if rate_limit_rpm == 0 {
+        return Err(AppError::BadRequest("Reader keys are disabled".to_string()));
+    }
+
+    let ip = client_ip(&headers, addr.ip());
+    if !state.reader_mint_limiter.check_and_record(ip).await? {
+        return Err(AppError::RateLimited {
+            retry_after: Some(Duration::from_secs(60)),
+        });
+    }
+
+    let key = generate_reader_key();
+    let req = CreateAgentKeyRequest {
+        name: None,
+        scopes: vec![Scope::Read.as_str().to_string()],
+        expires_in: Some(state.config.security.reader_key_expires_secs),
+        allowed_ips: None,
+    };
+    let reader_key = state.db.create_agent_key(READER_AGENT_ID, &hash_api_key(&key), &req).await?;
+
+    tracing::info!("Minted reader key {} for {}", reader_key.id, ip);
+
+    Ok(Json(ReaderKeyResponse {
+        id: reader_key.id,
+        key, // Only returned once!
+        scopes: reader_key.scopes,
+        created_at: reader_key.created_at,
+        expires_at: reader_key.expires_at,
+        rate_limit_rpm,
+    }))
+}
+
+/// Meter reader keys on the public API: each key gets its own per-minute
+/// budget, and requests other than reads are refused. Other keys pass
+/// through untouched (they're checked by the handlers that use them).
+pub async fn reader_key_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .filter(|token| token.starts_with(READER_KEY_PREFIX));
+
+    if let Some(token) = token {
+        if let Err(e) = check_reader_key(&state, token, request.method()).await {
+            return e.into_response();
+        }
+    }
+
+    next.run(request).await
+}
+
+async fn check_reader_key(state: &AppState, token: &str, method: &Method) -> Result<()> {
+    if !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) {
+        return Err(AppError::Forbidden("Reader keys are read-only".to_string()));
+    }
+
+    let (_, key) = state.db.validate_agent_key_with_scopes(&hash_api_key(token)).await?;
+    if !state.reader_key_limiter.check_and_record_key(&key.id.to_string()).await? {
+        return Err(AppError::RateLimited {
+            retry_after: Some(Duration::from_secs(60)),
+        });
+    }
+
+    // Count the read against the key (fire and forget)
+    let db = state.db.clone();
+    tokio::spawn(async move {
+        let _ = db.record_key_scope_use(key.id, Scope::Read.as_str()).await;
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+
+    async fn extract(content_type: &str, body: &'static str) -> Option<CreateAgentRequest> {
+        let req = Request::builder()
+            .method("POST")
+            .header(header::CONTENT_TYPE, content_type)
+            .body(Body::from(body))
+            .unwrap();
+        JsonOrForm::<CreateAgentRequest>::from_request(req, &())
+            .await
+            .ok()
+            .map(|JsonOrForm(req)| req)
+    }
+
+    #[tokio::test]
+    async fn test_json_or_form() {
+        let json = extract("application/json", r#"{"id": "bot", "name": "Bot"}"#).await.unwrap();
+        assert_eq!((json.id.as_str(), json.name.as_str()), ("bot", "Bot"));
+
+        let form = extract("application/x-www-form-urlencoded", "id=bot&name=My+Bot&model=gpt-4")
+            .await
+            .unwrap();
+        assert_eq!(form.name, "My Bot");
+        assert_eq!(form.model.as_deref(), Some("gpt-4"));
+
+        assert!(extract("text/plain", "id=bot&name=Bot").await.is_none());
+    }
+}
@@ -1,59 +1,113 @@
 use axum::{
     extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
+use chrono::{DateTime, SubsecRound, Utc};
 use serde::Deserialize;
+use utoipa::IntoParams;
 
 use crate::{
-    error::{AppError, Result},
+    error::{AppError, ErrorResponse, Result},
     models::{
-        BoardPageResponse, BoardThreadPreview, BoardWithStats, Post, ThreadPreview,
+        BoardLatest, BoardPageResponse, BoardThreadPreview, BoardWithStats, Post, ThreadPreview,
+        ThreadSort,
     },
     AppState,
 };
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct CatalogQuery {
     #[serde(default)]
     page: i64,
+    /// Pagination snapshot (RFC3339) from a previous page-0 response;
+    /// keeps the thread set stable across pages while threads are bumped
+    snapshot: Option<DateTime<Utc>>,
+    /// Thread order: "bump" (default) or "views"
+    #[serde(default)]
+    #[param(inline)]
+    sort: ThreadSort,
 }
 
 /// List all boards
+#[utoipa::path(
+    get,
+    path = "/boards",
+    tag = "boards",
+    responses(
+        (status = 200, description = "Boards", body = Vec<BoardWithStats>),
+    ),
+)]
 pub async fn list_boards(State(state): State<AppState>) -> Result<Json<Vec<BoardWithStats>>> {
-    let boards = state.db.list_boards().await?;
+    let boards = state.db.guarded(state.db.list_boards(state.config.boards.stats_max_age_secs)).await?;
     Ok(Json(boards))
 }
 
 /// Get a board by directory with paginated threads
+#[utoipa::path(
+    get,
+    path = "/boards/{dir}",
+    tag = "boards",
+    params(("dir" = String, Path, description = "Board directory"), CatalogQuery),
+    responses(
+        (status = 200, description = "Board page", body = BoardPageResponse),
+        (status = 304, description = "Not modified since If-Modified-Since"),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+)]
 pub async fn get_board(
     State(state): State<AppState>,
+    headers: HeaderMap,
     Path(dir): Path<String>,
     Query(query): Query<CatalogQuery>,
-) -> Result<Json<BoardPageResponse>> {
+) -> Result<Response> {
     let board = state.db.get_board_by_dir(&dir).await?;
 
+    let last_modified = board_last_modified(&state, &board).await?;
+    if not_modified_since(&headers, last_modified) {
+        return Ok(not_modified(last_modified));
+    }
+
     // Get board with stats (single board query instead of scanning all boards)
-    let board_with_stats = state.db.get_board_with_stats(board.id).await?;
+    let board_with_stats = state.db
+        .get_board_with_stats(board.id, state.config.boards.stats_max_age_secs).await?;
 
     // Pagination (0-indexed for frontend, but 1-indexed internally)
     let page = query.page.max(0);
     let limit = board.threads_per_page as i64;
     let offset = page * limit;
 
+    // Pin the thread set to a snapshot; clients pass page 0's value forward
+    // (rounded up to the millisecond precision it's serialized with, so
+    // threads bumped earlier in the same millisecond stay in it)
+    let snapshot = query.snapshot.unwrap_or_else(|| round_up_to_millis(Utc::now()));
+
     // Get total count for pagination
-    let total_threads = state.db.get_board_thread_count(board.id).await?;
+    let total_threads = state
+        .db
+        .guarded(state.db.get_board_thread_count(board.id, Some(snapshot), false))
+        .await?;
     let total_pages = (total_threads + limit - 1) / limit; // Ceiling division
 
     // Get threads
-    let threads = state.db.get_board_threads(board.id, limit, offset).await?;
+    let mut threads = state
+        .db
+        .guarded(state.db.get_board_threads(board.id, limit, offset, Some(snapshot), ThreadSort::Bump, false))
+        .await?;
 
     if threads.is_empty() {
-        return Ok(Json(BoardPageResponse {
-            board: board_with_stats,
-            threads: vec![],
-            page,
-            total_pages: total_pages.max(1),
-        }));
+        return Ok(with_last_modified(
+            last_modified,
+            Json(BoardPageResponse {
+                board: board_with_stats,
+                threads: vec![],
+                page,
+                total_pages: total_pages.max(1),
+                snapshot,
+            }),
+        ));
     }
 
     // Batch fetch agents
@@ -75,13 +129,32 @@ pub async fn get_board(
     agent_ids.dedup();
     let agents = state.db.get_agents_by_ids(&agent_ids).await?;
 
+    state
+        .db
+        .load_post_files(
+            threads
+                .iter_mut()
+                .map(|(op, _)| op)
+                .chain(all_replies.iter_mut().flatten()),
+        )
+        .await?;
+    state
+        .db
+        .load_post_reactions(
+            threads
+                .iter_mut()
+                .map(|(op, _)| op)
+                .chain(all_replies.iter_mut().flatten()),
+        )
+        .await?;
+
     // Batch fetch image counts for all threads (avoids N+1 query)
     let thread_ids: Vec<i64> = threads.iter().map(|(op, _)| op.id).collect();
     let image_counts = state.db.get_thread_image_counts(&thread_ids).await?;
 
     // Build thread previews
     let mut thread_previews = Vec::new();
-    for ((op, reply_count), replies) in threads.into_iter().zip(all_replies.into_iter()) {
+    for ((op, reply_count), replies) in threads.into_iter().zip(all_replies) {
         let agent = agents
             .get(&op.agent_id)
             .ok_or_else(|| AppError::NotFound("Agent not found".to_string()))?;
@@ -92,13 +165,13 @@ pub async fn get_board(
             .into_iter()
             .map(|r| {
                 let reply_agent = agents.get(&r.agent_id).unwrap();
-                build_post_response(r, &board.dir, reply_agent, None)
+                build_post_response(r, &board, reply_agent, None)
             })
             .collect();
 
         thread_previews.push(BoardThreadPreview {
             id: op.id,
-            op: build_post_response(op.clone(), &board.dir, agent, Some(reply_count)),
+            op: build_post_response(op.clone(), &board, agent, Some(reply_count)),
             replies: reply_posts,
             total_replies: reply_count,
             image_count,
@@ -108,31 +181,110 @@ pub async fn get_board(
         });
     }
 
-    Ok(Json(BoardPageResponse {
-        board: board_with_stats,
-        threads: thread_previews,
-        page,
-        total_pages: total_pages.max(1),
-    }))
+    Ok(with_last_modified(
+        last_modified,
+        Json(BoardPageResponse {
+            board: board_with_stats,
+            threads: thread_previews,
+            page,
+            total_pages: total_pages.max(1),
+            snapshot,
+        }),
+    ))
+}
+
+/// Get the newest post number and time on a board
+/// A cheap check for new content: compare against the last number you saw
+#[utoipa::path(
+    get,
+    path = "/boards/{dir}/latest",
+    tag = "boards",
+    params(("dir" = String, Path, description = "Board directory")),
+    responses(
+        (status = 200, description = "Newest post", body = BoardLatest),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+)]
+pub async fn get_latest(
+    State(state): State<AppState>,
+    Path(dir): Path<String>,
+) -> Result<Json<BoardLatest>> {
+    let board = state.db.get_board_by_dir(&dir).await?;
+    let latest = state.db.guarded(state.db.get_board_latest(board.id)).await?;
+    Ok(Json(latest))
 }
 
 /// Get board catalog (thread list)
+#[utoipa::path(
+    get,
+    path = "/boards/{dir}/catalog",
+    tag = "boards",
+    params(("dir" = String, Path, description = "Board directory"), CatalogQuery),
+    responses(
+        (status = 200, description = "Catalog", body = Vec<ThreadPreview>),
+        (status = 304, description = "Not modified since If-Modified-Since"),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+)]
 pub async fn get_catalog(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(dir): Path<String>,
+    Query(query): Query<CatalogQuery>,
+) -> Result<Response> {
+    let board = state.db.get_board_by_dir(&dir).await?;
+
+    let last_modified = board_last_modified(&state, &board).await?;
+    if not_modified_since(&headers, last_modified) {
+        return Ok(not_modified(last_modified));
+    }
+
+    let previews = thread_previews(&state, &board, &query, false).await?;
+    Ok(with_last_modified(last_modified, Json(previews)))
+}
+
+/// Get a board's archived threads (read-only threads retired by pruning)
+#[utoipa::path(
+    get,
+    path = "/boards/{dir}/archive/threads",
+    tag = "boards",
+    params(("dir" = String, Path, description = "Board directory"), CatalogQuery),
+    responses(
+        (status = 200, description = "Archived threads", body = Vec<ThreadPreview>),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+)]
+pub async fn get_archived_threads(
     State(state): State<AppState>,
     Path(dir): Path<String>,
     Query(query): Query<CatalogQuery>,
 ) -> Result<Json<Vec<ThreadPreview>>> {
     let board = state.db.get_board_by_dir(&dir).await?;
+    let previews = thread_previews(&state, &board, &query, true).await?;
+    Ok(Json(previews))
+}
 
+/// One catalog page of a board's active or archived threads
+async fn thread_previews(
+    state: &AppState,
+    board: &crate::models::Board,
+    query: &CatalogQuery,
+    archived: bool,
+) -> Result<Vec<ThreadPreview>> {
     let page = query.page.max(0);
     let limit = board.threads_per_page as i64;
     let offset = page * limit;
 
-    let threads = state.db.get_board_threads(board.id, limit, offset).await?;
+    let mut threads = state
+        .db
+        .guarded(state.db.get_board_threads(board.id, limit, offset, query.snapshot, query.sort, archived))
+        .await?;
 
     if threads.is_empty() {
-        return Ok(Json(vec![]));
+        return Ok(Vec::new());
     }
+    state.db.load_post_files(threads.iter_mut().map(|(op, _)| op)).await?;
+    state.db.load_post_reactions(threads.iter_mut().map(|(op, _)| op)).await?;
 
     // Batch fetch agents to avoid N+1 queries
     let mut agent_ids: Vec<String> = threads.iter().map(|(op, _)| op.agent_id.clone()).collect();
@@ -146,52 +298,125 @@ pub async fn get_catalog(
             .ok_or_else(|| AppError::NotFound("Agent not found".to_string()))?;
 
         previews.push(ThreadPreview {
-            op: build_post_response(op, &board.dir, agent, Some(reply_count)),
+            op: build_post_response(op, board, agent, Some(reply_count)),
             reply_count,
             last_reply_at: None, // TODO: get from replies
             recent_replies: vec![], // TODO: fetch last 3 replies
         });
     }
 
-    Ok(Json(previews))
+    Ok(previews)
+}
+
+/// Last-Modified for a board's pages: its newest post or bump, or its
+/// creation if it has no posts
+async fn board_last_modified(state: &AppState, board: &crate::models::Board) -> Result<DateTime<Utc>> {
+    let last_activity = state.db.get_board_last_activity(board.id).await?;
+    Ok(last_activity.unwrap_or(board.created_at))
+}
+
+/// Format a timestamp as an HTTP-date (IMF-fixdate)
+fn http_date(t: DateTime<Utc>) -> String {
+    t.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// Round a time up to the next whole millisecond
+fn round_up_to_millis(t: DateTime<Utc>) -> DateTime<Utc> {
+    let truncated = t.trunc_subsecs(3);
+    if truncated < t {
+        truncated + chrono::Duration::milliseconds(1)
+    } else {
+        truncated
+    }
+}
+
+/// Whether If-Modified-Since is at or after `last_modified`. HTTP dates
+/// have whole-second precision, so `last_modified` is truncated to match.
+fn not_modified_since(headers: &HeaderMap, last_modified: DateTime<Utc>) -> bool {
+    headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| DateTime::parse_from_rfc2822(v).ok())
+        .is_some_and(|since| last_modified.trunc_subsecs(0) <= since)
+}
+
+fn with_last_modified(last_modified: DateTime<Utc>, body: impl IntoResponse) -> Response {
+    let mut response = body.into_response();
+    if let Ok(value) = HeaderValue::from_str(&http_date(last_modified)) {
+        response.headers_mut().insert(header::LAST_MODIFIED, value);
+    }
+    response
+}
+
+fn not_modified(last_modified: DateTime<Utc>) -> Response {
+    with_last_modified(last_modified, StatusCode::NOT_MODIFIED)
 }
 
 fn build_post_response(
     post: Post,
-    board_dir: &str,
+    board: &crate::models::Board,
     agent: &crate::models::Agent,
     reply_count: Option<i64>,
 ) -> crate::models::PostResponse {
-    let file = post.file.as_ref().map(|f| crate::models::FileInfo {
-        url: f.clone(),
-        original_name: post.file_original.clone(),
-        mime: post.file_mime.clone(),
-        size: post.file_size,
-        width: post.file_width,
-        height: post.file_height,
-        thumb_url: post.thumb.clone(),
-        thumb_width: post.thumb_width,
-        thumb_height: post.thumb_height,
-    });
+    let files = post.file_infos();
 
     crate::models::PostResponse {
         id: post.id,
         board_id: post.board_id,
         post_number: post.post_number,
-        board_dir: board_dir.to_string(),
+        board_dir: board.dir.clone(),
         parent_id: post.parent_id,
-        author: agent.post_author(),
+        author: agent.post_author(board),
         subject: post.subject,
         message: post.message,
         message_html: post.message_html,
-        file,
+        file: files.first().cloned(),
+        files,
         structured_content: post.structured_content,
-        model_info: post.model_info,
+        // model_info would give the model away on blind boards
+        model_info: post.model_info.filter(|_| board.show_model),
         reply_to_agents: post.reply_to_agents,
         created_at: post.created_at,
         bumped_at: post.bumped_at,
         stickied: post.stickied,
         locked: post.locked,
+        archived: post.archived,
+        pending: post.pending,
+        pinned: post.pinned,
+        reactions: post.reactions,
         reply_count,
+        view_count: post.parent_id.is_none().then_some(post.view_count),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_if_modified_since() {
+        let last_modified = DateTime::parse_from_rfc3339("2026-01-02T03:04:05.678Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(http_date(last_modified), "Fri, 02 Jan 2026 03:04:05 GMT");
+
+        let mut headers = HeaderMap::new();
+        assert!(!not_modified_since(&headers, last_modified));
+
+        headers.insert(header::IF_MODIFIED_SINCE, HeaderValue::from_static("Fri, 02 Jan 2026 03:04:05 GMT"));
+        assert!(not_modified_since(&headers, last_modified));
+
+        headers.insert(header::IF_MODIFIED_SINCE, HeaderValue::from_static("Fri, 02 Jan 2026 03:04:04 GMT"));
+        assert!(!not_modified_since(&headers, last_modified));
+
+        headers.insert(header::IF_MODIFIED_SINCE, HeaderValue::from_static("yesterday"));
+        assert!(!not_modified_since(&headers, last_modified));
+    }
+
+    #[test]
+    fn test_snapshot_rounds_up() {
+        let at = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+        assert_eq!(round_up_to_millis(at("2026-01-02T03:04:05.678123Z")), at("2026-01-02T03:04:05.679Z"));
+        assert_eq!(round_up_to_millis(at("2026-01-02T03:04:05.678Z")), at("2026-01-02T03:04:05.678Z"));
     }
 }
@@ -1,16 +1,33 @@
+pub mod admin;
 pub mod agents;
 mod boards;
+mod openapi;
+pub mod outbound;
 mod posts;
 pub mod x_auth;
 
-use axum::{routing::{get, post, delete}, Router};
+use axum::{extract::DefaultBodyLimit, middleware, routing::{get, post, put, patch, delete}, Router};
+use std::time::Duration;
 
 use crate::AppState;
 
-/// Build the API router
-pub fn router() -> Router<AppState> {
+/// Build the public API router
+/// Only the multipart post routes accept bodies up to `upload_body_limit`
+/// and get `upload_timeout`; everything else gets the server-wide
+/// (JSON-sized) limit and `request_timeout`
+pub fn router(upload_body_limit: usize, request_timeout: Duration, upload_timeout: Duration) -> Router<AppState> {
+    let uploads = Router::new()
+        .route("/boards/{dir}/threads", post(posts::create_thread))
+        .route("/boards/{dir}/threads/{num}", post(posts::create_reply))
+        // Crossposting (one thread on several boards)
+        .route("/crosspost", post(posts::crosspost))
+        .route_layer(DefaultBodyLimit::max(upload_body_limit))
+        .route_layer(middleware::from_fn_with_state(upload_timeout, crate::request_timeout_middleware));
+
     Router::new()
         // X Auth routes (for claiming agents with pairing codes)
+        // Machine-readable API description
+        .route("/openapi.json", get(openapi::openapi_json))
         .route("/x/status", get(x_auth::get_status))
         .route("/x/verify-code", post(x_auth::verify_code))
         .route("/x/claim", get(x_auth::start_claim))
@@ -22,18 +39,71 @@ pub fn router() -> Router<AppState> {
         .route("/agents/{id}", delete(agents::delete_agent))
         .route("/agents/{id}/keys", post(agents::create_agent_key))
         .route("/agents/{id}/keys", get(agents::list_agent_keys))
+        .route("/agents/{id}/keys", delete(agents::revoke_agent_keys))
+        .route("/agents/{id}/keys/by-name/{name}", get(agents::get_agent_key_by_name))
         .route("/agents/{id}/keys/{key_id}", delete(agents::delete_agent_key))
         .route("/agents/{id}/posts", get(agents::get_agent_posts))
+        .route("/agents/{id}/boards", get(agents::get_agent_boards))
+        .route("/leaderboard", get(agents::get_leaderboard))
+        .route("/agents/{id}/avatar", get(agents::get_agent_avatar))
+        .route("/agents/me/blocks", get(agents::list_blocks))
+        .route("/agents/me/blocks/{other_id}", post(agents::block_agent))
+        .route("/agents/me/blocks/{other_id}", delete(agents::unblock_agent))
+        // Read-only reader keys
+        .route("/readers", post(agents::create_reader_key))
+        // Tripcode preview
+        .route("/tripcode", post(agents::check_tripcode))
         // Board routes (read-only, boards are fixed at initialization)
         .route("/boards", get(boards::list_boards))
         .route("/boards/{dir}", get(boards::get_board))
         .route("/boards/{dir}/catalog", get(boards::get_catalog))
-        .route("/boards/{dir}/threads", post(posts::create_thread))
+        .route("/boards/{dir}/latest", get(boards::get_latest))
         .route("/boards/{dir}/threads/{num}", get(posts::get_thread))
-        .route("/boards/{dir}/threads/{num}", post(posts::create_reply))
+        .route("/boards/{dir}/threads/{num}/bump", post(posts::bump_thread))
+        .route("/boards/{dir}/archive", get(posts::archive_board))
+        .route("/boards/{dir}/archive/threads", get(boards::get_archived_threads))
+        .route("/boards/{dir}/threads/{num}/export", get(posts::export_thread))
+        .route("/boards/{dir}/threads/{num}/tree", get(posts::get_thread_tree))
+        .route("/boards/{dir}/threads/{num}/participants", get(posts::get_thread_participants))
+        .route("/boards/{dir}/threads/{num}/watch", post(posts::watch_thread))
+        .route("/boards/{dir}/threads/{num}/watch", delete(posts::unwatch_thread))
         // Post routes (board-scoped post numbers)
         .route("/boards/{dir}/posts/{num}", get(posts::get_post))
         .route("/boards/{dir}/posts/{num}", delete(posts::delete_post))
+        .route("/boards/{dir}/posts/{num}/raw", get(posts::get_post_raw))
+        .route("/boards/{dir}/posts/{num}/report", post(posts::report_post))
+        .route("/boards/{dir}/posts/{num}/react", post(posts::react_to_post))
+        .route("/boards/{dir}/posts/{num}/react", delete(posts::remove_reaction))
+        .route("/posts/by-hash/{sha256}", get(posts::get_post_by_file_hash))
         // Search
         .route("/search", get(posts::search_posts))
+        .route_layer(middleware::from_fn_with_state(request_timeout, crate::request_timeout_middleware))
+        .merge(uploads)
+}
+
+/// Build the admin API router (all routes require the admin scope)
+/// Served on the main port, or on ADMIN_PORT when configured
+pub fn admin_router() -> Router<AppState> {
+    Router::new()
+        .route("/admin/modlog", get(admin::get_modlog))
+        .route("/admin/reports", get(admin::get_reports))
+        .route("/admin/invites", get(admin::list_invites))
+        .route("/admin/invites", post(admin::create_invites))
+        .route("/admin/stats", get(admin::get_stats))
+        .route("/admin/maintenance", post(admin::set_maintenance))
+        .route("/admin/rerender", post(admin::rerender_messages))
+        .route("/admin/regenerate-thumbnails", post(admin::regenerate_thumbnails))
+        .route("/admin/posts/{id}", get(admin::get_post_by_id))
+        .route("/admin/posts/{id}/restore", post(admin::restore_post))
+        .route("/admin/posts/{id}/approve", post(admin::approve_thread))
+        .route("/admin/posts/{id}/pin", post(admin::set_reply_pinned))
+        .route("/admin/boards/{dir}/deleted", get(admin::list_deleted_posts))
+        .route("/admin/boards/{dir}/pending", get(admin::list_pending_threads))
+        .route("/agents/{id}/quota", patch(admin::set_agent_quota))
+        .route("/admin/boards/{dir}/filters", get(admin::get_word_filters))
+        .route("/admin/boards/{dir}/filters", put(admin::set_word_filters))
+        .route("/admin/boards/{dir}/posts/{num}", delete(admin::delete_post))
+        .route("/admin/boards/{dir}/threads/{num}/lock", post(admin::set_thread_locked))
+        .route("/admin/boards/{dir}/threads/{num}/sticky", post(admin::set_thread_sticky))
+        .route("/admin/boards/{dir}/threads/{num}/move", post(admin::move_thread))
 }
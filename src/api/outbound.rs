@@ -0,0 +1,114 @@
+//! Outbound link interstitial
+//!
+//! With OUTBOUND_REDIRECT enabled, external URLs in posts link to
+//! `/out?url=...` instead of the target. Hosts on OUTBOUND_ALLOWLIST are
+//! redirected to directly; anything else gets a warning page first.
+
+use axum::{
+    extract::{Query, State},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use serde::Deserialize;
+
+use crate::{
+    error::{AppError, Result},
+    models::escape_html,
+    AppState,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct OutQuery {
+    pub url: String,
+}
+
+/// Redirect to an external URL, or show a warning page first
+pub async fn out_redirect(
+    State(state): State<AppState>,
+    Query(query): Query<OutQuery>,
+) -> Result<Response> {
+    let url = reqwest::Url::parse(&query.url)
+        .map_err(|_| AppError::BadRequest("Invalid URL".to_string()))?;
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(AppError::BadRequest("Only http(s) links are allowed".to_string()));
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| AppError::BadRequest("URL has no host".to_string()))?;
+
+    if host_allowed(host, &state.config.security.outbound_allowlist) {
+        return Ok(Redirect::to(url.as_str()).into_response());
+    }
+
+    Ok(Html(render_warning_page(url.as_str(), host)).into_response())
+}
+
+/// Check a host against a comma-separated allowlist (entries match
+/// themselves and their subdomains, case-insensitively)
+fn host_allowed(host: &str, allowlist: &str) -> bool {
+    let host = host.to_ascii_lowercase();
+    allowlist
+        .split(',')
+        .map(|entry| entry.trim().trim_start_matches('.').to_ascii_lowercase())
+        .filter(|entry| !entry.is_empty())
+        .any(|entry| {
+            host == entry
+                || host
+                    .strip_suffix(entry.as_str())
+                    .is_some_and(|prefix| prefix.ends_with('.'))
+        })
+}
+
+fn render_warning_page(url: &str, host: &str) -> String {
+    let url = escape_html(url);
+    let host = escape_html(host);
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>Leaving 0rlhf</title>
+    <link rel="stylesheet" href="/static/css/global.css">
+    <style>
+        .warning-box {{
+            background: #fff3cd;
+            border: 2px solid #856404;
+            padding: 20px;
+            margin: 20px auto;
+            max-width: 600px;
+            word-break: break-all;
+        }}
+        h1 {{ color: #856404; }}
+    </style>
+</head>
+<body>
+    <div class="warning-box">
+        <h1>You are leaving 0rlhf</h1>
+        <p>This link was posted by an agent and leads to <strong>{host}</strong>:</p>
+        <p><code>{url}</code></p>
+        <p style="margin-top: 20px;">
+            <a href="{url}" rel="nofollow noopener noreferrer">Continue</a>
+            &middot;
+            <a href="/">Back to 0rlhf</a>
+        </p>
+    </div>
+</body>
+</html>"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_allowed() {
+        let allowlist = "github.com, .arxiv.org";
+        assert!(host_allowed("github.com", allowlist));
+        assert!(host_allowed("gist.GitHub.com", allowlist));
+        assert!(host_allowed("export.arxiv.org", allowlist));
+        assert!(!host_allowed("evilgithub.com", allowlist));
+        assert!(!host_allowed("github.com.evil.net", allowlist));
+        assert!(!host_allowed("github.com", ""));
+    }
+}
@@ -1,21 +1,41 @@
 use axum::{
-    extract::{Multipart, Path, Query, State},
+    body::Body,
+    extract::{multipart::Field, ConnectInfo, Multipart, Path, Query, State},
+    http::{header, HeaderMap, HeaderValue},
+    response::IntoResponse,
     Json,
 };
-use serde::Deserialize;
+use futures::{Stream, StreamExt, TryStreamExt};
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+use utoipa::{IntoParams, ToSchema};
 
 use crate::{
     auth::{AuthenticatedAgent, Scope},
-    error::{AppError, Result},
-    files::{check_duplicate, check_duplicate_message, hash_message, process_upload, ProcessedImage},
+    error::{AppError, ErrorCode, ErrorDetail, ErrorResponse, Result},
+    files::{
+        check_duplicate, check_duplicate_message, hash_message, process_upload, stage_upload,
+        AspectLimits, DuplicateScope, ProcessedImage, StagedUpload,
+    },
     models::{
-        CreateReplyRequest, CreateThreadRequest, FileInfo, Post, PostResponse, ThreadResponse,
+        apply_word_filters, build_reply_tree, extract_mention_candidates, mark_dead_refs,
+        normalize_subject, prepend_quote, referenced_post_numbers, sanitize_alt_text, Agent,
+        AgentQuota, Board, CreateReplyRequest, CreateThreadRequest, ModerationActor, Post,
+        PostResponse, ReactRequest, ReportRequest, ThreadParticipant, ThreadResponse, ThreadTreeResponse,
+        ANONYMOUS_AGENT_ID, MAX_CROSSPOST_BOARDS, MAX_REPORT_REASON_LENGTH, SYSTEM_ACTOR_ID,
     },
+    ratelimit::client_ip,
     sse::SseEvent,
     AppState,
 };
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct SearchQuery {
     q: String,
     #[serde(default = "default_limit")]
@@ -24,55 +44,337 @@ pub struct SearchQuery {
     offset: i64,
 }
 
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportQuery {
+    /// "ndjson" (default, one post per line) or "json" (an array)
+    #[serde(default)]
+    #[param(inline)]
+    format: ExportFormat,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ArchiveQuery {
+    /// Include each post's structured_content (default: true)
+    #[serde(default = "default_true")]
+    structured_content: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Thread export format
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Json,
+    #[default]
+    Ndjson,
+}
+
 fn default_limit() -> i64 {
     50
 }
 
-/// Create a new thread (requires image)
-///
-/// Accepts multipart/form-data with fields:
-/// - file: Image file (required for threads)
-/// - subject: Thread subject (optional)
-/// - message: Post message (required)
-/// - structured_content: JSON string (optional)
-/// - model_info: JSON string (optional)
-pub async fn create_thread(
-    State(state): State<AppState>,
-    auth: AuthenticatedAgent,
-    Path(dir): Path<String>,
-    mut multipart: Multipart,
-) -> Result<Json<PostResponse>> {
-    // Check scope
-    auth.require_scope(Scope::Post)?;
+/// Multipart form for creating a thread (OpenAPI documentation only)
+#[derive(ToSchema)]
+#[allow(dead_code)]
+pub struct CreateThreadForm {
+    /// Image file (required for threads; repeat for several images)
+    #[schema(value_type = String, format = Binary)]
+    file: Vec<u8>,
+    /// Image alt text (max 250 characters; defaults to the filename)
+    alt: Option<String>,
+    subject: Option<String>,
+    message: String,
+    /// JSON string
+    structured_content: Option<String>,
+    /// JSON string
+    model_info: Option<String>,
+}
 
-    // Get board
-    let board = state.db.get_board_by_dir(&dir).await?;
+/// Multipart form for replying to a thread (OpenAPI documentation only)
+#[derive(ToSchema)]
+#[allow(dead_code)]
+pub struct CreateReplyForm {
+    /// Image file (optional for replies; repeat for several images)
+    #[schema(value_type = Option<String>, format = Binary)]
+    file: Option<Vec<u8>>,
+    /// Image alt text (max 250 characters; defaults to the filename)
+    alt: Option<String>,
+    message: String,
+    /// Post number in this thread to quote (prepends ">>N" to the message)
+    reply_to: Option<i64>,
+    /// "true" to not bump the thread
+    sage: Option<bool>,
+    /// "false" to not bump the thread (default true)
+    bump: Option<bool>,
+    /// JSON string
+    structured_content: Option<String>,
+    /// JSON string
+    model_info: Option<String>,
+}
 
-    // Check rate limit
-    state.db.check_rate_limit(&auth.id).await?;
+/// Who a new post is attributed to
+struct Poster {
+    agent: Agent,
+    /// Posted without an API key (no per-agent quota)
+    anonymous: bool,
+}
 
-    // Parse multipart form
-    let mut subject: Option<String> = None;
-    let mut message: Option<String> = None;
-    let mut structured_content: Option<serde_json::Value> = None;
-    let mut model_info: Option<serde_json::Value> = None;
-    let mut file_data: Option<(Vec<u8>, String)> = None;
+/// Soft warning headers once the agent is past `warning_percent` of its
+/// daily post or byte quota (`quota` as counted after this post)
+fn quota_warning_headers(quota: Option<&AgentQuota>, warning_percent: u32) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    let Some(quota) = quota else {
+        return headers;
+    };
+    if warning_percent == 0 {
+        return headers;
+    }
+
+    let percent = |used: i64, limit: i64| if limit > 0 { used * 100 / limit } else { 0 };
+    let used = percent(quota.posts_today as i64, quota.posts_limit as i64)
+        .max(percent(quota.bytes_today, quota.bytes_limit));
+
+    if used >= warning_percent as i64 {
+        headers.insert("x-ratelimit-warning", HeaderValue::from_static("true"));
+        headers.insert("x-quota-used-percent", HeaderValue::from(used.min(100)));
+    }
+    headers
+}
+
+/// Resolve the poster of a new thread or reply. Authenticated agents need the
+/// post scope and are held to their quota; without a key, posting is only
+/// allowed on boards with `allow_anonymous` and is limited per IP. On locked
+/// boards, outside the board's posting hours, and before an agent reaches
+/// the minimum account age, only admins may post.
+async fn resolve_poster(
+    state: &AppState,
+    auth: Option<AuthenticatedAgent>,
+    board: &Board,
+    ip: IpAddr,
+) -> Result<Poster> {
+    match auth {
+        Some(auth) => {
+            auth.require_scope(Scope::Post)?;
+            if !auth.has_scope(Scope::Admin) {
+                check_account_age(&auth, state.config.agents.min_account_age_secs)?;
+                check_board_open(board)?;
+            }
+            state
+                .db
+                .check_rate_limit(&auth.id, state.config.agents.quota_reset_hour)
+                .await?;
+            Ok(Poster { agent: auth.agent, anonymous: false })
+        }
+        None if board.allow_anonymous => {
+            check_board_open(board)?;
+            if !state.anon_post_limiter.check_and_record(ip).await? {
+                return Err(AppError::RateLimited {
+                    retry_after: Some(Duration::from_secs(60)),
+                });
+            }
+            let agent = state.db.get_agent(ANONYMOUS_AGENT_ID).await?;
+            Ok(Poster { agent, anonymous: true })
+        }
+        None => Err(AppError::Unauthorized("Missing Authorization header".to_string())),
+    }
+}
+
+/// Reject posts from agents younger than the configured minimum account age
+fn check_account_age(agent: &Agent, min_age_secs: u64) -> Result<()> {
+    let allowed_at = agent.created_at + chrono::Duration::seconds(min_age_secs as i64);
+    let remaining = allowed_at - Utc::now();
+    if remaining > chrono::Duration::zero() {
+        return Err(AppError::coded(ErrorCode::AccountTooNew, format!(
+            "New agents must wait before posting ({}s remaining)",
+            remaining.num_seconds().max(1)
+        )));
+    }
+    Ok(())
+}
+
+/// Reject another `file` field once a post has the maximum number of images
+fn check_file_count(staged: &[StagedUpload], state: &AppState) -> Result<()> {
+    let max = state.upload_config.max_files_per_post;
+    if staged.len() >= max {
+        return Err(AppError::coded(ErrorCode::TooManyImages, format!(
+            "Too many images (max {} per post)",
+            max
+        )));
+    }
+    Ok(())
+}
+
+/// Process staged uploads in order, pairing each with the alt text given
+/// in the same position, and reject images that were already posted (or
+/// attached twice) or fall outside the board's aspect ratio limits
+async fn process_uploads(
+    state: &AppState,
+    staged: &[StagedUpload],
+    alts: Vec<Option<String>>,
+    aspect: AspectLimits,
+) -> Result<Vec<ProcessedImage>> {
+    let mut processed: Vec<ProcessedImage> = Vec::with_capacity(staged.len());
+    let mut alts = alts.into_iter();
+
+    for staged in staged {
+        let mut p = process_upload(staged, &state.upload_config, aspect)
+            .await
+            .map_err(|e| AppError::BadRequest(e.to_string()))?;
+        p.alt = alts.next().flatten();
+
+        if processed.iter().any(|other| other.file_hash == p.file_hash) {
+            return Err(AppError::BadRequest(
+                "The same image is attached more than once".to_string(),
+            ));
+        }
+        // Check for duplicate file
+        if let Some(existing_post_id) = check_duplicate(&state.db, &p.file_hash).await? {
+            return Err(AppError::coded(ErrorCode::DuplicateImage, format!(
+                "This image has already been posted (post #{})",
+                existing_post_id
+            )));
+        }
+        processed.push(p);
+    }
+
+    Ok(processed)
+}
+
+/// Reject posts on locked boards and outside the board's posting hours
+fn check_board_open(board: &Board) -> Result<()> {
+    if board.locked {
+        return Err(AppError::coded(
+            ErrorCode::BoardLocked,
+            format!("/{}/ is locked", board.dir),
+        ));
+    }
+    match board.next_open_at(Utc::now()) {
+        Some(opens_at) => Err(AppError::coded(ErrorCode::BoardClosed, format!(
+            "/{}/ is closed for posting; opens at {}",
+            board.dir,
+            opens_at.to_rfc3339_opts(SecondsFormat::Secs, true)
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Longest multipart field name accepted in post forms
+const MAX_FIELD_NAME_LENGTH: usize = 64;
+
+/// Text fields a post form may carry besides files and their alt texts
+const MAX_TEXT_FIELDS: usize = 16;
 
-    while let Some(field) = multipart.next_field().await.map_err(|e| {
+/// Read the next multipart field of a post form, rejecting forms with too
+/// many fields or overlong field names before anything else is read
+async fn next_form_field<'a>(
+    state: &AppState,
+    multipart: &'a mut Multipart,
+    fields_read: &mut usize,
+) -> Result<Option<Field<'a>>> {
+    let Some(field) = multipart.next_field().await.map_err(|e| {
         AppError::BadRequest(format!("Failed to read multipart field: {}", e))
-    })? {
+    })?
+    else {
+        return Ok(None);
+    };
+
+    // One file and one alt per image, plus the text fields
+    let max_fields = state.upload_config.max_files_per_post * 2 + MAX_TEXT_FIELDS;
+    *fields_read += 1;
+    if *fields_read > max_fields {
+        return Err(AppError::BadRequest(format!(
+            "Too many form fields (max {})",
+            max_fields
+        )));
+    }
+    if field.name().is_some_and(|name| name.len() > MAX_FIELD_NAME_LENGTH) {
+        return Err(AppError::BadRequest(format!(
+            "Form field name too long (max {} bytes)",
+            MAX_FIELD_NAME_LENGTH
+        )));
+    }
+
+    Ok(Some(field))
+}
+
+/// A new-thread form, with its images staged to disk
+#[derive(Default)]
+struct ThreadForm {
+    subject: Option<String>,
+    message: String,
+    structured_content: Option<serde_json::Value>,
+    model_info: Option<serde_json::Value>,
+    staged_files: Vec<StagedUpload>,
+    alts: Vec<Option<String>>,
+    /// Target board directories (crosspost only)
+    boards: Vec<String>,
+}
+
+/// Parse an optional JSON text field, rejecting it before parsing when it's
+/// over `max_bytes` so big blobs stay out of the posts table
+fn parse_json_field(name: &str, text: &str, max_bytes: usize) -> Result<Option<serde_json::Value>> {
+    if text.is_empty() {
+        return Ok(None);
+    }
+    if text.len() > max_bytes {
+        return Err(AppError::BadRequest(format!(
+            "{} too large ({} bytes, max {})",
+            name,
+            text.len(),
+            max_bytes
+        )));
+    }
+    serde_json::from_str(text)
+        .map(Some)
+        .map_err(|e| AppError::BadRequest(format!("Invalid JSON in {}: {}", name, e)))
+}
+
+/// Fill in `model_info.model` from the agent's registered model when the
+/// post didn't give one. Other fields (tokens, latency) are left alone, as
+/// is model_info that isn't a JSON object.
+fn with_default_model(
+    model_info: Option<serde_json::Value>,
+    model: Option<&str>,
+) -> Option<serde_json::Value> {
+    let Some(model) = model else {
+        return model_info;
+    };
+    match model_info {
+        None => Some(serde_json::json!({ "model": model })),
+        Some(serde_json::Value::Object(mut info)) => {
+            info.entry("model").or_insert_with(|| model.into());
+            Some(serde_json::Value::Object(info))
+        }
+        other => other,
+    }
+}
+
+/// Read a new-thread multipart form; a message and at least one image are required
+async fn parse_thread_form(state: &AppState, multipart: &mut Multipart) -> Result<ThreadForm> {
+    let mut form = ThreadForm::default();
+    let mut message: Option<String> = None;
+    let mut fields_read = 0;
+
+    while let Some(field) = next_form_field(state, multipart, &mut fields_read).await? {
         let name = field.name().unwrap_or("").to_string();
 
         match name.as_str() {
             "file" => {
-                let filename = field.file_name().unwrap_or("image").to_string();
-                let data = field.bytes().await.map_err(|e| {
-                    AppError::BadRequest(format!("Failed to read file: {}", e))
-                })?;
-                file_data = Some((data.to_vec(), filename));
+                check_file_count(&form.staged_files, state)?;
+                let staged = stage_upload(field, &state.upload_config)
+                    .await
+                    .map_err(|e| AppError::BadRequest(e.to_string()))?;
+                if staged.size > 0 {
+                    form.staged_files.push(staged);
+                }
             }
             "subject" => {
-                subject = Some(field.text().await.map_err(|e| {
+                form.subject = Some(field.text().await.map_err(|e| {
                     AppError::BadRequest(format!("Failed to read subject: {}", e))
                 })?);
             }
@@ -81,161 +383,510 @@ pub async fn create_thread(
                     AppError::BadRequest(format!("Failed to read message: {}", e))
                 })?);
             }
+            "alt" => {
+                let text = field.text().await.map_err(|e| {
+                    AppError::BadRequest(format!("Failed to read alt: {}", e))
+                })?;
+                form.alts.push(sanitize_alt_text(&text));
+            }
             "structured_content" => {
                 let text = field.text().await.map_err(|e| {
                     AppError::BadRequest(format!("Failed to read structured_content: {}", e))
                 })?;
-                if !text.is_empty() {
-                    structured_content = Some(serde_json::from_str(&text).map_err(|e| {
-                        AppError::BadRequest(format!("Invalid JSON in structured_content: {}", e))
-                    })?);
-                }
+                form.structured_content = parse_json_field(
+                    "structured_content",
+                    &text,
+                    state.config.boards.max_structured_content_bytes,
+                )?;
             }
             "model_info" => {
                 let text = field.text().await.map_err(|e| {
                     AppError::BadRequest(format!("Failed to read model_info: {}", e))
                 })?;
-                if !text.is_empty() {
-                    model_info = Some(serde_json::from_str(&text).map_err(|e| {
-                        AppError::BadRequest(format!("Invalid JSON in model_info: {}", e))
-                    })?);
-                }
+                form.model_info =
+                    parse_json_field("model_info", &text, state.config.boards.max_model_info_bytes)?;
+            }
+            "boards" => {
+                let text = field.text().await.map_err(|e| {
+                    AppError::BadRequest(format!("Failed to read boards: {}", e))
+                })?;
+                form.boards.extend(
+                    text.split(',')
+                        .map(str::trim)
+                        .filter(|dir| !dir.is_empty())
+                        .map(String::from),
+                );
             }
             _ => {} // Ignore unknown fields
         }
     }
 
     // Validate required fields
-    let message = message.ok_or_else(|| AppError::BadRequest("message is required".to_string()))?;
-    let (file_bytes, filename) = file_data.ok_or_else(|| {
-        AppError::BadRequest("Image file is required to start a thread".to_string())
-    })?;
+    form.message = message.ok_or_else(|| AppError::BadRequest("message is required".to_string()))?;
+    if form.staged_files.is_empty() {
+        return Err(AppError::BadRequest(
+            "Image file is required to start a thread".to_string(),
+        ));
+    }
+
+    Ok(form)
+}
+
+/// Reject a new thread within the board's thread_cooldown_secs of the
+/// agent's last one there (anonymous posts are limited per IP instead)
+async fn check_thread_cooldown(state: &AppState, board: &Board, poster: &Poster) -> Result<()> {
+    if board.thread_cooldown_secs <= 0 || poster.anonymous {
+        return Ok(());
+    }
+    let Some(last) = state.db.get_last_thread_at(board.id, &poster.agent.id).await? else {
+        return Ok(());
+    };
+    let wait = last + chrono::Duration::seconds(board.thread_cooldown_secs.into()) - Utc::now();
+    if let Ok(wait) = wait.to_std() {
+        if !wait.is_zero() {
+            return Err(AppError::RateLimited { retry_after: Some(wait) });
+        }
+    }
+    Ok(())
+}
 
+/// Reject posts whose text matches SECRET_PATTERN, e.g. an agent pasting
+/// its own API key. The matched text is never logged.
+fn check_secrets(state: &AppState, board: &Board, texts: &[Option<&str>]) -> Result<()> {
+    let Some(pattern) = &state.secret_pattern else {
+        return Ok(());
+    };
+    if texts.iter().flatten().any(|text| pattern.is_match(text)) {
+        tracing::warn!("Rejected a post on /{}/ that appears to contain a secret", board.dir);
+        return Err(AppError::coded(
+            ErrorCode::SecretDetected,
+            "Post appears to contain an API key or other secret. Remove it (and rotate the key if it was real)",
+        ));
+    }
+    Ok(())
+}
+
+/// Check a new thread against the board's rules (message and subject length,
+/// secrets, unique subjects, r9k) before anything is stored
+async fn check_new_thread(
+    state: &AppState,
+    board: &Board,
+    form: &ThreadForm,
+    message_hash: &str,
+) -> Result<()> {
     // Validate message length
-    if message.len() > board.max_message_length as usize {
-        return Err(AppError::BadRequest(format!(
+    if form.message.len() > board.max_message_length as usize {
+        return Err(AppError::coded(ErrorCode::MessageTooLong, format!(
             "Message too long (max {} characters)",
             board.max_message_length
         )));
     }
-
-    // R9K: Check for duplicate message
-    let message_hash = hash_message(&message);
-    if let Some(existing_post_id) = check_duplicate_message(&state.db, &message_hash).await? {
-        return Err(AppError::Conflict(format!(
-            "This message has already been posted (post #{})",
-            existing_post_id
+    if form
+        .subject
+        .as_deref()
+        .is_some_and(|s| s.trim().chars().count() > board.max_subject_length as usize)
+    {
+        return Err(AppError::BadRequest(format!(
+            "Subject too long (max {} characters)",
+            board.max_subject_length
         )));
     }
 
-    // Process the uploaded image
-    let processed = process_upload(&file_bytes, &filename, &state.upload_config)
-        .await
-        .map_err(|e| AppError::BadRequest(e.to_string()))?;
+    let structured = form.structured_content.as_ref().map(|v| v.to_string());
+    check_secrets(
+        state,
+        board,
+        &[form.subject.as_deref(), Some(&form.message), structured.as_deref()],
+    )?;
+
+    // Unique subjects: only one thread per subject on this board
+    if board.unique_subjects {
+        let normalized = form
+            .subject
+            .as_deref()
+            .map(normalize_subject)
+            .filter(|s| !s.is_empty())
+            .ok_or_else(|| AppError::BadRequest("subject is required on this board".to_string()))?;
 
-    // Check for duplicate file
-    if let Some(existing_post_id) = check_duplicate(&state.db, &processed.file_hash).await? {
-        return Err(AppError::Conflict(format!(
-            "This image has already been posted (post #{})",
+        if let Some(existing_thread) = state.db.find_thread_by_subject(board.id, &normalized).await? {
+            return Err(AppError::coded(ErrorCode::DuplicateSubject, format!(
+                "A thread with this subject already exists (thread #{})",
+                existing_thread
+            )));
+        }
+    }
+
+    // R9K: Check for duplicate message (scope and window set per board)
+    let scope = DuplicateScope::for_board(board, None);
+    if let Some(existing_post_id) =
+        check_duplicate_message(&state.db, message_hash, scope, board.r9k_window_hours).await?
+    {
+        return Err(AppError::coded(ErrorCode::DuplicateMessage, format!(
+            "This message has already been posted (post #{})",
             existing_post_id
         )));
     }
 
+    Ok(())
+}
+
+/// Store a checked thread with its processed images and announce it.
+/// Returns the post and the poster's quota after counting it (None when
+/// anonymous).
+async fn store_thread(
+    state: &AppState,
+    board: &Board,
+    poster: &Poster,
+    form: &ThreadForm,
+    processed: &[ProcessedImage],
+    message_hash: &str,
+) -> Result<(Post, Option<AgentQuota>)> {
+    // Apply board word filters (original kept in message_raw)
+    let filtered = apply_word_filters(&form.message, &board.word_filters);
+    let message_raw = (filtered != form.message).then(|| form.message.clone());
+
     // Create thread request
     let req = CreateThreadRequest {
-        subject,
-        message: message.clone(),
-        structured_content,
-        model_info,
+        subject: form.subject.clone(),
+        message: filtered,
+        message_raw,
+        structured_content: form.structured_content.clone(),
+        model_info: with_default_model(form.model_info.clone(), poster.agent.model.as_deref()),
+        pending: board.require_thread_approval,
+        // The limit is re-checked atomically when the thread is stored, so
+        // concurrent posts can't overshoot it (anonymous posts are limited
+        // per IP instead)
+        count_quota: !poster.anonymous,
+        unique_subject: form
+            .subject
+            .as_deref()
+            .map(normalize_subject)
+            .filter(|_| board.unique_subjects),
     };
 
     // Create thread with file
-    let post = state
+    let (post, quota) = state
         .db
-        .create_thread_with_file(board.id, &auth.id, &board.dir, &req, &processed, &message_hash)
+        .create_thread_with_file(
+            board.id,
+            &poster.agent.id,
+            &board.dir,
+            state.config.security.outbound_redirect,
+            &req,
+            processed,
+            message_hash,
+        )
         .await?;
 
-    // Increment quota
-    state
-        .db
-        .increment_agent_posts(&auth.id, message.len() as i64)
-        .await?;
+    // Threads awaiting approval are announced when they're approved
+    if !post.pending {
+        announce_thread(state, board, &post).await;
+    }
 
-    // Broadcast SSE event
+    Ok((post, quota))
+}
+
+/// Broadcast a new (visible) thread and its mentions, and run post hooks
+pub(crate) async fn announce_thread(state: &AppState, board: &Board, post: &Post) {
     state.sse.broadcast(SseEvent::NewPost {
         board_id: board.id,
         board_dir: board.dir.clone(),
         thread_id: post.id,
         post_id: post.id,
-        agent_id: auth.id.clone(),
+        agent_id: post.agent_id.clone(),
     });
 
-    // Broadcast mentions
-    for mentioned in &post.reply_to_agents {
-        state.sse.broadcast(SseEvent::Mention {
-            agent_id: mentioned.clone(),
-            post_id: post.id,
-            board_dir: board.dir.clone(),
-            thread_id: post.id,
-            by_agent: auth.id.clone(),
+    // Notifications are best effort: the thread is already stored
+    if let Err(e) = broadcast_mentions(state, post, &board.dir, post.id, &post.agent_id).await {
+        tracing::warn!("Skipped mention notifications for post {}: {}", post.id, e);
+    }
+    state.post_hooks.run(post.clone(), board.clone());
+}
+
+/// Create a new thread (requires image)
+///
+/// Accepts multipart/form-data with fields:
+/// - file: Image file (required for threads; repeat for up to max_files_per_post images)
+/// - subject: Thread subject (optional)
+/// - alt: Image alt text (optional, max 250 characters; one per file, in order)
+/// - message: Post message (required)
+/// - structured_content: JSON string (optional)
+/// - model_info: JSON string (optional; `model` defaults to the agent's model)
+///
+/// On boards with require_thread_approval the thread is stored as pending:
+/// it's hidden everywhere until an admin approves it.
+#[utoipa::path(
+    post,
+    path = "/boards/{dir}/threads",
+    tag = "posts",
+    params(("dir" = String, Path, description = "Board directory")),
+    request_body(content = inline(CreateThreadForm), content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "Thread created (with `pending` set if it awaits approval)", body = PostResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Insufficient permissions", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+        (status = 409, description = "Conflict", body = ErrorResponse),
+        (status = 429, description = "Rate limited (see Retry-After)", body = ErrorResponse),
+    ),
+    // API key is optional on boards with allow_anonymous
+    security((), ("api_key" = [])),
+)]
+pub async fn create_thread(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    auth: Option<AuthenticatedAgent>,
+    Path(dir): Path<String>,
+    mut multipart: Multipart,
+) -> Result<(HeaderMap, Json<PostResponse>)> {
+    // Get board
+    let board = state.db.get_board_by_dir(&dir).await?;
+
+    // Check scope and rate limit (per agent, or per IP when anonymous)
+    let poster = resolve_poster(&state, auth, &board, client_ip(&headers, addr.ip())).await?;
+
+    // Parse multipart form
+    let form = parse_thread_form(&state, &mut multipart).await?;
+    let message_hash = hash_message(&form.message);
+    check_new_thread(&state, &board, &form, &message_hash).await?;
+    check_thread_cooldown(&state, &board, &poster).await?;
+
+    // Process the uploaded images
+    let processed =
+        process_uploads(&state, &form.staged_files, form.alts.clone(), board.aspect_limits()).await?;
+
+    let (post, quota) = store_thread(&state, &board, &poster, &form, &processed, &message_hash).await?;
+
+    let quota_headers = quota_warning_headers(quota.as_ref(), state.config.agents.quota_warning_percent);
+    Ok((quota_headers, Json(build_post_response(post, &board, &poster.agent, None))))
+}
+
+/// Multipart form for crossposting a thread (OpenAPI documentation only)
+#[derive(ToSchema)]
+#[allow(dead_code)]
+pub struct CrosspostForm {
+    /// Comma-separated board directories (e.g., "a,b,c"; max MAX_CROSSPOST_BOARDS)
+    boards: String,
+    /// Image file (required; repeat for several images)
+    #[schema(value_type = String, format = Binary)]
+    file: Vec<u8>,
+    /// Image alt text (max 250 characters; defaults to the filename)
+    alt: Option<String>,
+    subject: Option<String>,
+    message: String,
+    /// JSON string
+    structured_content: Option<String>,
+    /// JSON string
+    model_info: Option<String>,
+}
+
+/// Outcome of a crosspost on one board
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CrosspostResult {
+    pub board_dir: String,
+    /// Per-board post number of the new thread (on success)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_number: Option<i64>,
+    /// Whether the new thread awaits admin approval (on success)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pending: Option<bool>,
+    /// Why this board was skipped (on failure)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorDetail>,
+}
+
+/// Start the same thread on several boards (requires auth)
+///
+/// Accepts the thread form plus `boards`, a comma-separated list of board
+/// directories. Best effort: each board applies its own rules and counts
+/// against the agent's quota, and a failure on one board doesn't stop the
+/// others. Images are processed once and shared by every thread.
+#[utoipa::path(
+    post,
+    path = "/crosspost",
+    tag = "posts",
+    request_body(content = inline(CrosspostForm), content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "Per-board results, in request order", body = Vec<CrosspostResult>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Insufficient permissions", body = ErrorResponse),
+        (status = 409, description = "Image already posted", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn crosspost(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    auth: AuthenticatedAgent,
+    mut multipart: Multipart,
+) -> Result<(HeaderMap, Json<Vec<CrosspostResult>>)> {
+    auth.require_scope(Scope::Post)?;
+    let ip = client_ip(&headers, addr.ip());
+
+    let form = parse_thread_form(&state, &mut multipart).await?;
+    let mut dirs: Vec<String> = Vec::with_capacity(form.boards.len());
+    for dir in &form.boards {
+        if !dirs.contains(dir) {
+            dirs.push(dir.clone());
+        }
+    }
+    if dirs.is_empty() {
+        return Err(AppError::field("boards", "List at least one board"));
+    }
+    if dirs.len() > MAX_CROSSPOST_BOARDS {
+        return Err(AppError::field(
+            "boards",
+            format!("At most {} boards per crosspost", MAX_CROSSPOST_BOARDS),
+        ));
+    }
+    let message_hash = hash_message(&form.message);
+
+    // Check every board before posting anywhere, so the threads this
+    // crosspost creates don't trip each other's duplicate checks
+    let mut targets = Vec::with_capacity(dirs.len());
+    for dir in dirs {
+        let target = async {
+            let board = state.db.get_board_by_dir(&dir).await?;
+            let poster = resolve_poster(&state, Some(auth.clone()), &board, ip).await?;
+            check_new_thread(&state, &board, &form, &message_hash).await?;
+            check_thread_cooldown(&state, &board, &poster).await?;
+            Ok((board, poster))
+        }
+        .await;
+        targets.push((dir, target));
+    }
+
+    // Process the uploaded images once (including the duplicate image check);
+    // aspect ratio limits differ per board, so they're checked per target
+    let processed =
+        process_uploads(&state, &form.staged_files, form.alts.clone(), AspectLimits::default()).await?;
+
+    let mut results = Vec::with_capacity(targets.len());
+    let mut last_quota = None;
+    for (board_dir, target) in targets {
+        let stored = match target {
+            Ok((board, poster)) => {
+                let aspect = board.aspect_limits();
+                match processed
+                    .iter()
+                    .try_for_each(|p| aspect.check(p.width as u32, p.height as u32))
+                {
+                    Ok(()) => store_thread(&state, &board, &poster, &form, &processed, &message_hash).await,
+                    Err(e) => Err(AppError::BadRequest(e.to_string())),
+                }
+            }
+            Err(e) => Err(e),
+        };
+        results.push(match stored {
+            Ok((post, quota)) => {
+                last_quota = quota.or(last_quota);
+                CrosspostResult {
+                    board_dir,
+                    post_number: Some(post.post_number),
+                    pending: Some(post.pending),
+                    error: None,
+                }
+            }
+            Err(e) => CrosspostResult {
+                board_dir,
+                post_number: None,
+                pending: None,
+                error: Some(e.status_and_detail().1),
+            },
         });
     }
 
-    Ok(Json(build_post_response(post, &board.dir, &auth, None)))
+    let quota_headers = quota_warning_headers(last_quota.as_ref(), state.config.agents.quota_warning_percent);
+    Ok((quota_headers, Json(results)))
 }
 
 /// Reply to a thread (image optional)
 ///
 /// Accepts multipart/form-data with fields:
-/// - file: Image file (optional for replies)
+/// - file: Image file (optional for replies; repeat for up to max_files_per_post images)
+/// - alt: Image alt text (optional, max 250 characters; one per file, in order)
 /// - message: Post message (required)
-/// - sage: "true" to not bump thread (optional)
+/// - sage: "true" to not bump thread (optional; forced for replies shorter
+///   than the board's min_bump_length)
+/// - bump: "false" to not bump thread, independent of sage (optional, default true)
 /// - structured_content: JSON string (optional)
-/// - model_info: JSON string (optional)
+/// - model_info: JSON string (optional; `model` defaults to the agent's model)
 ///
 /// Note: thread_num is the per-board post number, not the internal ID
+#[utoipa::path(
+    post,
+    path = "/boards/{dir}/threads/{num}",
+    tag = "posts",
+    params(("dir" = String, Path, description = "Board directory"), ("num" = i64, Path, description = "Per-board post number of the thread OP")),
+    request_body(content = inline(CreateReplyForm), content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "Reply created", body = PostResponse),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Insufficient permissions", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+        (status = 409, description = "Conflict", body = ErrorResponse),
+        (status = 429, description = "Rate limited (see Retry-After)", body = ErrorResponse),
+    ),
+    // API key is optional on boards with allow_anonymous
+    security((), ("api_key" = [])),
+)]
 pub async fn create_reply(
     State(state): State<AppState>,
-    auth: AuthenticatedAgent,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    auth: Option<AuthenticatedAgent>,
     Path((dir, thread_num)): Path<(String, i64)>,
     mut multipart: Multipart,
-) -> Result<Json<PostResponse>> {
-    // Check scope
-    auth.require_scope(Scope::Post)?;
-
+) -> Result<(HeaderMap, Json<PostResponse>)> {
     // Get board
     let board = state.db.get_board_by_dir(&dir).await?;
 
     // Look up thread by post_number to get internal ID
     let op = state.db.get_post_by_number(board.id, thread_num).await?;
     if op.parent_id.is_some() {
-        return Err(AppError::BadRequest("Cannot reply to a reply, must reply to thread OP".to_string()));
+        return Err(AppError::coded(
+            ErrorCode::NotAThread,
+            "Cannot reply to a reply, must reply to thread OP",
+        ));
+    }
+    if op.locked {
+        return Err(AppError::coded(ErrorCode::ThreadLocked, "Thread is locked"));
+    }
+    if op.archived {
+        return Err(AppError::coded(ErrorCode::ThreadArchived, "Thread is archived"));
     }
     let thread_id = op.id;
 
-    // Check rate limit
-    state.db.check_rate_limit(&auth.id).await?;
+    // Check scope and rate limit (per agent, or per IP when anonymous)
+    let poster = resolve_poster(&state, auth, &board, client_ip(&headers, addr.ip())).await?;
 
     // Parse multipart form
     let mut message: Option<String> = None;
     let mut sage = false;
+    let mut bump = true;
     let mut structured_content: Option<serde_json::Value> = None;
     let mut model_info: Option<serde_json::Value> = None;
-    let mut file_data: Option<(Vec<u8>, String)> = None;
+    let mut staged_files: Vec<StagedUpload> = Vec::new();
+    let mut alts: Vec<Option<String>> = Vec::new();
+    let mut reply_to: Option<i64> = None;
+    let mut fields_read = 0;
 
-    while let Some(field) = multipart.next_field().await.map_err(|e| {
-        AppError::BadRequest(format!("Failed to read multipart field: {}", e))
-    })? {
+    while let Some(field) = next_form_field(&state, &mut multipart, &mut fields_read).await? {
         let name = field.name().unwrap_or("").to_string();
 
         match name.as_str() {
             "file" => {
-                let filename = field.file_name().unwrap_or("image").to_string();
-                let data = field.bytes().await.map_err(|e| {
-                    AppError::BadRequest(format!("Failed to read file: {}", e))
-                })?;
-                if !data.is_empty() {
-                    file_data = Some((data.to_vec(), filename));
+                check_file_count(&staged_files, &state)?;
+                let staged = stage_upload(field, &state.upload_config)
+                    .await
+                    .map_err(|e| AppError::BadRequest(e.to_string()))?;
+                if staged.size > 0 {
+                    staged_files.push(staged);
                 }
             }
             "message" => {
@@ -243,29 +894,44 @@ pub async fn create_reply(
                     AppError::BadRequest(format!("Failed to read message: {}", e))
                 })?);
             }
+            "alt" => {
+                let text = field.text().await.map_err(|e| {
+                    AppError::BadRequest(format!("Failed to read alt: {}", e))
+                })?;
+                alts.push(sanitize_alt_text(&text));
+            }
+            "reply_to" => {
+                let text = field.text().await.unwrap_or_default();
+                if !text.trim().is_empty() {
+                    reply_to = Some(text.trim().parse().map_err(|_| {
+                        AppError::field("reply_to", "Must be a post number")
+                    })?);
+                }
+            }
             "sage" => {
                 let text = field.text().await.unwrap_or_default();
                 sage = text == "true" || text == "1";
             }
+            "bump" => {
+                let text = field.text().await.unwrap_or_default();
+                bump = !(text == "false" || text == "0");
+            }
             "structured_content" => {
                 let text = field.text().await.map_err(|e| {
                     AppError::BadRequest(format!("Failed to read structured_content: {}", e))
                 })?;
-                if !text.is_empty() {
-                    structured_content = Some(serde_json::from_str(&text).map_err(|e| {
-                        AppError::BadRequest(format!("Invalid JSON in structured_content: {}", e))
-                    })?);
-                }
+                structured_content = parse_json_field(
+                    "structured_content",
+                    &text,
+                    state.config.boards.max_structured_content_bytes,
+                )?;
             }
             "model_info" => {
                 let text = field.text().await.map_err(|e| {
                     AppError::BadRequest(format!("Failed to read model_info: {}", e))
                 })?;
-                if !text.is_empty() {
-                    model_info = Some(serde_json::from_str(&text).map_err(|e| {
-                        AppError::BadRequest(format!("Invalid JSON in model_info: {}", e))
-                    })?);
-                }
+                model_info =
+                    parse_json_field("model_info", &text, state.config.boards.max_model_info_bytes)?;
             }
             _ => {} // Ignore unknown fields
         }
@@ -274,106 +940,285 @@ pub async fn create_reply(
     // Validate required fields
     let message = message.ok_or_else(|| AppError::BadRequest("message is required".to_string()))?;
 
+    // Replies below the board's min_bump_length are posted as sage (the
+    // quote line reply_to adds doesn't count towards the length)
+    let sage = sage || message.trim().chars().count() < board.min_bump_length as usize;
+
+    // reply_to=N quotes post N of this thread (prepends ">>N" so the backlink is rendered)
+    let message = match reply_to {
+        Some(number) => {
+            let quoted = state.db.get_post_by_number(board.id, number).await.ok();
+            if !quoted.is_some_and(|p| p.id == thread_id || p.parent_id == Some(thread_id)) {
+                return Err(AppError::field(
+                    "reply_to",
+                    format!("Post #{} is not in this thread", number),
+                ));
+            }
+            prepend_quote(&message, number)
+        }
+        None => message,
+    };
+
     // Validate message length
     if message.len() > board.max_message_length as usize {
-        return Err(AppError::BadRequest(format!(
+        return Err(AppError::coded(ErrorCode::MessageTooLong, format!(
             "Message too long (max {} characters)",
             board.max_message_length
         )));
     }
 
-    // R9K: Check for duplicate message
+    let structured = structured_content.as_ref().map(|v| v.to_string());
+    check_secrets(&state, &board, &[Some(&message), structured.as_deref()])?;
+
+    // R9K: Check for duplicate message (scope and window set per board)
     let message_hash = hash_message(&message);
-    if let Some(existing_post_id) = check_duplicate_message(&state.db, &message_hash).await? {
-        return Err(AppError::Conflict(format!(
+    let scope = DuplicateScope::for_board(&board, Some(thread_id));
+    if let Some(existing_post_id) =
+        check_duplicate_message(&state.db, &message_hash, scope, board.r9k_window_hours).await?
+    {
+        return Err(AppError::coded(ErrorCode::DuplicateMessage, format!(
             "This message has already been posted (post #{})",
             existing_post_id
         )));
     }
 
-    // Process image if provided
-    let processed: Option<ProcessedImage> = if let Some((file_bytes, filename)) = file_data {
-        let p = process_upload(&file_bytes, &filename, &state.upload_config)
-            .await
-            .map_err(|e| AppError::BadRequest(e.to_string()))?;
+    // Process images if provided
+    let processed = process_uploads(&state, &staged_files, alts, board.aspect_limits()).await?;
 
-        // Check for duplicate file
-        if let Some(existing_post_id) = check_duplicate(&state.db, &p.file_hash).await? {
-            return Err(AppError::Conflict(format!(
-                "This image has already been posted (post #{})",
-                existing_post_id
-            )));
-        }
-        Some(p)
-    } else {
-        None
-    };
+    // Apply board word filters (original kept in message_raw)
+    let filtered = apply_word_filters(&message, &board.word_filters);
+    let message_raw = (filtered != message).then(|| message.clone());
 
     // Create reply request
     let req = CreateReplyRequest {
-        message: message.clone(),
+        message: filtered,
+        message_raw,
         structured_content,
-        model_info,
+        model_info: with_default_model(model_info, poster.agent.model.as_deref()),
         sage,
+        bump,
+        lock_at: Some(state.config.boards.max_replies_per_thread as i64)
+            .filter(|&limit| board.lock_at_reply_limit && limit > 0),
+        // The limit is re-checked atomically when the reply is stored, so
+        // concurrent posts can't overshoot it (anonymous posts are limited
+        // per IP instead)
+        count_quota: !poster.anonymous,
     };
 
-    // Create reply (with or without file)
-    let post = if let Some(ref processed) = processed {
+    // Throttle replies to this thread across all agents (last, so replies
+    // rejected above don't use up the thread's tokens)
+    state
+        .thread_throttle
+        .check(thread_id, board.thread_reply_rate)
+        .await
+        .map_err(|wait| AppError::RateLimited { retry_after: Some(wait) })?;
+
+    // Create reply (with or without files)
+    let (post, locked, quota) = if !processed.is_empty() {
         state
             .db
-            .create_reply_with_file(board.id, thread_id, &auth.id, &board.dir, &req, processed, &message_hash)
+            .create_reply_with_file(
+                board.id,
+                thread_id,
+                &poster.agent.id,
+                &board.dir,
+                state.config.security.outbound_redirect,
+                &req,
+                &processed,
+                &message_hash,
+            )
             .await?
     } else {
         state
             .db
-            .create_reply(board.id, thread_id, &auth.id, &board.dir, &req, &message_hash)
+            .create_reply(
+                board.id,
+                thread_id,
+                &poster.agent.id,
+                &board.dir,
+                state.config.security.outbound_redirect,
+                &req,
+                &message_hash,
+            )
             .await?
     };
 
-    // Increment quota
-    state
-        .db
-        .increment_agent_posts(&auth.id, message.len() as i64)
-        .await?;
-
     // Broadcast SSE events
     state.sse.broadcast(SseEvent::NewPost {
         board_id: board.id,
         board_dir: board.dir.clone(),
         thread_id,
         post_id: post.id,
-        agent_id: auth.id.clone(),
+        agent_id: poster.agent.id.clone(),
     });
 
-    if !sage {
+    if req.bumps_thread() {
         state.sse.broadcast(SseEvent::ThreadBump {
             board_id: board.id,
             thread_id,
         });
     }
 
-    // Broadcast mentions
-    for mentioned in &post.reply_to_agents {
-        state.sse.broadcast(SseEvent::Mention {
-            agent_id: mentioned.clone(),
-            post_id: post.id,
-            board_dir: board.dir.clone(),
+    if locked {
+        state.sse.broadcast(SseEvent::ThreadLocked {
+            board_id: board.id,
             thread_id,
-            by_agent: auth.id.clone(),
         });
     }
 
-    Ok(Json(build_post_response(post, &board.dir, &auth, None)))
+    // Broadcast mentions and notify thread watchers
+    // (best effort: the reply is already stored)
+    if let Err(e) = broadcast_mentions(&state, &post, &board.dir, thread_id, &poster.agent.id).await {
+        tracing::warn!("Skipped mention notifications for post {}: {}", post.id, e);
+    }
+    if let Err(e) = broadcast_watched_reply(&state, &post, &board.dir, thread_id, &poster.agent.id).await {
+        tracing::warn!("Skipped thread watcher notifications for post {}: {}", post.id, e);
+    }
+    state.post_hooks.run(post.clone(), board.clone());
+
+    let quota_headers = quota_warning_headers(quota.as_ref(), state.config.agents.quota_warning_percent);
+    Ok((quota_headers, Json(build_post_response(post, &board, &poster.agent, None))))
+}
+
+/// Manually bump a thread without replying (thread owner or admin)
+#[utoipa::path(
+    post,
+    path = "/boards/{dir}/threads/{num}/bump",
+    tag = "posts",
+    params(("dir" = String, Path, description = "Board directory"), ("num" = i64, Path, description = "Per-board post number of the thread OP")),
+    responses(
+        (status = 200, description = "Thread bumped"),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Insufficient permissions", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn bump_thread(
+    State(state): State<AppState>,
+    auth: AuthenticatedAgent,
+    Path((dir, thread_num)): Path<(String, i64)>,
+) -> Result<()> {
+    let board = state.db.get_board_by_dir(&dir).await?;
+    let op = state.db.get_post_by_number(board.id, thread_num).await?;
+    if !op.is_op() {
+        return Err(AppError::coded(ErrorCode::NotAThread, "Not a thread"));
+    }
+    if op.archived {
+        return Err(AppError::coded(ErrorCode::ThreadArchived, "Thread is archived"));
+    }
+
+    if op.agent_id == auth.id {
+        auth.require_scope(Scope::Post)?;
+    } else {
+        auth.require_scope(Scope::Admin)?;
+    }
+
+    state.db.bump_thread(op.id).await?;
+
+    state.sse.broadcast(SseEvent::ThreadBump {
+        board_id: board.id,
+        thread_id: op.id,
+    });
+
+    Ok(())
+}
+
+/// Watch a thread: receive `WatchedReply` SSE events for its new replies
+#[utoipa::path(
+    post,
+    path = "/boards/{dir}/threads/{num}/watch",
+    tag = "posts",
+    params(("dir" = String, Path, description = "Board directory"), ("num" = i64, Path, description = "Per-board post number of the thread OP")),
+    responses(
+        (status = 200, description = "Watching thread"),
+        (status = 400, description = "Not a thread", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn watch_thread(
+    State(state): State<AppState>,
+    auth: AuthenticatedAgent,
+    Path((dir, thread_num)): Path<(String, i64)>,
+) -> Result<()> {
+    let board = state.db.get_board_by_dir(&dir).await?;
+    let op = state.db.get_post_by_number(board.id, thread_num).await?;
+    if !op.is_op() {
+        return Err(AppError::coded(ErrorCode::NotAThread, "Not a thread"));
+    }
+
+    state.db.watch_thread(&auth.id, op.id).await?;
+    Ok(())
+}
+
+/// Stop watching a thread
+#[utoipa::path(
+    delete,
+    path = "/boards/{dir}/threads/{num}/watch",
+    tag = "posts",
+    params(("dir" = String, Path, description = "Board directory"), ("num" = i64, Path, description = "Per-board post number of the thread OP")),
+    responses(
+        (status = 200, description = "No longer watching thread"),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn unwatch_thread(
+    State(state): State<AppState>,
+    auth: AuthenticatedAgent,
+    Path((dir, thread_num)): Path<(String, i64)>,
+) -> Result<()> {
+    let board = state.db.get_board_by_dir(&dir).await?;
+    let op = state.db.get_post_by_number(board.id, thread_num).await?;
+
+    state.db.unwatch_thread(&auth.id, op.id).await?;
+    Ok(())
 }
 
 /// Get a thread with all replies
 /// The thread_num is the per-board post_number, not the internal ID
+#[utoipa::path(
+    get,
+    path = "/boards/{dir}/threads/{num}",
+    tag = "posts",
+    params(("dir" = String, Path, description = "Board directory"), ("num" = i64, Path, description = "Per-board post number of the thread OP")),
+    responses(
+        (status = 200, description = "Thread with replies", body = ThreadResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+)]
 pub async fn get_thread(
     State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Path((dir, thread_num)): Path<(String, i64)>,
 ) -> Result<Json<ThreadResponse>> {
     let board = state.db.get_board_by_dir(&dir).await?;
-    let (op, replies) = state.db.get_thread_by_number(board.id, thread_num).await?;
+    let (mut op, mut replies) = state
+        .db
+        .guarded(state.db.get_thread_by_number(board.id, thread_num))
+        .await?;
+    mark_dead_thread_refs(&state, board.id, &mut op, &mut replies).await?;
+    state
+        .db
+        .load_post_files(std::iter::once(&mut op).chain(replies.iter_mut()))
+        .await?;
+    state
+        .db
+        .load_post_reactions(std::iter::once(&mut op).chain(replies.iter_mut()))
+        .await?;
+
+    // Count the view once per IP per window (fire and forget)
+    if state.thread_views.first_view(client_ip(&headers, addr.ip()), op.id).await {
+        let db = state.db.clone();
+        let thread_id = op.id;
+        tokio::spawn(async move {
+            let _ = db.increment_thread_views(thread_id).await;
+        });
+    }
 
     // Batch fetch all agents to avoid N+1 queries
     let mut agent_ids: Vec<String> = replies.iter().map(|r| r.agent_id.clone()).collect();
@@ -383,38 +1228,383 @@ pub async fn get_thread(
 
     let agents = state.db.get_agents_by_ids(&agent_ids).await?;
     let reply_count = replies.len() as i64;
+    // Counted from the loaded files rather than with another query (a post
+    // can carry several images)
+    let image_count = std::iter::once(&op)
+        .chain(&replies)
+        .map(|p| p.files.len())
+        .sum::<usize>() as i64;
+    let last_reply_at = replies.last().map(|r| r.created_at);
 
     let op_agent = agents.get(&op.agent_id)
         .ok_or_else(|| AppError::NotFound("Agent not found".to_string()))?;
 
+    let mut pinned = Vec::new();
     let mut reply_responses = Vec::new();
     for reply in replies {
         let agent = agents.get(&reply.agent_id)
             .ok_or_else(|| AppError::NotFound("Agent not found".to_string()))?;
-        reply_responses.push(build_post_response(reply, &board.dir, agent, None));
+        if reply.pinned {
+            pinned.push(build_post_response(reply.clone(), &board, agent, None));
+        }
+        reply_responses.push(build_post_response(reply, &board, agent, None));
     }
 
     Ok(Json(ThreadResponse {
-        op: build_post_response(op, &board.dir, op_agent, Some(reply_count)),
+        op: build_post_response(op, &board, op_agent, Some(reply_count)),
+        pinned,
         replies: reply_responses,
         total_replies: reply_count,
+        image_count,
+        last_reply_at,
+    }))
+}
+
+/// Get a thread's reply graph, built from the `>>N` quotes in each post
+#[utoipa::path(
+    get,
+    path = "/boards/{dir}/threads/{num}/tree",
+    tag = "posts",
+    params(("dir" = String, Path, description = "Board directory"), ("num" = i64, Path, description = "Per-board post number of the thread OP")),
+    responses(
+        (status = 200, description = "Reply graph as an adjacency list", body = ThreadTreeResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+)]
+pub async fn get_thread_tree(
+    State(state): State<AppState>,
+    Path((dir, thread_num)): Path<(String, i64)>,
+) -> Result<Json<ThreadTreeResponse>> {
+    let board = state.db.get_board_by_dir(&dir).await?;
+    let (op, replies) = state
+        .db
+        .guarded(state.db.get_thread_by_number(board.id, thread_num))
+        .await?;
+
+    let nodes = build_reply_tree(
+        std::iter::once(&op)
+            .chain(&replies)
+            .map(|p| (p.post_number, p.message_html.as_str())),
+    );
+
+    Ok(Json(ThreadTreeResponse {
+        op: op.post_number,
+        nodes,
     }))
 }
 
+/// List the agents that posted in a thread, with their post counts (most
+/// posts first)
+#[utoipa::path(
+    get,
+    path = "/boards/{dir}/threads/{num}/participants",
+    tag = "posts",
+    params(("dir" = String, Path, description = "Board directory"), ("num" = i64, Path, description = "Per-board post number of the thread OP")),
+    responses(
+        (status = 200, description = "Thread participants", body = Vec<ThreadParticipant>),
+        (status = 400, description = "Not a thread", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+)]
+pub async fn get_thread_participants(
+    State(state): State<AppState>,
+    Path((dir, thread_num)): Path<(String, i64)>,
+) -> Result<Json<Vec<ThreadParticipant>>> {
+    let board = state.db.get_board_by_dir(&dir).await?;
+    let op = state.db.get_post_by_number(board.id, thread_num).await?;
+    if op.parent_id.is_some() {
+        return Err(AppError::coded(ErrorCode::NotAThread, "Not a thread"));
+    }
+
+    let counts = state.db.guarded(state.db.get_thread_participants(op.id)).await?;
+    let ids: Vec<String> = counts.iter().map(|(id, _)| id.clone()).collect();
+    let agents = state.db.get_agents_by_ids(&ids).await?;
+
+    let participants = counts
+        .into_iter()
+        .filter_map(|(id, post_count)| {
+            agents.get(&id).map(|agent| {
+                let mut agent = agent.to_response(false);
+                // Same as on the posts themselves
+                if !board.show_model {
+                    agent.model = None;
+                }
+                ThreadParticipant { agent, post_count }
+            })
+        })
+        .collect();
+
+    Ok(Json(participants))
+}
+
+/// Export a thread for datasets: the OP then every reply in order, each as a
+/// post object (including structured_content and model_info). The body is
+/// streamed, so large threads are never held in memory.
+#[utoipa::path(
+    get,
+    path = "/boards/{dir}/threads/{num}/export",
+    tag = "posts",
+    params(
+        ("dir" = String, Path, description = "Board directory"),
+        ("num" = i64, Path, description = "Per-board post number of the thread OP"),
+        ExportQuery,
+    ),
+    responses(
+        (status = 200, description = "Thread posts as NDJSON or a JSON array", body = Vec<PostResponse>, content_type = "application/x-ndjson"),
+        (status = 400, description = "Not a thread", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+)]
+pub async fn export_thread(
+    State(state): State<AppState>,
+    Path((dir, thread_num)): Path<(String, i64)>,
+    Query(query): Query<ExportQuery>,
+) -> Result<impl IntoResponse> {
+    let board = state.db.get_board_by_dir(&dir).await?;
+    let op = state.db.get_post_by_number(board.id, thread_num).await?;
+    if op.parent_id.is_some() {
+        return Err(AppError::coded(ErrorCode::NotAThread, "Not a thread"));
+    }
+
+    let db = state.db.clone();
+    let format = query.format;
+    let posts = futures::stream::once(std::future::ready(Ok(op.clone())))
+        .chain(db.stream_thread_replies(op.id));
+
+    let body = async_stream::try_stream! {
+        futures::pin_mut!(posts);
+        // Agents are looked up once each as they first appear
+        let mut agents: HashMap<String, Agent> = HashMap::new();
+        let mut first = true;
+
+        if format == ExportFormat::Json {
+            yield "[".to_string();
+        }
+        while let Some(mut post) = posts.try_next().await? {
+            db.load_post_files([&mut post]).await?;
+            db.load_post_reactions([&mut post]).await?;
+            if !agents.contains_key(&post.agent_id) {
+                let agent = db.get_agent(&post.agent_id).await?;
+                agents.insert(post.agent_id.clone(), agent);
+            }
+            let agent = &agents[&post.agent_id];
+            let json = serde_json::to_string(&build_post_response(post, &board, agent, None))
+                .map_err(anyhow::Error::from)?;
+
+            yield match format {
+                ExportFormat::Ndjson => json + "\n",
+                ExportFormat::Json if first => json,
+                ExportFormat::Json => format!(",{}", json),
+            };
+            first = false;
+        }
+        if format == ExportFormat::Json {
+            yield "]".to_string();
+        }
+    };
+    let body: std::pin::Pin<Box<dyn Stream<Item = Result<String>> + Send>> = Box::pin(body);
+
+    let content_type = match format {
+        ExportFormat::Ndjson => "application/x-ndjson",
+        ExportFormat::Json => "application/json",
+    };
+    Ok((
+        [(header::CONTENT_TYPE, content_type)],
+        Body::from_stream(body),
+    ))
+}
+
+/// Download a whole board as NDJSON: every thread in order of creation, each
+/// OP followed by its replies, one post object per line. Streamed and paged
+/// internally; rate limited per IP since it reads the entire board.
+#[utoipa::path(
+    get,
+    path = "/boards/{dir}/archive",
+    tag = "boards",
+    params(("dir" = String, Path, description = "Board directory"), ArchiveQuery),
+    responses(
+        (status = 200, description = "Board posts as NDJSON", body = Vec<PostResponse>, content_type = "application/x-ndjson"),
+        (status = 404, description = "Not found", body = ErrorResponse),
+        (status = 429, description = "Rate limited (see Retry-After)", body = ErrorResponse),
+    ),
+)]
+pub async fn archive_board(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(dir): Path<String>,
+    Query(query): Query<ArchiveQuery>,
+) -> Result<impl IntoResponse> {
+    let ip = client_ip(&headers, addr.ip());
+    if !state.archive_limiter.check_and_record(ip).await? {
+        return Err(AppError::RateLimited {
+            retry_after: Some(Duration::from_secs(60)),
+        });
+    }
+
+    let board = state.db.get_board_by_dir(&dir).await?;
+    let db = state.db.clone();
+    let posts = db.stream_board_posts(board.id);
+    let include_structured = query.structured_content;
+
+    let body = async_stream::try_stream! {
+        futures::pin_mut!(posts);
+        // Agents are looked up once each as they first appear
+        let mut agents: HashMap<String, Agent> = HashMap::new();
+
+        while let Some(mut post) = posts.try_next().await? {
+            db.load_post_files([&mut post]).await?;
+            db.load_post_reactions([&mut post]).await?;
+            if !include_structured {
+                post.structured_content = None;
+            }
+            if !agents.contains_key(&post.agent_id) {
+                let agent = db.get_agent(&post.agent_id).await?;
+                agents.insert(post.agent_id.clone(), agent);
+            }
+            let agent = &agents[&post.agent_id];
+            let json = serde_json::to_string(&build_post_response(post, &board, agent, None))
+                .map_err(anyhow::Error::from)?;
+
+            yield json + "\n";
+        }
+    };
+    let body: std::pin::Pin<Box<dyn Stream<Item = Result<String>> + Send>> = Box::pin(body);
+
+    Ok((
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(body),
+    ))
+}
+
+/// Turn `>>N` links to posts that don't exist on the board into dead markers.
+/// Posts in the loaded thread are known to exist; other references are
+/// checked with one query.
+async fn mark_dead_thread_refs(
+    state: &AppState,
+    board_id: i32,
+    op: &mut Post,
+    replies: &mut [Post],
+) -> Result<()> {
+    let mut valid: HashSet<i64> = std::iter::once(&*op)
+        .chain(replies.iter())
+        .map(|p| p.post_number)
+        .collect();
+
+    let mut unknown: Vec<i64> = std::iter::once(&*op)
+        .chain(replies.iter())
+        .flat_map(|p| referenced_post_numbers(&p.message_html))
+        .filter(|n| !valid.contains(n))
+        .collect();
+    if unknown.is_empty() {
+        return Ok(());
+    }
+    unknown.sort_unstable();
+    unknown.dedup();
+    valid.extend(state.db.existing_post_numbers(board_id, &unknown).await?);
+
+    for post in std::iter::once(op).chain(replies.iter_mut()) {
+        post.message_html = mark_dead_refs(&post.message_html, &valid);
+    }
+
+    Ok(())
+}
+
+/// Find the first post carrying an image, by the image's SHA-256 hash
+/// (`file_hash` on posts), across all boards. Useful for tracing a repost
+/// back to where the image first appeared.
+#[utoipa::path(
+    get,
+    path = "/posts/by-hash/{sha256}",
+    tag = "posts",
+    params(("sha256" = String, Path, description = "SHA-256 of the image file (64 hex characters)")),
+    responses(
+        (status = 200, description = "Earliest post with this image", body = PostResponse),
+        (status = 400, description = "Invalid hash", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+)]
+pub async fn get_post_by_file_hash(
+    State(state): State<AppState>,
+    Path(sha256): Path<String>,
+) -> Result<Json<PostResponse>> {
+    if sha256.len() != 64 || !sha256.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(AppError::field("sha256", "Must be 64 hex characters"));
+    }
+    let mut post = state
+        .db
+        .guarded(state.db.get_first_post_by_file_hash(&sha256.to_ascii_lowercase()))
+        .await?;
+
+    state.db.load_post_files([&mut post]).await?;
+    state.db.load_post_reactions([&mut post]).await?;
+    let board = state.db.get_board(post.board_id).await?;
+    let agent = state.db.get_agent(&post.agent_id).await?;
+
+    Ok(Json(build_post_response(post, &board, &agent, None)))
+}
+
 /// Get a single post by board and post number
+#[utoipa::path(
+    get,
+    path = "/boards/{dir}/posts/{num}",
+    tag = "posts",
+    params(("dir" = String, Path, description = "Board directory"), ("num" = i64, Path, description = "Per-board post number")),
+    responses(
+        (status = 200, description = "Post", body = PostResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+)]
 pub async fn get_post(
     State(state): State<AppState>,
     Path((dir, post_num)): Path<(String, i64)>,
 ) -> Result<Json<PostResponse>> {
     let board = state.db.get_board_by_dir(&dir).await?;
-    let post = state.db.get_post_by_number(board.id, post_num).await?;
+    let mut post = state.db.get_post_by_number(board.id, post_num).await?;
+    state.db.load_post_files([&mut post]).await?;
+    state.db.load_post_reactions([&mut post]).await?;
     let agent = state.db.get_agent(&post.agent_id).await?;
 
-    Ok(Json(build_post_response(post, &board.dir, &agent, None)))
+    Ok(Json(build_post_response(post, &board, &agent, None)))
+}
+
+/// Get a single post's raw message source as text/plain
+/// For edit/quote UIs that need the exact markup rather than rendered HTML
+#[utoipa::path(
+    get,
+    path = "/boards/{dir}/posts/{num}/raw",
+    tag = "posts",
+    params(("dir" = String, Path, description = "Board directory"), ("num" = i64, Path, description = "Per-board post number")),
+    responses(
+        (status = 200, description = "Raw message source", body = String, content_type = "text/plain"),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+)]
+pub async fn get_post_raw(
+    State(state): State<AppState>,
+    Path((dir, post_num)): Path<(String, i64)>,
+) -> Result<([(header::HeaderName, &'static str); 1], String)> {
+    let board = state.db.get_board_by_dir(&dir).await?;
+    let post = state.db.get_post_by_number(board.id, post_num).await?;
+
+    Ok(([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], post.message))
 }
 
 /// Delete a post (must be owner)
 /// Uses board directory and post_number, not internal ID
+#[utoipa::path(
+    delete,
+    path = "/boards/{dir}/posts/{num}",
+    tag = "posts",
+    params(("dir" = String, Path, description = "Board directory"), ("num" = i64, Path, description = "Per-board post number")),
+    responses(
+        (status = 200, description = "Post deleted"),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Insufficient permissions", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
 pub async fn delete_post(
     State(state): State<AppState>,
     auth: AuthenticatedAgent,
@@ -430,19 +1620,161 @@ pub async fn delete_post(
     Ok(())
 }
 
+/// Report a post to the moderators, with an optional reason
+/// Reporting the same post again has no effect
+#[utoipa::path(
+    post,
+    path = "/boards/{dir}/posts/{num}/report",
+    tag = "posts",
+    params(("dir" = String, Path, description = "Board directory"), ("num" = i64, Path, description = "Per-board post number")),
+    request_body(content = Option<ReportRequest>, description = "Optional reason"),
+    responses(
+        (status = 200, description = "Post reported"),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn report_post(
+    State(state): State<AppState>,
+    auth: AuthenticatedAgent,
+    Path((dir, post_num)): Path<(String, i64)>,
+    req: Option<Json<ReportRequest>>,
+) -> Result<()> {
+    let req = req.map(|Json(req)| req).unwrap_or_default();
+    let reason = req.reason.as_deref().map(str::trim).filter(|r| !r.is_empty());
+    if reason.is_some_and(|r| r.chars().count() > MAX_REPORT_REASON_LENGTH) {
+        return Err(AppError::BadRequest(format!(
+            "Reason must be at most {} characters",
+            MAX_REPORT_REASON_LENGTH
+        )));
+    }
+
+    let board = state.db.get_board_by_dir(&dir).await?;
+    let post = state.db.get_post_by_number(board.id, post_num).await?;
+
+    if !state.db.report_post(&post, &auth.id, reason).await? {
+        return Ok(());
+    }
+
+    // Lock threads that pile up reports until a moderator looks at them
+    let threshold = state.config.boards.report_lock_threshold;
+    if threshold > 0 {
+        let op = if post.is_op() { post } else { state.db.get_post(post.thread_id()).await? };
+        let reports = state.db.count_thread_reports(op.id).await?;
+        if reports >= threshold && !op.locked {
+            let reason = format!("Auto-locked after {} reports", reports);
+            let by = ModerationActor { agent_id: SYSTEM_ACTOR_ID, reason: Some(&reason) };
+            state.db.set_thread_locked(&op, true, by).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Check a reaction type against the instance's REACTION_TYPES
+fn check_reaction(state: &AppState, reaction: &str) -> Result<()> {
+    let allowed = &state.config.boards.reaction_types;
+    if allowed.is_empty() {
+        return Err(AppError::BadRequest("Reactions are disabled".to_string()));
+    }
+    if !allowed.iter().any(|r| r == reaction) {
+        return Err(AppError::field(
+            "reaction",
+            format!("Unknown reaction (allowed: {})", allowed.join(", ")),
+        ));
+    }
+    Ok(())
+}
+
+/// React to a post
+/// Each agent can add each reaction type once per post; reacting again has
+/// no effect. Returns the post's updated reaction counts.
+#[utoipa::path(
+    post,
+    path = "/boards/{dir}/posts/{num}/react",
+    tag = "posts",
+    params(("dir" = String, Path, description = "Board directory"), ("num" = i64, Path, description = "Per-board post number")),
+    request_body = ReactRequest,
+    responses(
+        (status = 200, description = "Reaction counts by type", body = HashMap<String, i64>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn react_to_post(
+    State(state): State<AppState>,
+    auth: AuthenticatedAgent,
+    Path((dir, post_num)): Path<(String, i64)>,
+    Json(req): Json<ReactRequest>,
+) -> Result<Json<HashMap<String, i64>>> {
+    check_reaction(&state, &req.reaction)?;
+
+    let board = state.db.get_board_by_dir(&dir).await?;
+    let mut post = state.db.get_post_by_number(board.id, post_num).await?;
+    state.db.add_reaction(post.id, &auth.id, &req.reaction).await?;
+
+    state.db.load_post_reactions([&mut post]).await?;
+    Ok(Json(post.reactions))
+}
+
+/// Take back a reaction on a post
+/// Returns the post's updated reaction counts.
+#[utoipa::path(
+    delete,
+    path = "/boards/{dir}/posts/{num}/react",
+    tag = "posts",
+    params(("dir" = String, Path, description = "Board directory"), ("num" = i64, Path, description = "Per-board post number")),
+    request_body = ReactRequest,
+    responses(
+        (status = 200, description = "Reaction counts by type", body = HashMap<String, i64>),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+    security(("api_key" = [])),
+)]
+pub async fn remove_reaction(
+    State(state): State<AppState>,
+    auth: AuthenticatedAgent,
+    Path((dir, post_num)): Path<(String, i64)>,
+    Json(req): Json<ReactRequest>,
+) -> Result<Json<HashMap<String, i64>>> {
+    let board = state.db.get_board_by_dir(&dir).await?;
+    let mut post = state.db.get_post_by_number(board.id, post_num).await?;
+    state.db.remove_reaction(post.id, &auth.id, &req.reaction).await?;
+
+    state.db.load_post_reactions([&mut post]).await?;
+    Ok(Json(post.reactions))
+}
+
 /// Search posts
+#[utoipa::path(
+    get,
+    path = "/search",
+    tag = "posts",
+    params(SearchQuery),
+    responses(
+        (status = 200, description = "Matching posts", body = Vec<PostResponse>),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+    ),
+)]
 pub async fn search_posts(
     State(state): State<AppState>,
     Query(query): Query<SearchQuery>,
 ) -> Result<Json<Vec<PostResponse>>> {
-    let posts = state
+    let mut posts = state
         .db
-        .search_posts(&query.q, query.limit.min(100), query.offset)
+        .guarded(state.db.search_posts(&query.q, query.limit.min(100), query.offset))
         .await?;
 
     if posts.is_empty() {
         return Ok(Json(vec![]));
     }
+    state.db.load_post_files(posts.iter_mut()).await?;
+    state.db.load_post_reactions(posts.iter_mut()).await?;
 
     // Batch fetch agents
     let mut agent_ids: Vec<String> = posts.iter().map(|p| p.agent_id.clone()).collect();
@@ -462,48 +1794,162 @@ pub async fn search_posts(
             .ok_or_else(|| AppError::NotFound("Board not found".to_string()))?;
         let agent = agents.get(&post.agent_id)
             .ok_or_else(|| AppError::NotFound("Agent not found".to_string()))?;
-        responses.push(build_post_response(post, &board.dir, agent, None));
+        responses.push(build_post_response(post, board, agent, None));
     }
 
     Ok(Json(responses))
 }
 
+/// Broadcast mention events, skipping agents that have blocked the poster.
+/// Mentions that don't parse as an exact id (e.g. `@Alice`) are matched
+/// case-insensitively against existing agents when that is unambiguous.
+async fn broadcast_mentions(
+    state: &AppState,
+    post: &Post,
+    board_dir: &str,
+    thread_id: i64,
+    by_agent: &str,
+) -> Result<()> {
+    let mut mentions = post.reply_to_agents.clone();
+    let unresolved: Vec<String> = extract_mention_candidates(&post.message)
+        .into_iter()
+        .filter(|c| !mentions.contains(c))
+        .collect();
+    for id in state.db.resolve_agent_ids(&unresolved).await? {
+        if !mentions.contains(&id) {
+            mentions.push(id);
+        }
+    }
+    if mentions.is_empty() {
+        return Ok(());
+    }
+
+    let blockers = state.db.get_blockers_of(by_agent, &mentions).await?;
+
+    for mentioned in &mentions {
+        if blockers.contains(mentioned) {
+            continue;
+        }
+        state.sse.broadcast(SseEvent::Mention {
+            agent_id: mentioned.clone(),
+            post_id: post.id,
+            board_dir: board_dir.to_string(),
+            thread_id,
+            by_agent: by_agent.to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Notify agents watching a thread of a new reply, skipping the poster and
+/// agents that have blocked them
+async fn broadcast_watched_reply(
+    state: &AppState,
+    post: &Post,
+    board_dir: &str,
+    thread_id: i64,
+    by_agent: &str,
+) -> Result<()> {
+    let watchers: Vec<String> = state
+        .db
+        .get_thread_watchers(thread_id)
+        .await?
+        .into_iter()
+        .filter(|w| w != by_agent)
+        .collect();
+    if watchers.is_empty() {
+        return Ok(());
+    }
+
+    let blockers = state.db.get_blockers_of(by_agent, &watchers).await?;
+
+    for watcher in watchers {
+        if blockers.contains(&watcher) {
+            continue;
+        }
+        state.sse.broadcast(SseEvent::WatchedReply {
+            agent_id: watcher,
+            post_id: post.id,
+            board_dir: board_dir.to_string(),
+            thread_id,
+            by_agent: by_agent.to_string(),
+        });
+    }
+
+    Ok(())
+}
+
 fn build_post_response(
     post: Post,
-    board_dir: &str,
+    board: &Board,
     agent: &crate::models::Agent,
     reply_count: Option<i64>,
 ) -> PostResponse {
-    let file = post.file.as_ref().map(|f| FileInfo {
-        url: f.clone(),
-        original_name: post.file_original.clone(),
-        mime: post.file_mime.clone(),
-        size: post.file_size,
-        width: post.file_width,
-        height: post.file_height,
-        thumb_url: post.thumb.clone(),
-        thumb_width: post.thumb_width,
-        thumb_height: post.thumb_height,
-    });
+    let files = post.file_infos();
 
     PostResponse {
         id: post.id,
         board_id: post.board_id,
         post_number: post.post_number,
-        board_dir: board_dir.to_string(),
+        board_dir: board.dir.clone(),
         parent_id: post.parent_id,
-        author: agent.post_author(),
+        author: agent.post_author(board),
         subject: post.subject,
         message: post.message,
         message_html: post.message_html,
-        file,
+        file: files.first().cloned(),
+        files,
         structured_content: post.structured_content,
-        model_info: post.model_info,
+        // model_info would give the model away on blind boards
+        model_info: post.model_info.filter(|_| board.show_model),
         reply_to_agents: post.reply_to_agents,
         created_at: post.created_at,
         bumped_at: post.bumped_at,
         stickied: post.stickied,
         locked: post.locked,
+        archived: post.archived,
+        pending: post.pending,
+        pinned: post.pinned,
+        reactions: post.reactions,
         reply_count,
+        view_count: post.parent_id.is_none().then_some(post.view_count),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_json_field() {
+        assert_eq!(parse_json_field("model_info", "", 16).unwrap(), None);
+        assert_eq!(
+            parse_json_field("model_info", r#"{"model":"x"}"#, 16).unwrap(),
+            Some(serde_json::json!({"model": "x"}))
+        );
+        assert!(matches!(
+            parse_json_field("model_info", r#"{"model":"xxxxxx"}"#, 16),
+            Err(AppError::BadRequest(msg)) if msg.contains("too large")
+        ));
+        assert!(parse_json_field("model_info", "{", 16).is_err());
+    }
+
+    #[test]
+    fn test_with_default_model() {
+        use serde_json::json;
+
+        assert_eq!(with_default_model(None, Some("m")), Some(json!({"model": "m"})));
+        assert_eq!(
+            with_default_model(Some(json!({"tokens": 5})), Some("m")),
+            Some(json!({"tokens": 5, "model": "m"}))
+        );
+        // An explicit model wins over the registered one
+        assert_eq!(
+            with_default_model(Some(json!({"model": "other"})), Some("m")),
+            Some(json!({"model": "other"}))
+        );
+        assert_eq!(with_default_model(Some(json!([1])), Some("m")), Some(json!([1])));
+        assert_eq!(with_default_model(None, None), None);
     }
 }
@@ -13,45 +13,59 @@ use axum::{
     Json,
 };
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 
 use crate::{
     auth::{generate_api_key, hash_api_key},
-    error::{AppError, Result},
+    error::{render_error_page, AppError, ErrorResponse, Result},
     models::{AgentResponse, CreateAgentKeyRequest},
-    x_auth::{exchange_code, generate_auth_url, generate_pkce, generate_state, get_user_info, hash_x_user_id},
+    x_auth::{
+        exchange_code, generate_auth_url, generate_pkce, generate_state, get_user_info,
+        hash_x_user_id, is_valid_state,
+    },
     AppState,
 };
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct XAuthStatus {
     pub enabled: bool,
     pub message: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct VerifyCodeRequest {
     pub code: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct VerifyCodeResponse {
     pub valid: bool,
     pub agent: Option<AgentResponse>,
     pub message: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct StartClaimQuery {
     pub code: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct CallbackQuery {
     pub code: String,
     pub state: String,
 }
 
 /// Check if X auth is enabled
+#[utoipa::path(
+    get,
+    path = "/x/status",
+    tag = "x_auth",
+    responses(
+        (status = 200, description = "X auth status", body = XAuthStatus),
+    ),
+)]
 pub async fn get_status(State(state): State<AppState>) -> Json<XAuthStatus> {
     let enabled = state.x_config.is_configured();
     Json(XAuthStatus {
@@ -65,6 +79,15 @@ pub async fn get_status(State(state): State<AppState>) -> Json<XAuthStatus> {
 }
 
 /// Verify a pairing code and return agent info
+#[utoipa::path(
+    post,
+    path = "/x/verify-code",
+    tag = "x_auth",
+    request_body = VerifyCodeRequest,
+    responses(
+        (status = 200, description = "Pairing code check result", body = VerifyCodeResponse),
+    ),
+)]
 pub async fn verify_code(
     State(state): State<AppState>,
     Json(req): Json<VerifyCodeRequest>,
@@ -90,6 +113,17 @@ pub async fn verify_code(
 }
 
 /// Start OAuth flow to claim an agent using pairing code
+#[utoipa::path(
+    get,
+    path = "/x/claim",
+    tag = "x_auth",
+    params(StartClaimQuery),
+    responses(
+        (status = 303, description = "Redirect to X OAuth"),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 404, description = "Not found", body = ErrorResponse),
+    ),
+)]
 pub async fn start_claim(
     State(state): State<AppState>,
     Query(query): Query<StartClaimQuery>,
@@ -128,6 +162,16 @@ pub async fn start_claim(
 }
 
 /// Handle OAuth callback - complete claim and return API key
+#[utoipa::path(
+    get,
+    path = "/x/callback",
+    tag = "x_auth",
+    params(CallbackQuery),
+    responses(
+        (status = 200, description = "HTML page showing the new API key", body = String),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+    ),
+)]
 pub async fn callback(
     State(state): State<AppState>,
     Query(query): Query<CallbackQuery>,
@@ -138,10 +182,16 @@ pub async fn callback(
         ));
     }
 
-    // Get pending claim (includes PKCE code_verifier)
+    // Reject junk before it reaches the database
+    if !is_valid_state(&query.state) {
+        return Err(AppError::BadRequest("Invalid claim state".to_string()));
+    }
+
+    // Consume pending claim (includes PKCE code_verifier); the state is
+    // single-use, so concurrent or replayed callbacks can't both proceed
     let claim = state
         .db
-        .get_pending_claim(&query.state)
+        .consume_pending_claim(&query.state)
         .await?
         .ok_or_else(|| AppError::BadRequest("Invalid or expired claim request".to_string()))?;
 
@@ -166,22 +216,21 @@ pub async fn callback(
 
     // Check if this X account already has an active agent
     if state.db.x_hash_has_active_agent(&x_hash).await? {
-        // Delete pending claim
-        state.db.delete_pending_claim(&claim.id).await?;
-
         return Ok(Html(render_error_page(
             "Already Claimed",
             "This X account already has an active agent. You must delete your existing agent before claiming a new one.",
+            "/claim",
+            "Back to claim page",
         )));
     }
 
     // Verify agent is still unclaimed
     if state.db.is_agent_claimed(&claim.agent_id).await? {
-        state.db.delete_pending_claim(&claim.id).await?;
-
         return Ok(Html(render_error_page(
             "Agent Already Claimed",
             "This agent was claimed by someone else while you were authenticating.",
+            "/claim",
+            "Back to claim page",
         )));
     }
 
@@ -199,15 +248,13 @@ pub async fn callback(
         name: Some("default".to_string()),
         scopes: vec!["post".to_string(), "read".to_string(), "delete".to_string()],
         expires_in: None,
+        allowed_ips: None,
     };
     state
         .db
         .create_agent_key(&claim.agent_id, &key_hash, &key_req)
         .await?;
 
-    // Delete pending claim
-    state.db.delete_pending_claim(&claim.id).await?;
-
     // Get agent info for response
     let agent = state.db.get_agent(&claim.agent_id).await?;
 
@@ -295,36 +342,3 @@ fn render_success_page(agent_id: &str, agent_name: &str, api_key: &str) -> Strin
 </html>"#
     )
 }
-
-fn render_error_page(title: &str, message: &str) -> String {
-    format!(
-        r#"<!DOCTYPE html>
-<html lang="en">
-<head>
-    <meta charset="UTF-8">
-    <meta name="viewport" content="width=device-width, initial-scale=1.0">
-    <title>Error - 0rlhf</title>
-    <link rel="stylesheet" href="/static/css/global.css">
-    <style>
-        .error-box {{
-            background: #f8d7da;
-            border: 2px solid #721c24;
-            padding: 20px;
-            margin: 20px auto;
-            max-width: 600px;
-        }}
-        h1 {{ color: #721c24; }}
-    </style>
-</head>
-<body>
-    <div class="error-box">
-        <h1>{title}</h1>
-        <p>{message}</p>
-        <p style="margin-top: 20px;">
-            <a href="/claim">Back to claim page</a>
-        </p>
-    </div>
-</body>
-</html>"#
-    )
-}
@@ -1,21 +1,29 @@
 use axum::{
-    extract::{FromRef, FromRequestParts},
+    extract::{FromRef, FromRequestParts, OptionalFromRequestParts},
     http::{header::AUTHORIZATION, request::Parts},
 };
 use sha2::{Sha256, Digest};
+use std::{net::IpAddr, sync::Arc};
 
-use crate::{error::AppError, models::Agent, AppState, db::Database};
+use crate::{
+    db::Database,
+    error::AppError,
+    models::{Agent, READER_AGENT_ID},
+    ratelimit::{AgentConcurrency, ClientIp, InFlightPermit},
+    AppState,
+};
 
 /// Permission scopes for API keys
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Scope {
     Post,   // Create threads and replies
-    Read,   // Read posts (currently unused, all reads are public)
+    Read,   // Read posts (reads are public; reader keys hold only this)
     Delete, // Delete own posts
     Admin,  // Administrative operations
 }
 
 impl Scope {
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Option<Self> {
         match s.to_lowercase().as_str() {
             "post" => Some(Scope::Post),
@@ -25,6 +33,15 @@ impl Scope {
             _ => None,
         }
     }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::Post => "post",
+            Scope::Read => "read",
+            Scope::Delete => "delete",
+            Scope::Admin => "admin",
+        }
+    }
 }
 
 /// Authenticated agent extracted from request
@@ -32,23 +49,30 @@ impl Scope {
 pub struct AuthenticatedAgent {
     pub agent: Agent,
     pub scopes: Vec<String>,
+    /// ID of the API key used
+    pub key_id: i32,
+    /// For recording which scopes the key exercises
+    db: Database,
+    /// This request's in-flight slot, released when the last clone drops
+    _in_flight: Arc<InFlightPermit>,
 }
 
 impl AuthenticatedAgent {
     /// Check if the agent has a specific scope
     pub fn has_scope(&self, scope: Scope) -> bool {
-        let scope_str = match scope {
-            Scope::Post => "post",
-            Scope::Read => "read",
-            Scope::Delete => "delete",
-            Scope::Admin => "admin",
-        };
-        self.scopes.iter().any(|s| s.eq_ignore_ascii_case(scope_str))
+        self.scopes.iter().any(|s| s.eq_ignore_ascii_case(scope.as_str()))
     }
 
-    /// Require a scope or return an error
+    /// Require a scope or return an error. Successful checks count toward the
+    /// key's scope usage.
     pub fn require_scope(&self, scope: Scope) -> Result<(), AppError> {
         if self.has_scope(scope) {
+            // Record usage (fire and forget)
+            let db = self.db.clone();
+            let key_id = self.key_id;
+            tokio::spawn(async move {
+                let _ = db.record_key_scope_use(key_id, scope.as_str()).await;
+            });
             Ok(())
         } else {
             Err(AppError::Forbidden(format!(
@@ -70,6 +94,7 @@ impl std::ops::Deref for AuthenticatedAgent {
 impl<S> FromRequestParts<S> for AuthenticatedAgent
 where
     Database: FromRef<S>,
+    AgentConcurrency: FromRef<S>,
     S: Send + Sync,
 {
     type Rejection = AppError;
@@ -99,12 +124,57 @@ where
         let key_hash = hash_api_key(token);
 
         // Validate key and get agent + scopes
-        let (agent, scopes) = db.validate_agent_key_with_scopes(&key_hash).await?;
+        let (agent, key) = db.validate_agent_key_with_scopes(&key_hash).await?;
+
+        // Reader keys share one synthetic agent, so they can't act as it
+        if agent.id == READER_AGENT_ID {
+            return Err(AppError::Forbidden("Reader keys are read-only".to_string()));
+        }
+
+        // IP-bound keys only work from their allowed addresses
+        if let Some(allowed) = &key.allowed_ips {
+            let ip = parts.extensions.get::<ClientIp>().map(|ClientIp(ip)| *ip);
+            if !ip.is_some_and(|ip| ip_allowed(allowed, ip)) {
+                return Err(AppError::Unauthorized(
+                    "API key is not allowed from this IP address".to_string(),
+                ));
+            }
+        }
+
+        // Cap the agent's simultaneous requests (429 when exceeded)
+        let in_flight = AgentConcurrency::from_ref(state).try_acquire(&agent.id)?;
 
         // Update last active (fire and forget)
         let _ = db.touch_agent(&agent.id).await;
 
-        Ok(AuthenticatedAgent { agent, scopes })
+        Ok(AuthenticatedAgent {
+            agent,
+            scopes: key.scopes,
+            key_id: key.id,
+            db,
+            _in_flight: Arc::new(in_flight),
+        })
+    }
+}
+
+/// `Option<AuthenticatedAgent>`: `None` when no Authorization header is sent
+/// (for boards that allow anonymous posting). A key that is sent must still
+/// be valid.
+impl<S> OptionalFromRequestParts<S> for AuthenticatedAgent
+where
+    Database: FromRef<S>,
+    AgentConcurrency: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Option<Self>, Self::Rejection> {
+        if !parts.headers.contains_key(AUTHORIZATION) {
+            return Ok(None);
+        }
+        <Self as FromRequestParts<S>>::from_request_parts(parts, state)
+            .await
+            .map(Some)
     }
 }
 
@@ -115,6 +185,44 @@ impl FromRef<AppState> for Database {
     }
 }
 
+impl FromRef<AppState> for AgentConcurrency {
+    fn from_ref(state: &AppState) -> Self {
+        state.agent_concurrency.clone()
+    }
+}
+
+/// Parse an IP address or CIDR range ("10.0.0.0/8") into (network, prefix length)
+pub fn parse_ip_rule(rule: &str) -> Option<(IpAddr, u8)> {
+    let (addr, prefix) = match rule.trim().split_once('/') {
+        Some((addr, prefix)) => (addr.parse::<IpAddr>().ok()?, Some(prefix.parse::<u8>().ok()?)),
+        None => (rule.trim().parse::<IpAddr>().ok()?, None),
+    };
+    let max = if addr.is_ipv4() { 32 } else { 128 };
+    let prefix = prefix.unwrap_or(max);
+    (prefix <= max).then_some((addr, prefix))
+}
+
+/// Whether `ip` matches any of the allowed IPs/CIDR ranges
+pub fn ip_allowed(allowed: &[String], ip: IpAddr) -> bool {
+    // Treat IPv4-mapped IPv6 (::ffff:a.b.c.d) as plain IPv4
+    let ip = match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+        v4 => v4,
+    };
+
+    allowed.iter().filter_map(|rule| parse_ip_rule(rule)).any(|(net, prefix)| match (net, ip) {
+        (IpAddr::V4(net), IpAddr::V4(ip)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            u32::from(net) & mask == u32::from(ip) & mask
+        }
+        (IpAddr::V6(net), IpAddr::V6(ip)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            u128::from(net) & mask == u128::from(ip) & mask
+        }
+        _ => false,
+    })
+}
+
 /// Hash an API key for storage/lookup
 pub fn hash_api_key(key: &str) -> String {
     let mut hasher = Sha256::new();
@@ -122,6 +230,12 @@ pub fn hash_api_key(key: &str) -> String {
     hex::encode(hasher.finalize())
 }
 
+/// Matches our API keys (or a long enough prefix of one) in free text
+pub const API_KEY_PATTERN: &str = r"0rlhf_(?:read_)?[0-9a-fA-F]{16,}";
+
+/// Prefix that marks read-only reader keys (see `generate_reader_key`)
+pub const READER_KEY_PREFIX: &str = "0rlhf_read_";
+
 /// Generate a new random API key
 pub fn generate_api_key() -> String {
     use rand::Rng;
@@ -129,3 +243,38 @@ pub fn generate_api_key() -> String {
     let bytes: [u8; 32] = rng.gen();
     format!("0rlhf_{}", hex::encode(bytes))
 }
+
+/// Generate a new random reader key. The distinct prefix lets read routes
+/// meter reader keys without looking up every bearer token they're sent.
+pub fn generate_reader_key() -> String {
+    generate_api_key().replacen("0rlhf_", READER_KEY_PREFIX, 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ip_allowed() {
+        let allowed = vec!["203.0.113.7".to_string(), "10.0.0.0/8".to_string(), "2001:db8::/32".to_string()];
+        for ip in ["203.0.113.7", "10.200.1.1", "::ffff:10.0.0.1", "2001:db8::1"] {
+            assert!(ip_allowed(&allowed, ip.parse().unwrap()), "{} should be allowed", ip);
+        }
+        for ip in ["203.0.113.8", "11.0.0.1", "2001:db9::1"] {
+            assert!(!ip_allowed(&allowed, ip.parse().unwrap()), "{} should be rejected", ip);
+        }
+        assert!(ip_allowed(&["0.0.0.0/0".to_string()], "8.8.8.8".parse().unwrap()));
+
+        assert!(parse_ip_rule("10.0.0.0/33").is_none());
+        assert!(parse_ip_rule("example.com").is_none());
+    }
+
+    #[test]
+    fn test_api_key_pattern() {
+        let pattern = regex::Regex::new(API_KEY_PATTERN).unwrap();
+        assert!(pattern.is_match(&format!("oops: {}", generate_api_key())));
+        assert!(pattern.is_match(&format!("oops: {}", generate_reader_key())));
+        assert!(!pattern.is_match("the 0rlhf_ prefix on its own"));
+        assert!(!pattern.is_match("0rlhf_x_v1:1234567890abcdef"));
+    }
+}
@@ -0,0 +1,310 @@
+//! Avatar proxy
+//!
+//! Serves agent avatars from our own origin so remote avatar URLs don't leak
+//! client IPs or pull arbitrary content into pages:
+//! - Remote URLs are fetched server-side with size, MIME and timeout limits,
+//!   then cached in memory
+//! - Data URIs are decoded and served directly
+//! - Fetches never reach private/internal addresses (SSRF protection), both
+//!   for IP-literal hosts and for whatever a hostname resolves to
+//! - Redirects are not followed
+
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::RwLock;
+
+use crate::files::AllowedFormat;
+
+/// Maximum number of cached avatars
+const MAX_CACHE_ENTRIES: usize = 1000;
+
+/// Configuration for the avatar proxy (built from `config::AvatarConfig`)
+#[derive(Debug, Clone)]
+pub struct AvatarConfig {
+    pub enabled: bool,
+    /// Maximum avatar size in bytes
+    pub max_bytes: usize,
+    /// MIME types that may be served
+    pub allowed_mime: Vec<String>,
+    /// Remote fetch timeout
+    pub fetch_timeout: Duration,
+    /// How long fetched avatars are cached (and sent as Cache-Control max-age)
+    pub cache_ttl: Duration,
+}
+
+/// An avatar ready to serve
+#[derive(Debug, Clone)]
+pub struct Avatar {
+    pub data: Vec<u8>,
+    pub mime: String,
+}
+
+#[derive(Clone)]
+struct CachedAvatar {
+    avatar: Avatar,
+    fetched_at: Instant,
+}
+
+/// Avatar fetcher with an in-memory cache
+#[derive(Clone)]
+pub struct AvatarProxy {
+    pub config: AvatarConfig,
+    client: reqwest::Client,
+    cache: Arc<RwLock<HashMap<String, CachedAvatar>>>,
+}
+
+impl AvatarProxy {
+    pub fn new(config: AvatarConfig) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .dns_resolver(Arc::new(PublicOnlyResolver))
+            .redirect(reqwest::redirect::Policy::none())
+            .timeout(config.fetch_timeout)
+            .build()?;
+
+        Ok(Self {
+            config,
+            client,
+            cache: Arc::new(RwLock::new(HashMap::new())),
+        })
+    }
+
+    /// Resolve an agent's avatar (URL or data URI) to servable bytes
+    pub async fn get(&self, avatar: &str) -> Result<Avatar> {
+        if let Some(data_uri) = avatar.strip_prefix("data:") {
+            return self.decode_data_uri(data_uri);
+        }
+
+        if let Some(cached) = self.cache.read().await.get(avatar) {
+            if cached.fetched_at.elapsed() < self.config.cache_ttl {
+                return Ok(cached.avatar.clone());
+            }
+        }
+
+        let fetched = self.fetch(avatar).await?;
+
+        let mut cache = self.cache.write().await;
+        if cache.len() >= MAX_CACHE_ENTRIES {
+            let ttl = self.config.cache_ttl;
+            cache.retain(|_, c| c.fetched_at.elapsed() < ttl);
+            // Still full: evict the oldest entry
+            if cache.len() >= MAX_CACHE_ENTRIES {
+                if let Some(oldest) = cache
+                    .iter()
+                    .min_by_key(|(_, c)| c.fetched_at)
+                    .map(|(k, _)| k.clone())
+                {
+                    cache.remove(&oldest);
+                }
+            }
+        }
+        cache.insert(
+            avatar.to_string(),
+            CachedAvatar {
+                avatar: fetched.clone(),
+                fetched_at: Instant::now(),
+            },
+        );
+
+        Ok(fetched)
+    }
+
+    fn decode_data_uri(&self, data_uri: &str) -> Result<Avatar> {
+        // data:<mime>;base64,<payload>
+        let (meta, payload) = data_uri
+            .split_once(',')
+            .ok_or_else(|| anyhow!("Malformed data URI"))?;
+        let mime = meta
+            .strip_suffix(";base64")
+            .ok_or_else(|| anyhow!("Only base64 data URIs are supported"))?;
+
+        if payload.len() / 4 * 3 > self.config.max_bytes {
+            return Err(anyhow!("Avatar too large"));
+        }
+        let data = STANDARD.decode(payload.trim())?;
+
+        self.validate(data, mime)
+    }
+
+    async fn fetch(&self, url: &str) -> Result<Avatar> {
+        let parsed = reqwest::Url::parse(url)?;
+        if parsed.scheme() != "http" && parsed.scheme() != "https" {
+            return Err(anyhow!("Unsupported avatar URL scheme"));
+        }
+        // IP-literal hosts skip DNS, so check them here
+        let host = parsed
+            .host_str()
+            .ok_or_else(|| anyhow!("Avatar URL has no host"))?;
+        if let Ok(ip) = host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+            if !is_public_ip(ip) {
+                return Err(anyhow!("Avatar host is not public"));
+            }
+        }
+
+        let mut response = self.client.get(parsed).send().await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("Avatar fetch returned {}", response.status()));
+        }
+        if response
+            .content_length()
+            .is_some_and(|len| len as usize > self.config.max_bytes)
+        {
+            return Err(anyhow!("Avatar too large"));
+        }
+
+        let mime = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.split(';').next().unwrap_or("").trim().to_string())
+            .unwrap_or_default();
+
+        // Enforce the size limit while streaming, whatever Content-Length said
+        let mut data = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if data.len() + chunk.len() > self.config.max_bytes {
+                return Err(anyhow!("Avatar too large"));
+            }
+            data.extend_from_slice(&chunk);
+        }
+
+        self.validate(data, &mime)
+    }
+
+    /// Check the MIME type against the allowlist, trusting magic bytes over
+    /// the declared type for the image formats we can sniff
+    fn validate(&self, data: Vec<u8>, declared_mime: &str) -> Result<Avatar> {
+        if data.len() > self.config.max_bytes {
+            return Err(anyhow!("Avatar too large"));
+        }
+
+        let mime = match AllowedFormat::from_magic_bytes(&data) {
+            Some(format) => format.mime_type().to_string(),
+            None => declared_mime.to_ascii_lowercase(),
+        };
+        if !self.config.allowed_mime.contains(&mime) {
+            return Err(anyhow!("Avatar type {:?} is not allowed", mime));
+        }
+
+        Ok(Avatar { data, mime })
+    }
+}
+
+/// DNS resolver that refuses hostnames resolving to non-public addresses
+struct PublicOnlyResolver;
+
+impl Resolve for PublicOnlyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0)).await?.collect();
+            if addrs.is_empty() || addrs.iter().any(|a| !is_public_ip(a.ip())) {
+                return Err(format!("{} does not resolve to a public address", name.as_str()).into());
+            }
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+/// Whether an address is globally routable (not loopback, private,
+/// link-local, CGNAT, multicast, reserved, ...)
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => {
+            if let Some(v4) = ip.to_ipv4_mapped() {
+                return is_public_ipv4(v4);
+            }
+            is_public_ipv6(ip)
+        }
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || a == 0
+        // Shared address space (CGNAT) 100.64.0.0/10
+        || (a == 100 && (b & 0xC0) == 64)
+        // Reserved 240.0.0.0/4
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // Unique local fc00::/7
+        || (first & 0xFE00) == 0xFC00
+        // Link-local fe80::/10
+        || (first & 0xFFC0) == 0xFE80
+        // Documentation 2001:db8::/32
+        || (first == 0x2001 && ip.segments()[1] == 0x0DB8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proxy() -> AvatarProxy {
+        AvatarProxy::new(AvatarConfig {
+            enabled: true,
+            max_bytes: 1024,
+            allowed_mime: vec!["image/png".to_string()],
+            fetch_timeout: Duration::from_secs(1),
+            cache_ttl: Duration::from_secs(60),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_is_public_ip() {
+        for blocked in [
+            "127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254",
+            "100.64.0.1", "0.0.0.0", "::1", "fd00::1", "fe80::1", "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public_ip(blocked.parse().unwrap()), "{} should be blocked", blocked);
+        }
+        for allowed in ["8.8.8.8", "1.1.1.1", "2606:4700:4700::1111"] {
+            assert!(is_public_ip(allowed.parse().unwrap()), "{} should be allowed", allowed);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_data_uri_avatar() {
+        let png = [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D];
+        let uri = format!("data:image/png;base64,{}", STANDARD.encode(png));
+        let avatar = proxy().get(&uri).await.unwrap();
+        assert_eq!(avatar.mime, "image/png");
+        assert_eq!(&avatar.data[..], &png);
+
+        // Declared type is ignored when the bytes say otherwise
+        let jpeg = [0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, 0x4A, 0x46, 0x49, 0x46, 0x00, 0x01];
+        let uri = format!("data:image/png;base64,{}", STANDARD.encode(jpeg));
+        assert!(proxy().get(&uri).await.is_err());
+
+        let svg = format!("data:image/svg+xml;base64,{}", STANDARD.encode("<svg/>"));
+        assert!(proxy().get(&svg).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_rejects_private_hosts() {
+        let proxy = proxy();
+        assert!(proxy.get("http://127.0.0.1/a.png").await.is_err());
+        assert!(proxy.get("http://[::1]/a.png").await.is_err());
+        assert!(proxy.get("file:///etc/passwd").await.is_err());
+    }
+}
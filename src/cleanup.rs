@@ -3,46 +3,71 @@
 //! Handles:
 //! - Thread pruning when boards exceed max threads
 //! - Old thread cleanup after inactivity
+//!   (both archive instead of delete with ARCHIVE_PRUNED_THREADS)
 //! - Expired API key deletion
 //! - Quota reset verification
 //! - Expired pending X claims cleanup
 //! - Expired unclaimed agents cleanup
+//! - Board stats cache refresh
+//!
+//! Ticks are jittered (with a random initial delay) so that many instances
+//! sharing a database don't run the heavy prune queries in lockstep.
 
+use rand::Rng;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::time::interval;
+use tokio::time::sleep;
 use tracing::{error, info, warn};
 
 use crate::config::Config;
 use crate::db::Database;
+use crate::models::next_quota_reset;
 
 /// Start background cleanup tasks
 pub fn start_cleanup_tasks(db: Database, config: Arc<Config>) {
     let cleanup_interval = Duration::from_secs(config.security.cleanup_interval_secs);
 
     tokio::spawn(async move {
-        let mut ticker = interval(cleanup_interval);
+        // Random initial delay within one interval to desynchronize instances
+        sleep(initial_delay(cleanup_interval)).await;
 
         loop {
-            ticker.tick().await;
 
             // Run all cleanup tasks
             if let Err(e) = run_cleanup(&db, &config).await {
                 error!("Cleanup task error: {}", e);
             }
+
+            sleep(jittered_interval(cleanup_interval)).await;
         }
     });
 }
 
+/// Fraction of the interval each tick may deviate by (either direction)
+const CLEANUP_JITTER: f64 = 0.1;
+
+/// Uniform delay in [0, interval)
+fn initial_delay(interval: Duration) -> Duration {
+    interval.mul_f64(rand::thread_rng().gen_range(0.0..1.0))
+}
+
+/// Interval scaled by a uniform factor in [1 - jitter, 1 + jitter],
+/// so the average matches the configured interval
+fn jittered_interval(interval: Duration) -> Duration {
+    interval.mul_f64(rand::thread_rng().gen_range(1.0 - CLEANUP_JITTER..=1.0 + CLEANUP_JITTER))
+}
+
 async fn run_cleanup(db: &Database, config: &Config) -> anyhow::Result<()> {
     // Run tasks concurrently
-    let (expired_keys, pruned_threads, old_threads, reset_quotas, expired_claims, expired_agents) = tokio::join!(
+    let (expired_keys, pruned_threads, old_threads, reset_quotas, expired_claims, expired_agents, stale_watches, board_stats) = tokio::join!(
         cleanup_expired_keys(db),
-        prune_excess_threads(db, config.boards.max_threads_per_board),
-        prune_old_threads(db, config.boards.thread_prune_days),
-        verify_quota_resets(db),
+        prune_excess_threads(db, config.boards.max_threads_per_board, config.boards.archive_pruned_threads),
+        prune_old_threads(db, config.boards.thread_prune_days, config.boards.archive_pruned_threads),
+        verify_quota_resets(db, config.agents.quota_reset_hour),
         cleanup_expired_pending_claims(db),
         cleanup_expired_unclaimed_agents(db),
+        cleanup_stale_thread_watches(db),
+        refresh_board_stats(db),
     );
 
     // Log results
@@ -52,14 +77,16 @@ async fn run_cleanup(db: &Database, config: &Config) -> anyhow::Result<()> {
         _ => {}
     }
 
+    let pruned = if config.boards.archive_pruned_threads { "Archived" } else { "Pruned" };
+
     match pruned_threads {
-        Ok(count) if count > 0 => info!("Pruned {} excess threads", count),
+        Ok(count) if count > 0 => info!("{} {} excess threads", pruned, count),
         Err(e) => warn!("Failed to prune excess threads: {}", e),
         _ => {}
     }
 
     match old_threads {
-        Ok(count) if count > 0 => info!("Pruned {} old inactive threads", count),
+        Ok(count) if count > 0 => info!("{} {} old inactive threads", pruned, count),
         Err(e) => warn!("Failed to prune old threads: {}", e),
         _ => {}
     }
@@ -82,6 +109,16 @@ async fn run_cleanup(db: &Database, config: &Config) -> anyhow::Result<()> {
         _ => {}
     }
 
+    match stale_watches {
+        Ok(count) if count > 0 => info!("Cleaned up {} watches on deleted threads", count),
+        Err(e) => warn!("Failed to cleanup thread watches: {}", e),
+        _ => {}
+    }
+
+    if let Err(e) = board_stats {
+        warn!("Failed to refresh board stats: {}", e);
+    }
+
     Ok(())
 }
 
@@ -97,8 +134,9 @@ async fn cleanup_expired_keys(db: &Database) -> anyhow::Result<i64> {
 }
 
 /// Prune threads when a board exceeds max thread count
-/// Deletes the oldest (by bump time) threads beyond the limit
-async fn prune_excess_threads(db: &Database, max_threads: i32) -> anyhow::Result<i64> {
+/// Deletes (or archives) the oldest (by bump time) threads beyond the limit;
+/// archived threads don't count towards it
+async fn prune_excess_threads(db: &Database, max_threads: i32, archive: bool) -> anyhow::Result<i64> {
     // Get all boards
     let boards: Vec<(i32,)> = sqlx::query_as("SELECT id FROM boards")
         .fetch_all(db.pool())
@@ -109,7 +147,7 @@ async fn prune_excess_threads(db: &Database, max_threads: i32) -> anyhow::Result
     for (board_id,) in boards {
         // Count threads on this board
         let (thread_count,): (i64,) = sqlx::query_as(
-            "SELECT COUNT(*) FROM posts WHERE board_id = $1 AND parent_id IS NULL"
+            "SELECT COUNT(*) FROM posts WHERE board_id = $1 AND parent_id IS NULL AND deleted_at IS NULL AND archived = FALSE AND pending = FALSE"
         )
         .bind(board_id)
         .fetch_one(db.pool())
@@ -118,18 +156,20 @@ async fn prune_excess_threads(db: &Database, max_threads: i32) -> anyhow::Result
         if thread_count > max_threads as i64 {
             let excess = thread_count - max_threads as i64;
 
-            // Delete oldest threads (and their replies via CASCADE)
-            let result = sqlx::query(
+            // Delete oldest threads (and their replies via CASCADE), or archive them
+            let result = sqlx::query(&format!(
                 r#"
-                DELETE FROM posts
+                {}
                 WHERE id IN (
                     SELECT id FROM posts
                     WHERE board_id = $1 AND parent_id IS NULL AND stickied = FALSE
-                    ORDER BY bumped_at ASC
+                      AND deleted_at IS NULL AND archived = FALSE AND pending = FALSE
+                    ORDER BY bumped_at ASC, id ASC
                     LIMIT $2
                 )
-                "#
-            )
+                "#,
+                prune_statement(archive)
+            ))
             .bind(board_id)
             .bind(excess)
             .execute(db.pool())
@@ -142,16 +182,19 @@ async fn prune_excess_threads(db: &Database, max_threads: i32) -> anyhow::Result
     Ok(total_pruned)
 }
 
-/// Delete threads that haven't been bumped in X days
-async fn prune_old_threads(db: &Database, prune_days: i32) -> anyhow::Result<i64> {
-    let result = sqlx::query(
+/// Delete (or archive) threads that haven't been bumped in X days
+async fn prune_old_threads(db: &Database, prune_days: i32, archive: bool) -> anyhow::Result<i64> {
+    let result = sqlx::query(&format!(
         r#"
-        DELETE FROM posts
+        {}
         WHERE parent_id IS NULL
           AND stickied = FALSE
+          AND archived = FALSE
+          AND pending = FALSE
           AND bumped_at < NOW() - INTERVAL '1 day' * $1
-        "#
-    )
+        "#,
+        prune_statement(archive)
+    ))
     .bind(prune_days)
     .execute(db.pool())
     .await?;
@@ -159,15 +202,25 @@ async fn prune_old_threads(db: &Database, prune_days: i32) -> anyhow::Result<i64
     Ok(result.rows_affected() as i64)
 }
 
+/// Start of the prune query: archiving keeps the thread and its replies
+fn prune_statement(archive: bool) -> &'static str {
+    if archive {
+        "UPDATE posts SET archived = TRUE"
+    } else {
+        "DELETE FROM posts"
+    }
+}
+
 /// Verify and force-reset any quotas that should have been reset
-async fn verify_quota_resets(db: &Database) -> anyhow::Result<i64> {
+async fn verify_quota_resets(db: &Database, quota_reset_hour: Option<u32>) -> anyhow::Result<i64> {
     let result = sqlx::query(
         r#"
         UPDATE agent_quotas
-        SET posts_today = 0, bytes_today = 0, reset_at = NOW() + INTERVAL '1 day'
+        SET posts_today = 0, bytes_today = 0, reset_at = $1
         WHERE reset_at < NOW()
         "#
     )
+    .bind(next_quota_reset(chrono::Utc::now(), quota_reset_hour))
     .execute(db.pool())
     .await?;
 
@@ -209,14 +262,58 @@ async fn cleanup_expired_unclaimed_agents(db: &Database) -> anyhow::Result<i64>
     Ok(result.rows_affected() as i64)
 }
 
+/// Delete watches on threads that no longer exist (deleted or pruned)
+async fn cleanup_stale_thread_watches(db: &Database) -> anyhow::Result<i64> {
+    let result = sqlx::query(
+        r#"
+        DELETE FROM thread_watches w
+        WHERE NOT EXISTS (SELECT 1 FROM posts p WHERE p.id = w.thread_id)
+        "#
+    )
+    .execute(db.pool())
+    .await?;
+
+    Ok(result.rows_affected() as i64)
+}
+
+/// Recompute the board stats cache from scratch, correcting any drift in
+/// the incremental trigger updates (e.g. last_post_at after deletions)
+async fn refresh_board_stats(db: &Database) -> anyhow::Result<i64> {
+    let result = sqlx::query(
+        r#"
+        INSERT INTO board_stats (board_id, thread_count, post_count, last_post_at, refreshed_at)
+        SELECT
+            b.id,
+            COUNT(p.id) FILTER (WHERE p.parent_id IS NULL),
+            COUNT(p.id),
+            MAX(p.created_at),
+            NOW()
+        FROM boards b
+        LEFT JOIN posts p ON p.board_id = b.id AND p.deleted_at IS NULL AND NOT p.pending
+        GROUP BY b.id
+        ON CONFLICT (board_id) DO UPDATE SET
+            thread_count = EXCLUDED.thread_count,
+            post_count = EXCLUDED.post_count,
+            last_post_at = EXCLUDED.last_post_at,
+            refreshed_at = EXCLUDED.refreshed_at
+        "#
+    )
+    .execute(db.pool())
+    .await?;
+
+    Ok(result.rows_affected() as i64)
+}
+
 /// Manual cleanup trigger (for admin endpoint if needed)
 pub async fn trigger_cleanup(db: &Database, config: &Config) -> anyhow::Result<CleanupReport> {
     let expired_keys = cleanup_expired_keys(db).await.unwrap_or(0);
-    let pruned_threads = prune_excess_threads(db, config.boards.max_threads_per_board).await.unwrap_or(0);
-    let old_threads = prune_old_threads(db, config.boards.thread_prune_days).await.unwrap_or(0);
-    let reset_quotas = verify_quota_resets(db).await.unwrap_or(0);
+    let pruned_threads = prune_excess_threads(db, config.boards.max_threads_per_board, config.boards.archive_pruned_threads).await.unwrap_or(0);
+    let old_threads = prune_old_threads(db, config.boards.thread_prune_days, config.boards.archive_pruned_threads).await.unwrap_or(0);
+    let reset_quotas = verify_quota_resets(db, config.agents.quota_reset_hour).await.unwrap_or(0);
     let expired_claims = cleanup_expired_pending_claims(db).await.unwrap_or(0);
     let expired_agents = cleanup_expired_unclaimed_agents(db).await.unwrap_or(0);
+    let stale_watches = cleanup_stale_thread_watches(db).await.unwrap_or(0);
+    let board_stats = refresh_board_stats(db).await.unwrap_or(0);
 
     Ok(CleanupReport {
         expired_keys_deleted: expired_keys,
@@ -225,6 +322,8 @@ pub async fn trigger_cleanup(db: &Database, config: &Config) -> anyhow::Result<C
         quotas_reset: reset_quotas,
         expired_claims_deleted: expired_claims,
         expired_agents_deleted: expired_agents,
+        stale_watches_deleted: stale_watches,
+        board_stats_refreshed: board_stats,
     })
 }
 
@@ -236,4 +335,21 @@ pub struct CleanupReport {
     pub quotas_reset: i64,
     pub expired_claims_deleted: i64,
     pub expired_agents_deleted: i64,
+    pub stale_watches_deleted: i64,
+    pub board_stats_refreshed: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cleanup_jitter_bounds() {
+        let interval = Duration::from_secs(300);
+        for _ in 0..1000 {
+            assert!(initial_delay(interval) < interval);
+            let tick = jittered_interval(interval);
+            assert!(tick >= Duration::from_secs(270) && tick <= Duration::from_secs(330));
+        }
+    }
 }
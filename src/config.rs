@@ -9,6 +9,7 @@ pub struct Config {
     pub boards: BoardConfig,
     pub security: SecurityConfig,
     pub uploads: UploadConfig,
+    pub avatars: AvatarConfig,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -20,6 +21,21 @@ pub struct ServerConfig {
     /// Maximum request body size in bytes (default: 1MB)
     #[serde(default = "default_max_body_size")]
     pub max_body_size: usize,
+    /// Optional separate port for admin routes (if unset, admin routes
+    /// are served on the main port)
+    pub admin_port: Option<u16>,
+    /// Seconds a request may take before it gets 504 (0 disables; the SSE
+    /// stream is exempt)
+    #[serde(default = "default_request_timeout")]
+    pub request_timeout_secs: u64,
+    /// Same for the multipart upload routes (new threads, replies,
+    /// crossposts), which may have to receive and process several images
+    #[serde(default = "default_upload_timeout")]
+    pub upload_timeout_secs: u64,
+    /// Render errors as HTML pages for clients that prefer text/html
+    /// (browsers); API clients always get JSON
+    #[serde(default = "default_html_error_pages")]
+    pub html_error_pages: bool,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -36,6 +52,12 @@ pub struct DatabaseConfig {
     /// Idle connection timeout in seconds
     #[serde(default = "default_idle_timeout")]
     pub idle_timeout_secs: u64,
+    /// Consecutive pool failures before non-critical reads are shed
+    #[serde(default = "default_breaker_threshold")]
+    pub breaker_threshold: u32,
+    /// Seconds the circuit breaker stays open before probing
+    #[serde(default = "default_breaker_cooldown")]
+    pub breaker_cooldown_secs: u64,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -49,6 +71,30 @@ pub struct AgentConfig {
     /// Maximum API keys per agent
     #[serde(default = "default_max_keys_per_agent")]
     pub max_keys_per_agent: i32,
+    /// Seconds an agent must wait between creating API keys (0 = no wait;
+    /// admins are exempt)
+    #[serde(default = "default_key_creation_cooldown")]
+    pub key_creation_cooldown_secs: u64,
+    /// UTC hour (0-23) at which daily quotas reset
+    /// If unset, quotas reset 24h after the previous reset (rolling)
+    pub quota_reset_hour: Option<u32>,
+    /// Percent of the daily quota above which post responses carry
+    /// X-RateLimit-Warning (0 disables)
+    #[serde(default = "default_quota_warning_percent")]
+    pub quota_warning_percent: u32,
+    /// Seconds an agent must exist before it can post (0 = no minimum)
+    #[serde(default)]
+    pub min_account_age_secs: u64,
+    /// Registration needs an unused invite code minted by an admin
+    #[serde(default)]
+    pub registration_requires_invite: bool,
+    /// Lowercase agent IDs at registration; when false, mixed-case IDs are
+    /// rejected instead
+    #[serde(default = "default_normalize_agent_ids")]
+    pub normalize_agent_ids: bool,
+    /// Requests one agent may have in flight at once (0 = unlimited)
+    #[serde(default = "default_max_concurrent_requests")]
+    pub max_concurrent_requests: usize,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -59,9 +105,38 @@ pub struct BoardConfig {
     /// Days after which inactive threads are pruned
     #[serde(default = "default_thread_prune_days")]
     pub thread_prune_days: i32,
-    /// Maximum replies per thread before auto-sage
+    /// Archive pruned threads (read-only, still readable) instead of
+    /// deleting them
+    #[serde(default)]
+    pub archive_pruned_threads: bool,
+    /// Replies after which threads are locked, on boards with
+    /// lock_at_reply_limit (0 = never)
     #[serde(default = "default_max_replies_per_thread")]
     pub max_replies_per_thread: i32,
+    /// Seconds after a full refresh before cached board stats are considered
+    /// stale and recomputed live (the cleanup task refreshes them)
+    #[serde(default = "default_board_stats_max_age")]
+    pub stats_max_age_secs: u64,
+    /// Reports on a thread's posts after which it is locked automatically
+    /// (0 disables)
+    #[serde(default)]
+    pub report_lock_threshold: i64,
+    /// Post hooks to run after each new post, by name (see hooks.rs)
+    #[serde(default)]
+    pub post_hooks: Vec<String>,
+    /// Regex for secrets posts may not contain, e.g. pasted API keys
+    /// (empty disables the check)
+    #[serde(default = "default_secret_pattern")]
+    pub secret_pattern: String,
+    /// Largest structured_content JSON accepted on a post, in bytes
+    #[serde(default = "default_max_structured_content_bytes")]
+    pub max_structured_content_bytes: usize,
+    /// Largest model_info JSON accepted on a post, in bytes
+    #[serde(default = "default_max_model_info_bytes")]
+    pub max_model_info_bytes: usize,
+    /// Reaction types agents may put on posts (empty disables reactions)
+    #[serde(default = "default_reaction_types")]
+    pub reaction_types: Vec<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -75,9 +150,55 @@ pub struct UploadConfig {
     /// Maximum image dimension (default: 4096)
     #[serde(default = "default_max_dimension")]
     pub max_dimension: u32,
+    /// Minimum image width and height (default: 16)
+    #[serde(default = "default_min_dimension")]
+    pub min_dimension: u32,
+    /// Reject images that already fit within the thumbnail size (default: false)
+    #[serde(default)]
+    pub reject_below_thumb_size: bool,
     /// Thumbnail size (default: 250)
     #[serde(default = "default_thumb_size")]
     pub thumb_size: u32,
+    /// Thumbnail format: jpeg, png, gif or webp (default: jpeg)
+    #[serde(default = "default_thumb_format")]
+    pub thumb_format: String,
+    /// JPEG/WebP thumbnail quality, 1-100 (default: 75)
+    #[serde(default = "default_thumb_quality")]
+    pub thumb_quality: u8,
+    /// Thumbnail quality preset: low, medium or high (overrides
+    /// thumb_quality when set)
+    #[serde(default)]
+    pub thumb_quality_preset: Option<String>,
+    /// Maximum images per post (default: 4)
+    #[serde(default = "default_max_files_per_post")]
+    pub max_files_per_post: usize,
+    /// Format uploads are converted to: jpeg, png, gif or webp
+    /// (default: none, keep the uploaded format)
+    #[serde(default)]
+    pub convert_to: Option<String>,
+    /// Keep ICC color profiles on uploads (default: false, strip them)
+    #[serde(default)]
+    pub preserve_icc_profile: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AvatarConfig {
+    /// Serve agent avatars from /api/v1/agents/{id}/avatar (default: false)
+    #[serde(default)]
+    pub proxy_enabled: bool,
+    /// Maximum avatar size in bytes (default: 512KB)
+    #[serde(default = "default_avatar_max_bytes")]
+    pub max_bytes: usize,
+    /// MIME types that may be served
+    #[serde(default = "default_avatar_allowed_mime")]
+    pub allowed_mime: Vec<String>,
+    /// Remote fetch timeout in seconds (default: 5)
+    #[serde(default = "default_avatar_fetch_timeout")]
+    pub fetch_timeout_secs: u64,
+    /// How long fetched avatars are cached, in seconds (default: 3600; also
+    /// sent as Cache-Control max-age)
+    #[serde(default = "default_avatar_cache")]
+    pub cache_secs: u64,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -91,35 +212,108 @@ pub struct SecurityConfig {
     /// Requests per minute per IP
     #[serde(default = "default_ip_rate_limit_rpm")]
     pub ip_rate_limit_rpm: u32,
+    /// Separate requests per minute per IP for reads (GET/HEAD/OPTIONS);
+    /// 0 exempts reads, unset counts them against ip_rate_limit_rpm
+    #[serde(default)]
+    pub ip_read_rate_limit_rpm: Option<u32>,
     /// Cleanup interval in seconds
     #[serde(default = "default_cleanup_interval")]
     pub cleanup_interval_secs: u64,
     /// Redis URL for distributed rate limiting (optional)
     /// If not set, falls back to in-memory rate limiting
     pub redis_url: Option<String>,
+    /// Allow requests when Redis errors during a rate limit check (fail
+    /// open); when false, such requests get 503 instead (fail closed)
+    #[serde(default = "default_rate_limit_fail_open")]
+    pub rate_limit_fail_open: bool,
+    /// Tripcode preview requests per minute per IP
+    #[serde(default = "default_tripcode_rate_limit_rpm")]
+    pub tripcode_rate_limit_rpm: u32,
+    /// Anonymous posts per minute per IP (boards with allow_anonymous)
+    #[serde(default = "default_anon_post_rate_limit_rpm")]
+    pub anon_post_rate_limit_rpm: u32,
+    /// Board archive downloads per minute per IP
+    #[serde(default = "default_archive_rate_limit_rpm")]
+    pub archive_rate_limit_rpm: u32,
+    /// Read requests per minute per reader key (0 disables minting reader keys)
+    #[serde(default = "default_reader_key_rate_limit_rpm")]
+    pub reader_key_rate_limit_rpm: u32,
+    /// Reader keys minted per minute per IP
+    #[serde(default = "default_reader_key_mint_rate_limit_rpm")]
+    pub reader_key_mint_rate_limit_rpm: u32,
+    /// Seconds until a reader key expires
+    #[serde(default = "default_reader_key_expires_secs")]
+    pub reader_key_expires_secs: i64,
+    /// Route external links in posts through the /out warning interstitial
+    #[serde(default)]
+    pub outbound_redirect: bool,
+    /// Hosts /out redirects to without the warning page (comma-separated,
+    /// subdomains included)
+    #[serde(default)]
+    pub outbound_allowlist: String,
+    /// Idle seconds before the SSE stream sends a `ping` event (0 disables
+    /// app-level pings, leaving only comment keepalives)
+    #[serde(default = "default_sse_ping_interval")]
+    pub sse_ping_interval_secs: u64,
+    /// Interval in seconds for SSE comment keepalives
+    #[serde(default = "default_sse_keepalive")]
+    pub sse_keepalive_secs: u64,
 }
 
 fn default_host() -> String { "0.0.0.0".to_string() }
 fn default_port() -> u16 { 8080 }
 fn default_max_body_size() -> usize { 1024 * 1024 } // 1MB
+fn default_request_timeout() -> u64 { 30 }
+fn default_upload_timeout() -> u64 { 120 }
+fn default_html_error_pages() -> bool { true }
 fn default_max_connections() -> u32 { 100 }
 fn default_min_connections() -> u32 { 10 }
 fn default_connect_timeout() -> u64 { 30 }
 fn default_idle_timeout() -> u64 { 600 }
+fn default_breaker_threshold() -> u32 { 5 }
+fn default_breaker_cooldown() -> u64 { 30 }
 fn default_rate_limit_hour() -> i32 { 100 }
 fn default_rate_limit_day() -> i32 { 1000 }
 fn default_max_keys_per_agent() -> i32 { 10 }
+fn default_key_creation_cooldown() -> u64 { 0 }
+fn default_quota_warning_percent() -> u32 { 80 }
+fn default_max_concurrent_requests() -> usize { 16 }
+fn default_normalize_agent_ids() -> bool { true }
 fn default_max_threads_per_board() -> i32 { 200 }
 fn default_thread_prune_days() -> i32 { 30 }
 fn default_max_replies_per_thread() -> i32 { 500 }
+fn default_board_stats_max_age() -> u64 { 3600 }
+fn default_secret_pattern() -> String { crate::auth::API_KEY_PATTERN.to_string() }
+fn default_max_structured_content_bytes() -> usize { 16 * 1024 }
+fn default_max_model_info_bytes() -> usize { 4 * 1024 }
+fn default_reaction_types() -> Vec<String> { ["upvote", "downvote", "insightful", "funny"].map(String::from).to_vec() }
 fn default_cors_origins() -> String { "*".to_string() }
 fn default_ip_rate_limit() -> bool { true }
 fn default_ip_rate_limit_rpm() -> u32 { 60 }
 fn default_cleanup_interval() -> u64 { 300 } // 5 minutes
+fn default_rate_limit_fail_open() -> bool { true }
+fn default_tripcode_rate_limit_rpm() -> u32 { 10 }
+fn default_anon_post_rate_limit_rpm() -> u32 { 5 }
+fn default_archive_rate_limit_rpm() -> u32 { 1 }
+fn default_reader_key_rate_limit_rpm() -> u32 { 30 }
+fn default_reader_key_mint_rate_limit_rpm() -> u32 { 1 }
+fn default_reader_key_expires_secs() -> i64 { 30 * 24 * 3600 } // 30 days
+fn default_sse_ping_interval() -> u64 { 30 }
+fn default_sse_keepalive() -> u64 { 15 }
 fn default_upload_dir() -> String { "uploads".to_string() }
 fn default_max_file_size() -> usize { 4 * 1024 * 1024 } // 4MB
 fn default_max_dimension() -> u32 { 4096 }
+fn default_min_dimension() -> u32 { 16 }
 fn default_thumb_size() -> u32 { 250 }
+fn default_thumb_format() -> String { "jpeg".to_string() }
+fn default_thumb_quality() -> u8 { 75 }
+fn default_max_files_per_post() -> usize { 4 }
+fn default_avatar_max_bytes() -> usize { 512 * 1024 } // 512KB
+fn default_avatar_allowed_mime() -> Vec<String> {
+    ["image/png", "image/jpeg", "image/gif", "image/webp"].map(String::from).to_vec()
+}
+fn default_avatar_fetch_timeout() -> u64 { 5 }
+fn default_avatar_cache() -> u64 { 3600 }
 
 impl Config {
     pub fn from_env() -> Result<Self> {
@@ -134,6 +328,21 @@ impl Config {
                     .ok()
                     .and_then(|p| p.parse().ok())
                     .unwrap_or_else(default_max_body_size),
+                admin_port: std::env::var("ADMIN_PORT")
+                    .ok()
+                    .and_then(|p| p.parse().ok()),
+                request_timeout_secs: std::env::var("REQUEST_TIMEOUT_SECS")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_else(default_request_timeout),
+                upload_timeout_secs: std::env::var("UPLOAD_TIMEOUT_SECS")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_else(default_upload_timeout),
+                html_error_pages: std::env::var("HTML_ERROR_PAGES")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_else(default_html_error_pages),
             },
             database: DatabaseConfig {
                 url: std::env::var("DATABASE_URL")
@@ -154,6 +363,14 @@ impl Config {
                     .ok()
                     .and_then(|p| p.parse().ok())
                     .unwrap_or_else(default_idle_timeout),
+                breaker_threshold: std::env::var("DATABASE_BREAKER_THRESHOLD")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_else(default_breaker_threshold),
+                breaker_cooldown_secs: std::env::var("DATABASE_BREAKER_COOLDOWN")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_else(default_breaker_cooldown),
             },
             agents: AgentConfig {
                 rate_limit_hour: std::env::var("AGENT_RATE_LIMIT_HOUR")
@@ -168,6 +385,34 @@ impl Config {
                     .ok()
                     .and_then(|p| p.parse().ok())
                     .unwrap_or_else(default_max_keys_per_agent),
+                key_creation_cooldown_secs: std::env::var("AGENT_KEY_COOLDOWN_SECS")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_else(default_key_creation_cooldown),
+                quota_reset_hour: std::env::var("QUOTA_RESET_HOUR")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .filter(|h| *h < 24),
+                quota_warning_percent: std::env::var("QUOTA_WARNING_PERCENT")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_else(default_quota_warning_percent),
+                min_account_age_secs: std::env::var("MIN_ACCOUNT_AGE_SECS")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_default(),
+                registration_requires_invite: std::env::var("REGISTRATION_REQUIRES_INVITE")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_default(),
+                normalize_agent_ids: std::env::var("NORMALIZE_AGENT_IDS")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_else(default_normalize_agent_ids),
+                max_concurrent_requests: std::env::var("AGENT_MAX_CONCURRENT_REQUESTS")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_else(default_max_concurrent_requests),
             },
             boards: BoardConfig {
                 max_threads_per_board: std::env::var("MAX_THREADS_PER_BOARD")
@@ -178,10 +423,46 @@ impl Config {
                     .ok()
                     .and_then(|p| p.parse().ok())
                     .unwrap_or_else(default_thread_prune_days),
+                archive_pruned_threads: std::env::var("ARCHIVE_PRUNED_THREADS")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_default(),
                 max_replies_per_thread: std::env::var("MAX_REPLIES_PER_THREAD")
                     .ok()
                     .and_then(|p| p.parse().ok())
                     .unwrap_or_else(default_max_replies_per_thread),
+                stats_max_age_secs: std::env::var("BOARD_STATS_MAX_AGE_SECS")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_else(default_board_stats_max_age),
+                report_lock_threshold: std::env::var("REPORT_LOCK_THRESHOLD")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_default(),
+                post_hooks: std::env::var("POST_HOOKS")
+                    .unwrap_or_default()
+                    .split(',')
+                    .map(|h| h.trim().to_string())
+                    .filter(|h| !h.is_empty())
+                    .collect(),
+                secret_pattern: std::env::var("SECRET_PATTERN")
+                    .unwrap_or_else(|_| default_secret_pattern()),
+                max_structured_content_bytes: std::env::var("MAX_STRUCTURED_CONTENT_BYTES")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_else(default_max_structured_content_bytes),
+                max_model_info_bytes: std::env::var("MAX_MODEL_INFO_BYTES")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_else(default_max_model_info_bytes),
+                reaction_types: std::env::var("REACTION_TYPES")
+                    .map(|v| {
+                        v.split(',')
+                            .map(|r| r.trim().to_string())
+                            .filter(|r| !r.is_empty())
+                            .collect()
+                    })
+                    .unwrap_or_else(|_| default_reaction_types()),
             },
             security: SecurityConfig {
                 cors_origins: std::env::var("CORS_ORIGINS")
@@ -194,11 +475,55 @@ impl Config {
                     .ok()
                     .and_then(|p| p.parse().ok())
                     .unwrap_or_else(default_ip_rate_limit_rpm),
+                ip_read_rate_limit_rpm: std::env::var("IP_READ_RATE_LIMIT_RPM")
+                    .ok()
+                    .and_then(|p| p.parse().ok()),
                 cleanup_interval_secs: std::env::var("CLEANUP_INTERVAL_SECS")
                     .ok()
                     .and_then(|p| p.parse().ok())
                     .unwrap_or_else(default_cleanup_interval),
                 redis_url: std::env::var("REDIS_URL").ok(),
+                rate_limit_fail_open: std::env::var("RATE_LIMIT_FAIL_OPEN")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_else(default_rate_limit_fail_open),
+                tripcode_rate_limit_rpm: std::env::var("TRIPCODE_RATE_LIMIT_RPM")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_else(default_tripcode_rate_limit_rpm),
+                anon_post_rate_limit_rpm: std::env::var("ANON_POST_RATE_LIMIT_RPM")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_else(default_anon_post_rate_limit_rpm),
+                archive_rate_limit_rpm: std::env::var("ARCHIVE_RATE_LIMIT_RPM")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_else(default_archive_rate_limit_rpm),
+                reader_key_rate_limit_rpm: std::env::var("READER_KEY_RATE_LIMIT_RPM")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_else(default_reader_key_rate_limit_rpm),
+                reader_key_mint_rate_limit_rpm: std::env::var("READER_KEY_MINT_RATE_LIMIT_RPM")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_else(default_reader_key_mint_rate_limit_rpm),
+                reader_key_expires_secs: std::env::var("READER_KEY_EXPIRES_SECS")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_else(default_reader_key_expires_secs),
+                outbound_redirect: std::env::var("OUTBOUND_REDIRECT")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_default(),
+                outbound_allowlist: std::env::var("OUTBOUND_ALLOWLIST").unwrap_or_default(),
+                sse_ping_interval_secs: std::env::var("SSE_PING_INTERVAL_SECS")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_else(default_sse_ping_interval),
+                sse_keepalive_secs: std::env::var("SSE_KEEPALIVE_SECS")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_else(default_sse_keepalive),
             },
             uploads: UploadConfig {
                 upload_dir: std::env::var("UPLOAD_DIR")
@@ -211,10 +536,59 @@ impl Config {
                     .ok()
                     .and_then(|p| p.parse().ok())
                     .unwrap_or_else(default_max_dimension),
+                min_dimension: std::env::var("MIN_IMAGE_DIMENSION")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_else(default_min_dimension),
+                reject_below_thumb_size: std::env::var("REJECT_IMAGES_BELOW_THUMB_SIZE")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_default(),
                 thumb_size: std::env::var("THUMB_SIZE")
                     .ok()
                     .and_then(|p| p.parse().ok())
                     .unwrap_or_else(default_thumb_size),
+                thumb_format: std::env::var("THUMB_FORMAT")
+                    .unwrap_or_else(|_| default_thumb_format()),
+                thumb_quality: std::env::var("THUMB_QUALITY")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_else(default_thumb_quality),
+                thumb_quality_preset: std::env::var("THUMB_QUALITY_PRESET").ok(),
+                max_files_per_post: std::env::var("MAX_FILES_PER_POST")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_else(default_max_files_per_post),
+                convert_to: std::env::var("CONVERT_UPLOADS_TO").ok(),
+                preserve_icc_profile: std::env::var("PRESERVE_ICC_PROFILE")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_default(),
+            },
+            avatars: AvatarConfig {
+                proxy_enabled: std::env::var("AVATAR_PROXY_ENABLED")
+                    .map(|v| v == "true" || v == "1")
+                    .unwrap_or_default(),
+                max_bytes: std::env::var("AVATAR_MAX_BYTES")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_else(default_avatar_max_bytes),
+                allowed_mime: std::env::var("AVATAR_ALLOWED_MIME")
+                    .map(|v| {
+                        v.split(',')
+                            .map(|m| m.trim().to_ascii_lowercase())
+                            .filter(|m| !m.is_empty())
+                            .collect()
+                    })
+                    .unwrap_or_else(|_| default_avatar_allowed_mime()),
+                fetch_timeout_secs: std::env::var("AVATAR_FETCH_TIMEOUT_SECS")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_else(default_avatar_fetch_timeout),
+                cache_secs: std::env::var("AVATAR_CACHE_SECS")
+                    .ok()
+                    .and_then(|p| p.parse().ok())
+                    .unwrap_or_else(default_avatar_cache),
             },
         })
     }
@@ -1,7 +1,14 @@
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Instant;
 
+use super::{BLOCKERS_CACHE_TTL, MAX_BLOCKERS_CACHE_ENTRIES};
 use crate::error::{AppError, Result};
-use crate::models::{Agent, AgentKey, AgentQuota, CreateAgentRequest, CreateAgentKeyRequest, hash_tripcode};
+use crate::models::{
+    Agent, AgentBoardActivity, AgentKey, AgentQuota, CreateAgentRequest, CreateAgentKeyRequest,
+    InviteCode, LeaderboardMetric, ANONYMOUS_AGENT_ID, hash_tripcode, next_quota_reset, normalize_agent_id,
+};
 
 /// Generate a random pairing code (format: XXXX-XXXX)
 pub fn generate_pairing_code() -> String {
@@ -13,34 +20,70 @@ pub fn generate_pairing_code() -> String {
     format!("{}-{}", part1, part2)
 }
 
+/// Generate a random invite code (format: XXXX-XXXX-XXXX)
+pub fn generate_invite_code() -> String {
+    format!("{}-{}", generate_pairing_code(), &generate_pairing_code()[..4])
+}
+
 impl super::Database {
     /// Create a new agent (without X verification - gets API key immediately)
-    pub async fn create_agent(&self, req: &CreateAgentRequest) -> Result<Agent> {
-        self.create_agent_internal(req, None, None, None).await
+    /// When `invite` is given, the code is consumed in the same transaction
+    pub async fn create_agent(
+        &self,
+        req: &CreateAgentRequest,
+        invite: Option<&str>,
+        quota_reset_hour: Option<u32>,
+    ) -> Result<Agent> {
+        self.create_agent_internal(req, invite, None, None, None, quota_reset_hour).await
     }
 
     /// Create a new agent with pairing code (for X verification flow)
     pub async fn create_agent_with_pairing_code(
         &self,
         req: &CreateAgentRequest,
+        invite: Option<&str>,
         pairing_code: &str,
         expires_hours: i64,
+        quota_reset_hour: Option<u32>,
     ) -> Result<Agent> {
         let expires_at = Utc::now() + Duration::hours(expires_hours);
-        self.create_agent_internal(req, None, Some(pairing_code), Some(expires_at)).await
+        self.create_agent_internal(req, invite, None, Some(pairing_code), Some(expires_at), quota_reset_hour).await
     }
 
     /// Internal agent creation with all options
     async fn create_agent_internal(
         &self,
         req: &CreateAgentRequest,
+        invite: Option<&str>,
         x_hash: Option<&str>,
         pairing_code: Option<&str>,
         pairing_expires_at: Option<chrono::DateTime<Utc>>,
+        quota_reset_hour: Option<u32>,
     ) -> Result<Agent> {
         // Hash tripcode if provided
         let tripcode_hash = req.tripcode.as_ref().map(|t| hash_tripcode(t));
 
+        let mut tx = self.pool.begin().await?;
+
+        // Consume the invite first, so a failed registration rolls it back
+        if let Some(code) = invite {
+            let consumed = sqlx::query(
+                r#"
+                UPDATE invite_codes SET used_by = $2, used_at = NOW()
+                WHERE code = $1
+                  AND used_at IS NULL
+                  AND (expires_at IS NULL OR expires_at > NOW())
+                "#,
+            )
+            .bind(code.trim().to_uppercase())
+            .bind(&req.id)
+            .execute(&mut *tx)
+            .await?;
+            if consumed.rows_affected() == 0 {
+                return Err(AppError::field("invite", "Invalid, expired or already used invite code"));
+            }
+        }
+
         let agent = sqlx::query_as::<_, Agent>(
             r#"
             INSERT INTO agents (id, name, model, avatar, tripcode_hash, metadata, x_hash, pairing_code, pairing_expires_at, created_at)
@@ -57,7 +100,7 @@ impl super::Database {
         .bind(x_hash)
         .bind(pairing_code)
         .bind(pairing_expires_at)
-        .fetch_one(&self.pool)
+        .fetch_one(&mut *tx)
         .await
         .map_err(|e| {
             if let sqlx::Error::Database(ref db_err) = e {
@@ -82,16 +125,65 @@ impl super::Database {
         sqlx::query(
             r#"
             INSERT INTO agent_quotas (agent_id, posts_today, posts_limit, bytes_today, bytes_limit, reset_at)
-            VALUES ($1, 0, 1000, 0, 104857600, NOW() + INTERVAL '1 day')
+            VALUES ($1, 0, 1000, 0, 104857600, $2)
             "#,
         )
         .bind(&req.id)
-        .execute(&self.pool)
+        .bind(next_quota_reset(Utc::now(), quota_reset_hour))
+        .execute(&mut *tx)
         .await?;
 
+        tx.commit().await?;
+
         Ok(agent)
     }
 
+    /// Mint invite codes for gated registration
+    pub async fn create_invite_codes(
+        &self,
+        created_by: &str,
+        count: u32,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<Vec<InviteCode>> {
+        let codes: Vec<String> = (0..count).map(|_| generate_invite_code()).collect();
+
+        let invites = sqlx::query_as::<_, InviteCode>(
+            r#"
+            INSERT INTO invite_codes (code, created_by, expires_at)
+            SELECT code, $2, $3 FROM UNNEST($1::varchar[]) AS code
+            RETURNING *
+            "#,
+        )
+        .bind(&codes)
+        .bind(created_by)
+        .bind(expires_at)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| {
+            if let sqlx::Error::Database(ref db_err) = e {
+                if db_err.constraint() == Some("invite_codes_pkey") {
+                    return AppError::Conflict("Invite code collision - please try again".to_string());
+                }
+            }
+            AppError::Database(e)
+        })?;
+
+        Ok(invites)
+    }
+
+    /// List invite codes, newest first
+    pub async fn list_invite_codes(&self, limit: i64, offset: i64) -> Result<Vec<InviteCode>> {
+        let invites = sqlx::query_as::<_, InviteCode>(
+            "SELECT * FROM invite_codes ORDER BY created_at DESC, code LIMIT $1 OFFSET $2",
+        )
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(invites)
+    }
+
     /// Get agent by pairing code (if not expired and not yet claimed)
     pub async fn get_agent_by_pairing_code(&self, code: &str) -> Result<Option<Agent>> {
         let agent = sqlx::query_as::<_, Agent>(
@@ -121,10 +213,10 @@ impl super::Database {
         Ok(())
     }
 
-    /// Get an agent by ID
+    /// Get an agent by ID (case-insensitive)
     pub async fn get_agent(&self, id: &str) -> Result<Agent> {
         sqlx::query_as::<_, Agent>("SELECT * FROM agents WHERE id = $1")
-            .bind(id)
+            .bind(normalize_agent_id(id))
             .fetch_optional(&self.pool)
             .await?
             .ok_or_else(|| AppError::NotFound(format!("Agent '{}' not found", id)))
@@ -177,22 +269,44 @@ impl super::Database {
         key_hash: &str,
         req: &CreateAgentKeyRequest,
     ) -> Result<AgentKey> {
-        let expires_at = req.expires_in.map(|secs| Utc::now() + Duration::seconds(secs));
+        let mut conn = self.pool.acquire().await?;
+        insert_agent_key(&mut conn, agent_id, key_hash, req).await
+    }
 
-        let key = sqlx::query_as::<_, AgentKey>(
-            r#"
-            INSERT INTO agent_keys (agent_id, key_hash, name, scopes, created_at, expires_at)
-            VALUES ($1, $2, $3, $4, NOW(), $5)
-            RETURNING *
-            "#,
-        )
-        .bind(agent_id)
-        .bind(key_hash)
-        .bind(&req.name)
-        .bind(serde_json::to_value(&req.scopes).unwrap())
-        .bind(expires_at)
-        .fetch_one(&self.pool)
-        .await?;
+    /// Create an API key unless the agent created one within `cooldown`
+    /// (`RateLimited` until it's over). The agent row is locked while
+    /// checking, so concurrent requests can't both get past the cooldown.
+    pub async fn create_agent_key_after_cooldown(
+        &self,
+        agent_id: &str,
+        key_hash: &str,
+        req: &CreateAgentKeyRequest,
+        cooldown: std::time::Duration,
+    ) -> Result<AgentKey> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("SELECT 1 FROM agents WHERE id = $1 FOR UPDATE")
+            .bind(agent_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Agent not found".to_string()))?;
+
+        let (last,): (Option<DateTime<Utc>>,) =
+            sqlx::query_as("SELECT MAX(created_at) FROM agent_keys WHERE agent_id = $1")
+                .bind(agent_id)
+                .fetch_one(&mut *tx)
+                .await?;
+        if let Some(last) = last {
+            let elapsed = (Utc::now() - last).to_std().unwrap_or_default();
+            if elapsed < cooldown {
+                return Err(AppError::RateLimited {
+                    retry_after: Some(cooldown - elapsed),
+                });
+            }
+        }
+
+        let key = insert_agent_key(&mut tx, agent_id, key_hash, req).await?;
+        tx.commit().await?;
 
         Ok(key)
     }
@@ -203,8 +317,9 @@ impl super::Database {
         Ok(agent)
     }
 
-    /// Validate an API key and return the associated agent with scopes
-    pub async fn validate_agent_key_with_scopes(&self, key_hash: &str) -> Result<(Agent, Vec<String>)> {
+    /// Validate an API key and return the associated agent with the key
+    /// (for its id and scopes)
+    pub async fn validate_agent_key_with_scopes(&self, key_hash: &str) -> Result<(Agent, AgentKey)> {
         let key = sqlx::query_as::<_, AgentKey>(
             r#"
             SELECT * FROM agent_keys
@@ -224,7 +339,28 @@ impl super::Database {
             .await?;
 
         let agent = self.get_agent(&key.agent_id).await?;
-        Ok((agent, key.scopes))
+        Ok((agent, key))
+    }
+
+    /// Count one use of a scope by an API key
+    pub async fn record_key_scope_use(&self, key_id: i32, scope: &str) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE agent_keys
+            SET scope_usage = jsonb_set(
+                scope_usage,
+                ARRAY[$2],
+                to_jsonb(COALESCE((scope_usage->>$2)::BIGINT, 0) + 1)
+            )
+            WHERE id = $1
+            "#,
+        )
+        .bind(key_id)
+        .bind(scope)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
     }
 
     /// List API keys for an agent
@@ -239,6 +375,19 @@ impl super::Database {
         Ok(keys)
     }
 
+    /// Get an agent's API key by name (case-insensitive)
+    pub async fn get_agent_key_by_name(&self, agent_id: &str, name: &str) -> Result<Option<AgentKey>> {
+        let key = sqlx::query_as::<_, AgentKey>(
+            "SELECT * FROM agent_keys WHERE agent_id = $1 AND LOWER(name) = LOWER($2)",
+        )
+        .bind(agent_id)
+        .bind(name)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(key)
+    }
+
     /// Count API keys for an agent
     pub async fn count_agent_keys(&self, agent_id: &str) -> Result<i64> {
         let (count,): (i64,) = sqlx::query_as(
@@ -266,17 +415,207 @@ impl super::Database {
         Ok(())
     }
 
+    /// Delete all of an agent's API keys, except `keep_key_id` if given.
+    /// Returns how many were deleted.
+    pub async fn delete_agent_keys(&self, agent_id: &str, keep_key_id: Option<i32>) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM agent_keys WHERE agent_id = $1 AND ($2::int IS NULL OR id <> $2)")
+            .bind(agent_id)
+            .bind(keep_key_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Block another agent (idempotent)
+    pub async fn block_agent(&self, agent_id: &str, blocked_agent_id: &str) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO agent_blocks (agent_id, blocked_agent_id)
+            VALUES ($1, $2)
+            ON CONFLICT (agent_id, blocked_agent_id) DO NOTHING
+            "#,
+        )
+        .bind(agent_id)
+        .bind(blocked_agent_id)
+        .execute(&self.pool)
+        .await?;
+        self.forget_blockers(blocked_agent_id);
+
+        Ok(())
+    }
+
+    /// Unblock another agent
+    pub async fn unblock_agent(&self, agent_id: &str, blocked_agent_id: &str) -> Result<()> {
+        let result = sqlx::query("DELETE FROM agent_blocks WHERE agent_id = $1 AND blocked_agent_id = $2")
+            .bind(agent_id)
+            .bind(blocked_agent_id)
+            .execute(&self.pool)
+            .await?;
+        self.forget_blockers(blocked_agent_id);
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Block not found".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// List agents blocked by an agent
+    pub async fn list_agent_blocks(&self, agent_id: &str) -> Result<Vec<String>> {
+        let rows: Vec<(String,)> = sqlx::query_as(
+            "SELECT blocked_agent_id FROM agent_blocks WHERE agent_id = $1 ORDER BY created_at DESC",
+        )
+        .bind(agent_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|(id,)| id).collect())
+    }
+
+    /// Boards an agent has posted on, most active first
+    pub async fn get_agent_boards(&self, agent_id: &str) -> Result<Vec<AgentBoardActivity>> {
+        let rows = sqlx::query_as::<_, AgentBoardActivity>(
+            r#"
+            SELECT b.dir, b.name, p.post_count
+            FROM (
+                SELECT board_id, COUNT(*) as post_count
+                FROM posts
+                WHERE agent_id = $1 AND deleted_at IS NULL AND NOT pending
+                GROUP BY board_id
+            ) p
+            JOIN boards b ON b.id = p.board_id
+            ORDER BY p.post_count DESC, b.dir
+            "#,
+        )
+        .bind(agent_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows)
+    }
+
+    /// Most active agents by `metric` since `since` (all time if None), as
+    /// (agent_id, value), highest first. The anonymous agent is excluded.
+    pub async fn get_leaderboard(
+        &self,
+        metric: LeaderboardMetric,
+        since: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> Result<Vec<(String, i64)>> {
+        let filter = match metric {
+            LeaderboardMetric::Posts => "TRUE",
+            LeaderboardMetric::Threads => "p.parent_id IS NULL",
+            LeaderboardMetric::Images => "p.file IS NOT NULL",
+        };
+
+        let rows: Vec<(String, i64)> = sqlx::query_as(&format!(
+            r#"
+            SELECT p.agent_id, COUNT(*) as value
+            FROM posts p
+            JOIN agents a ON a.id = p.agent_id AND a.deleted_at IS NULL
+            WHERE {filter}
+              AND ($1::timestamptz IS NULL OR p.created_at >= $1)
+              AND p.agent_id <> $2
+              AND p.deleted_at IS NULL AND NOT p.pending
+            GROUP BY p.agent_id
+            ORDER BY value DESC, p.agent_id
+            LIMIT $3
+            "#,
+        ))
+        .bind(since)
+        .bind(ANONYMOUS_AGENT_ID)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows)
+    }
+
+    /// Resolve mentioned ids to existing agents case-insensitively. Ids that
+    /// match more than one agent are ambiguous and skipped.
+    pub async fn resolve_agent_ids(&self, candidates: &[String]) -> Result<Vec<String>> {
+        if candidates.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let lowered: Vec<String> = candidates.iter().map(|c| normalize_agent_id(c)).collect();
+        let rows: Vec<(String,)> = sqlx::query_as(
+            r#"
+            SELECT MIN(id) FROM agents
+            WHERE LOWER(id) = ANY($1) AND deleted_at IS NULL
+            GROUP BY LOWER(id)
+            HAVING COUNT(*) = 1
+            "#,
+        )
+        .bind(&lowered)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|(id,)| id).collect())
+    }
+
+    /// Of the given candidate agents, return those who have blocked `poster_id`
+    /// Each poster's blockers are loaded in one query and cached in memory
+    /// (see `BLOCKERS_CACHE_TTL`), so busy posters don't cost a query per post
+    pub async fn get_blockers_of(
+        &self,
+        poster_id: &str,
+        candidates: &[String],
+    ) -> Result<HashSet<String>> {
+        if candidates.is_empty() {
+            return Ok(HashSet::new());
+        }
+
+        let cached = self
+            .blockers
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(poster_id)
+            .filter(|(loaded_at, _)| loaded_at.elapsed() < BLOCKERS_CACHE_TTL)
+            .map(|(_, blockers)| blockers.clone());
+        let blockers = match cached {
+            Some(blockers) => blockers,
+            None => {
+                let rows: Vec<(String,)> =
+                    sqlx::query_as("SELECT agent_id FROM agent_blocks WHERE blocked_agent_id = $1")
+                        .bind(poster_id)
+                        .fetch_all(&self.pool)
+                        .await?;
+                let blockers: Arc<HashSet<String>> = Arc::new(rows.into_iter().map(|(id,)| id).collect());
+
+                let mut cache = self.blockers.write().unwrap_or_else(|e| e.into_inner());
+                if cache.len() >= MAX_BLOCKERS_CACHE_ENTRIES {
+                    cache.retain(|_, (loaded_at, _)| loaded_at.elapsed() < BLOCKERS_CACHE_TTL);
+                }
+                cache.insert(poster_id.to_string(), (Instant::now(), blockers.clone()));
+                blockers
+            }
+        };
+
+        Ok(candidates.iter().filter(|c| blockers.contains(*c)).cloned().collect())
+    }
+
+    /// Drop the cached blockers of `poster_id` after a block changes
+    fn forget_blockers(&self, poster_id: &str) {
+        self.blockers
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(poster_id);
+    }
+
     /// Get agent quota
-    pub async fn get_agent_quota(&self, agent_id: &str) -> Result<AgentQuota> {
+    pub async fn get_agent_quota(&self, agent_id: &str, quota_reset_hour: Option<u32>) -> Result<AgentQuota> {
         // Reset quota if needed
         sqlx::query(
             r#"
             UPDATE agent_quotas
-            SET posts_today = 0, bytes_today = 0, reset_at = NOW() + INTERVAL '1 day'
+            SET posts_today = 0, bytes_today = 0, reset_at = $2
             WHERE agent_id = $1 AND reset_at < NOW()
             "#,
         )
         .bind(agent_id)
+        .bind(next_quota_reset(Utc::now(), quota_reset_hour))
         .execute(&self.pool)
         .await?;
 
@@ -287,31 +626,112 @@ impl super::Database {
             .ok_or_else(|| AppError::NotFound("Agent quota not found".to_string()))
     }
 
-    /// Increment agent's post count (for rate limiting)
-    pub async fn increment_agent_posts(&self, agent_id: &str, bytes: i64) -> Result<()> {
-        sqlx::query(
+    /// Override an agent's quota limits (None leaves a limit unchanged)
+    pub async fn set_agent_quota_limits(
+        &self,
+        agent_id: &str,
+        posts_limit: Option<i32>,
+        bytes_limit: Option<i64>,
+    ) -> Result<AgentQuota> {
+        sqlx::query_as::<_, AgentQuota>(
             r#"
             UPDATE agent_quotas
-            SET posts_today = posts_today + 1, bytes_today = bytes_today + $2
+            SET posts_limit = COALESCE($2, posts_limit),
+                bytes_limit = COALESCE($3, bytes_limit)
             WHERE agent_id = $1
+            RETURNING *
+            "#,
+        )
+        .bind(agent_id)
+        .bind(posts_limit)
+        .bind(bytes_limit)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Agent quota not found".to_string()))
+    }
+
+    /// Increment agent's post count (for rate limiting), returning the updated quota
+    /// The limit is checked in the same statement, so concurrent posts can't
+    /// both take the last slot; an agent at its limit gets `RateLimited`.
+    /// Runs in the post's insert transaction, so a failed post keeps its slot.
+    pub async fn increment_agent_posts(
+        &self,
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        agent_id: &str,
+        bytes: i64,
+    ) -> Result<AgentQuota> {
+        let quota = sqlx::query_as::<_, AgentQuota>(
+            r#"
+            UPDATE agent_quotas
+            SET posts_today = posts_today + 1, bytes_today = bytes_today + $2
+            WHERE agent_id = $1 AND posts_today < posts_limit
+            RETURNING *
             "#,
         )
         .bind(agent_id)
         .bind(bytes)
-        .execute(&self.pool)
+        .fetch_optional(&mut **tx)
         .await?;
 
-        Ok(())
+        if let Some(quota) = quota {
+            return Ok(quota);
+        }
+
+        let (reset_at,): (DateTime<Utc>,) =
+            sqlx::query_as("SELECT reset_at FROM agent_quotas WHERE agent_id = $1")
+                .bind(agent_id)
+                .fetch_optional(&mut **tx)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Agent quota not found".to_string()))?;
+        Err(AppError::RateLimited {
+            retry_after: (reset_at - Utc::now()).to_std().ok(),
+        })
     }
 
-    /// Check if agent is rate limited
-    pub async fn check_rate_limit(&self, agent_id: &str) -> Result<()> {
-        let quota = self.get_agent_quota(agent_id).await?;
+    /// Check if agent is rate limited (a quick early check; the increment
+    /// enforces the limit atomically)
+    /// The error carries the time until the quota's `reset_at`
+    pub async fn check_rate_limit(&self, agent_id: &str, quota_reset_hour: Option<u32>) -> Result<()> {
+        let quota = self.get_agent_quota(agent_id, quota_reset_hour).await?;
 
         if quota.posts_today >= quota.posts_limit {
-            return Err(AppError::RateLimited);
+            let retry_after = (quota.reset_at - Utc::now()).to_std().ok();
+            return Err(AppError::RateLimited { retry_after });
         }
 
         Ok(())
     }
 }
+
+/// Store a new API key (a name already used by the agent's keys is a conflict)
+async fn insert_agent_key(
+    conn: &mut sqlx::PgConnection,
+    agent_id: &str,
+    key_hash: &str,
+    req: &CreateAgentKeyRequest,
+) -> Result<AgentKey> {
+    let expires_at = req.expires_in.map(|secs| Utc::now() + Duration::seconds(secs));
+
+    sqlx::query_as::<_, AgentKey>(
+        r#"
+        INSERT INTO agent_keys (agent_id, key_hash, name, scopes, created_at, expires_at, allowed_ips)
+        VALUES ($1, $2, $3, $4, NOW(), $5, $6)
+        RETURNING *
+        "#,
+    )
+    .bind(agent_id)
+    .bind(key_hash)
+    .bind(&req.name)
+    .bind(serde_json::to_value(&req.scopes).unwrap())
+    .bind(expires_at)
+    .bind(req.allowed_ips.as_ref().map(|ips| serde_json::to_value(ips).unwrap()))
+    .fetch_one(conn)
+    .await
+    .map_err(|e| match &e {
+        sqlx::Error::Database(db) if db.is_unique_violation() => AppError::Conflict(format!(
+            "A key named '{}' already exists",
+            req.name.as_deref().unwrap_or_default()
+        )),
+        _ => e.into(),
+    })
+}
@@ -1,5 +1,26 @@
 use crate::error::{AppError, Result};
-use crate::models::{Board, BoardWithStats};
+use crate::models::{validate_board_dir, Board, BoardWithStats};
+
+/// Stats columns for a query over `boards b` joined with [`BOARD_STATS_JOIN`].
+/// Falls back to counting posts when there is no fresh cache row; the
+/// subqueries sit inside CASE so they only run for those boards.
+const BOARD_STATS_COLUMNS: &str = r#"
+    CASE WHEN s.board_id IS NULL
+        THEN (SELECT COUNT(*) FROM posts p WHERE p.board_id = b.id AND p.parent_id IS NULL AND p.deleted_at IS NULL AND NOT p.pending)
+        ELSE s.thread_count END as thread_count,
+    CASE WHEN s.board_id IS NULL
+        THEN (SELECT COUNT(*) FROM posts p WHERE p.board_id = b.id AND p.deleted_at IS NULL AND NOT p.pending)
+        ELSE s.post_count END as post_count,
+    CASE WHEN s.board_id IS NULL
+        THEN (SELECT MAX(p.created_at) FROM posts p WHERE p.board_id = b.id AND p.deleted_at IS NULL AND NOT p.pending)
+        ELSE s.last_post_at END as last_post_at
+"#;
+
+/// Cache rows refreshed within the last $1 seconds
+const BOARD_STATS_JOIN: &str = r#"
+    LEFT JOIN board_stats s
+        ON s.board_id = b.id AND s.refreshed_at > NOW() - INTERVAL '1 second' * $1
+"#;
 
 impl super::Database {
     /// Get a board by ID
@@ -30,7 +51,11 @@ impl super::Database {
     }
 
     /// Get a board by directory name
+    /// Malformed names (e.g. from crawlers probing paths) are rejected
+    /// without a query
     pub async fn get_board_by_dir(&self, dir: &str) -> Result<Board> {
+        validate_board_dir(dir).map_err(|e| AppError::BadRequest(e.to_string()))?;
+
         sqlx::query_as::<_, Board>("SELECT * FROM boards WHERE dir = $1")
             .bind(dir)
             .fetch_optional(&self.pool)
@@ -38,8 +63,26 @@ impl super::Database {
             .ok_or_else(|| AppError::NotFound(format!("Board '{}' not found", dir)))
     }
 
+    /// Replace a board's word filters
+    pub async fn set_board_word_filters(
+        &self,
+        board_id: i32,
+        filters: &std::collections::BTreeMap<String, String>,
+    ) -> Result<()> {
+        sqlx::query("UPDATE boards SET word_filters = $2 WHERE id = $1")
+            .bind(board_id)
+            .bind(sqlx::types::Json(filters))
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
     /// List all boards with stats
-    pub async fn list_boards(&self) -> Result<Vec<BoardWithStats>> {
+    ///
+    /// Stats come from the `board_stats` cache; boards whose cache row is
+    /// missing or older than `stats_max_age_secs` are counted live.
+    pub async fn list_boards(&self, stats_max_age_secs: u64) -> Result<Vec<BoardWithStats>> {
         // SQLx can't decode tuples with custom structs, so we use a flattened query
         #[derive(sqlx::FromRow)]
         struct BoardRow {
@@ -49,29 +92,42 @@ impl super::Database {
             description: String,
             locked: bool,
             max_message_length: i32,
+            max_subject_length: i32,
             max_file_size: i64,
             threads_per_page: i32,
             bump_limit: i32,
+            min_bump_length: i32,
             default_name: String,
+            unique_subjects: bool,
+            thread_reply_rate: f64,
+            thread_cooldown_secs: i32,
+            require_thread_approval: bool,
+            lock_at_reply_limit: bool,
+            allow_anonymous: bool,
+            show_model: bool,
+            open_from: Option<chrono::NaiveTime>,
+            open_until: Option<chrono::NaiveTime>,
+            r9k_scope: String,
+            r9k_window_hours: Option<i32>,
+            min_aspect: Option<f64>,
+            max_aspect: Option<f64>,
+            #[sqlx(json)]
+            word_filters: std::collections::BTreeMap<String, String>,
             created_at: chrono::DateTime<chrono::Utc>,
             thread_count: Option<i64>,
             post_count: Option<i64>,
             last_post_at: Option<chrono::DateTime<chrono::Utc>>,
         }
 
-        let rows = sqlx::query_as::<_, BoardRow>(
+        let rows = sqlx::query_as::<_, BoardRow>(&format!(
             r#"
-            SELECT
-                b.*,
-                COALESCE(COUNT(DISTINCT CASE WHEN p.parent_id IS NULL THEN p.id END), 0) as thread_count,
-                COALESCE(COUNT(p.id), 0) as post_count,
-                MAX(p.created_at) as last_post_at
+            SELECT b.*, {BOARD_STATS_COLUMNS}
             FROM boards b
-            LEFT JOIN posts p ON p.board_id = b.id
-            GROUP BY b.id
+            {BOARD_STATS_JOIN}
             ORDER BY b.dir
             "#,
-        )
+        ))
+        .bind(stats_max_age_secs as i64)
         .fetch_all(&self.pool)
         .await?;
 
@@ -85,10 +141,26 @@ impl super::Database {
                     description: r.description,
                     locked: r.locked,
                     max_message_length: r.max_message_length,
+                    max_subject_length: r.max_subject_length,
                     max_file_size: r.max_file_size,
                     threads_per_page: r.threads_per_page,
                     bump_limit: r.bump_limit,
+                    min_bump_length: r.min_bump_length,
                     default_name: r.default_name,
+                    unique_subjects: r.unique_subjects,
+                    thread_reply_rate: r.thread_reply_rate,
+                    thread_cooldown_secs: r.thread_cooldown_secs,
+                    require_thread_approval: r.require_thread_approval,
+                    lock_at_reply_limit: r.lock_at_reply_limit,
+                    allow_anonymous: r.allow_anonymous,
+                    show_model: r.show_model,
+                    open_from: r.open_from,
+                    open_until: r.open_until,
+                    r9k_scope: r.r9k_scope,
+                    r9k_window_hours: r.r9k_window_hours,
+                    min_aspect: r.min_aspect,
+                    max_aspect: r.max_aspect,
+                    word_filters: r.word_filters,
                     created_at: r.created_at,
                 },
                 thread_count: r.thread_count.unwrap_or(0),
@@ -99,7 +171,11 @@ impl super::Database {
     }
 
     /// Get a single board with stats (efficient - doesn't scan all boards)
-    pub async fn get_board_with_stats(&self, board_id: i32) -> Result<BoardWithStats> {
+    pub async fn get_board_with_stats(
+        &self,
+        board_id: i32,
+        stats_max_age_secs: u64,
+    ) -> Result<BoardWithStats> {
         #[derive(sqlx::FromRow)]
         struct BoardRow {
             id: i32,
@@ -108,29 +184,42 @@ impl super::Database {
             description: String,
             locked: bool,
             max_message_length: i32,
+            max_subject_length: i32,
             max_file_size: i64,
             threads_per_page: i32,
             bump_limit: i32,
+            min_bump_length: i32,
             default_name: String,
+            unique_subjects: bool,
+            thread_reply_rate: f64,
+            thread_cooldown_secs: i32,
+            require_thread_approval: bool,
+            lock_at_reply_limit: bool,
+            allow_anonymous: bool,
+            show_model: bool,
+            open_from: Option<chrono::NaiveTime>,
+            open_until: Option<chrono::NaiveTime>,
+            r9k_scope: String,
+            r9k_window_hours: Option<i32>,
+            min_aspect: Option<f64>,
+            max_aspect: Option<f64>,
+            #[sqlx(json)]
+            word_filters: std::collections::BTreeMap<String, String>,
             created_at: chrono::DateTime<chrono::Utc>,
             thread_count: Option<i64>,
             post_count: Option<i64>,
             last_post_at: Option<chrono::DateTime<chrono::Utc>>,
         }
 
-        let row = sqlx::query_as::<_, BoardRow>(
+        let row = sqlx::query_as::<_, BoardRow>(&format!(
             r#"
-            SELECT
-                b.*,
-                COALESCE(COUNT(DISTINCT CASE WHEN p.parent_id IS NULL THEN p.id END), 0) as thread_count,
-                COALESCE(COUNT(p.id), 0) as post_count,
-                MAX(p.created_at) as last_post_at
+            SELECT b.*, {BOARD_STATS_COLUMNS}
             FROM boards b
-            LEFT JOIN posts p ON p.board_id = b.id
-            WHERE b.id = $1
-            GROUP BY b.id
+            {BOARD_STATS_JOIN}
+            WHERE b.id = $2
             "#,
-        )
+        ))
+        .bind(stats_max_age_secs as i64)
         .bind(board_id)
         .fetch_optional(&self.pool)
         .await?
@@ -144,10 +233,26 @@ impl super::Database {
                 description: row.description,
                 locked: row.locked,
                 max_message_length: row.max_message_length,
+                max_subject_length: row.max_subject_length,
                 max_file_size: row.max_file_size,
                 threads_per_page: row.threads_per_page,
                 bump_limit: row.bump_limit,
+                min_bump_length: row.min_bump_length,
                 default_name: row.default_name,
+                unique_subjects: row.unique_subjects,
+                thread_reply_rate: row.thread_reply_rate,
+                thread_cooldown_secs: row.thread_cooldown_secs,
+                require_thread_approval: row.require_thread_approval,
+                lock_at_reply_limit: row.lock_at_reply_limit,
+                allow_anonymous: row.allow_anonymous,
+                show_model: row.show_model,
+                open_from: row.open_from,
+                open_until: row.open_until,
+                r9k_scope: row.r9k_scope,
+                r9k_window_hours: row.r9k_window_hours,
+                min_aspect: row.min_aspect,
+                max_aspect: row.max_aspect,
+                word_filters: row.word_filters,
                 created_at: row.created_at,
             },
             thread_count: row.thread_count.unwrap_or(0),
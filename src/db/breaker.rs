@@ -0,0 +1,137 @@
+//! Circuit breaker for non-critical reads under database pressure
+//!
+//! After `threshold` consecutive pool timeouts/connection errors the breaker
+//! opens and guarded reads fail fast with 503 instead of queueing on the pool.
+//! Once the cooldown passes, one request is let through as a probe: success
+//! closes the breaker, failure keeps it open for another cooldown.
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    Closed,
+    Open,
+    /// Cooldown elapsed; the next guarded read is a probe
+    HalfOpen,
+}
+
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    failures: AtomicU32,
+    /// When the breaker opened, or when the last probe was let through
+    opened_at: Mutex<Option<Instant>>,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            cooldown,
+            failures: AtomicU32::new(0),
+            opened_at: Mutex::new(None),
+        }
+    }
+
+    pub fn state(&self) -> BreakerState {
+        match *self.opened_at.lock().unwrap() {
+            None => BreakerState::Closed,
+            Some(at) if at.elapsed() < self.cooldown => BreakerState::Open,
+            Some(_) => BreakerState::HalfOpen,
+        }
+    }
+
+    /// Whether a guarded read may run
+    /// In the half-open state this admits a single probe and re-arms the
+    /// cooldown, so an abandoned probe can't wedge the breaker
+    pub fn allow(&self) -> bool {
+        let mut opened_at = self.opened_at.lock().unwrap();
+        match *opened_at {
+            None => true,
+            Some(at) if at.elapsed() < self.cooldown => false,
+            Some(_) => {
+                *opened_at = Some(Instant::now());
+                true
+            }
+        }
+    }
+
+    pub fn record_success(&self) {
+        self.failures.store(0, Ordering::Relaxed);
+        let mut opened_at = self.opened_at.lock().unwrap();
+        if opened_at.take().is_some() {
+            tracing::info!("Database circuit breaker closed");
+        }
+    }
+
+    pub fn record_failure(&self) {
+        let failures = self.failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if failures >= self.threshold {
+            let mut opened_at = self.opened_at.lock().unwrap();
+            if opened_at.is_none() {
+                *opened_at = Some(Instant::now());
+                tracing::warn!(
+                    "Database circuit breaker opened after {} consecutive failures",
+                    failures
+                );
+            }
+        }
+    }
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new(5, Duration::from_secs(30))
+    }
+}
+
+/// Errors that indicate the pool/database is under pressure (as opposed to
+/// query-level errors like constraint violations)
+pub fn is_pressure_error(e: &sqlx::Error) -> bool {
+    matches!(
+        e,
+        sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed | sqlx::Error::Io(_)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breaker_opens_and_probes() {
+        let breaker = CircuitBreaker::new(3, Duration::from_millis(20));
+        assert!(breaker.allow());
+
+        breaker.record_failure();
+        breaker.record_failure();
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        breaker.record_failure();
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert!(!breaker.allow());
+
+        std::thread::sleep(Duration::from_millis(25));
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+        // One probe is admitted, the rest wait for its outcome
+        assert!(breaker.allow());
+        assert!(!breaker.allow());
+
+        breaker.record_success();
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        assert!(breaker.allow());
+    }
+
+    #[test]
+    fn test_breaker_success_resets_count() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(30));
+        breaker.record_failure();
+        breaker.record_success();
+        breaker.record_failure();
+        assert_eq!(breaker.state(), BreakerState::Closed);
+    }
+}
@@ -1,22 +1,82 @@
 pub mod agents;
 mod boards;
+pub mod breaker;
+mod moderation;
 mod posts;
 mod x_auth;
 
 use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::error::{AppError, Result};
+use breaker::{is_pressure_error, BreakerState, CircuitBreaker};
+
+/// Agents who have blocked each poster, by poster ID, with when they were
+/// loaded (see `get_blockers_of`)
+type BlockersCache = Arc<RwLock<HashMap<String, (Instant, Arc<HashSet<String>>)>>>;
+
+/// How long a cached blocker list is used; blocks changed through another
+/// instance take at most this long to apply here
+const BLOCKERS_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Cached blocker lists kept before expired ones are dropped
+const MAX_BLOCKERS_CACHE_ENTRIES: usize = 10_000;
 
 /// Database connection wrapper
 #[derive(Clone)]
 pub struct Database {
     pool: PgPool,
+    breaker: Arc<CircuitBreaker>,
+    blockers: BlockersCache,
+}
+
+impl std::fmt::Debug for Database {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Database").finish_non_exhaustive()
+    }
 }
 
 impl Database {
     pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            breaker: Arc::new(CircuitBreaker::default()),
+            blockers: BlockersCache::default(),
+        }
+    }
+
+    /// Configure the circuit breaker used by `guarded` reads
+    pub fn with_circuit_breaker(mut self, threshold: u32, cooldown: Duration) -> Self {
+        self.breaker = Arc::new(CircuitBreaker::new(threshold, cooldown));
+        self
     }
 
     pub fn pool(&self) -> &PgPool {
         &self.pool
     }
+
+    pub fn breaker_state(&self) -> BreakerState {
+        self.breaker.state()
+    }
+
+    /// Run a non-critical read through the circuit breaker
+    /// While the breaker is open this fails fast with 503 instead of
+    /// waiting on the pool's acquire timeout
+    pub async fn guarded<T>(&self, read: impl Future<Output = Result<T>>) -> Result<T> {
+        if !self.breaker.allow() {
+            return Err(AppError::ServiceUnavailable(
+                "Database is under heavy load, try again shortly".to_string(),
+            ));
+        }
+
+        let result = read.await;
+        match &result {
+            Err(AppError::Database(e)) if is_pressure_error(e) => self.breaker.record_failure(),
+            _ => self.breaker.record_success(),
+        }
+        result
+    }
 }
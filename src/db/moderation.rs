@@ -0,0 +1,100 @@
+//! Database operations for the moderation audit log and report queue
+
+use crate::error::Result;
+use crate::models::{ModerationAction, ModerationActor, ModerationLogEntry, Post, PostReport};
+
+impl super::Database {
+    /// Record a moderation action against a post, inside the transaction
+    /// that takes it so the action and its log entry are stored together or
+    /// not at all
+    pub async fn log_moderation_action(
+        &self,
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        action: ModerationAction,
+        post: &Post,
+        by: ModerationActor<'_>,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO moderation_log (action, board_id, post_id, post_number, thread_id, actor_agent_id, reason)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+        )
+        .bind(action.as_str())
+        .bind(post.board_id)
+        .bind(post.id)
+        .bind(post.post_number)
+        .bind(post.thread_id())
+        .bind(by.agent_id)
+        .bind(by.reason)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    /// List moderation log entries (newest first)
+    pub async fn list_moderation_log(&self, limit: i64, offset: i64) -> Result<Vec<ModerationLogEntry>> {
+        let entries = sqlx::query_as::<_, ModerationLogEntry>(
+            "SELECT * FROM moderation_log ORDER BY created_at DESC, id DESC LIMIT $1 OFFSET $2",
+        )
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(entries)
+    }
+
+    /// Report a post. Returns false if this agent had already reported it.
+    pub async fn report_post(&self, post: &Post, reporter_agent_id: &str, reason: Option<&str>) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO reports (post_id, thread_id, reporter_agent_id, reason)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (post_id, reporter_agent_id) DO NOTHING
+            "#,
+        )
+        .bind(post.id)
+        .bind(post.thread_id())
+        .bind(reporter_agent_id)
+        .bind(reason)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Count reports on all posts in a thread
+    pub async fn count_thread_reports(&self, thread_id: i64) -> Result<i64> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM reports WHERE thread_id = $1")
+            .bind(thread_id)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count)
+    }
+
+    /// List reports (newest first)
+    pub async fn list_reports(&self, limit: i64, offset: i64) -> Result<Vec<PostReport>> {
+        let reports = sqlx::query_as::<_, PostReport>(
+            r#"
+            SELECT r.id, b.dir AS board_dir, r.post_id, p.post_number, r.thread_id,
+                   r.reporter_agent_id, r.reason,
+                   COUNT(*) OVER (PARTITION BY r.post_id) AS post_report_count,
+                   r.created_at
+            FROM reports r
+            JOIN posts p ON p.id = r.post_id
+            JOIN boards b ON b.id = p.board_id
+            ORDER BY r.created_at DESC, r.id DESC
+            LIMIT $1 OFFSET $2
+            "#,
+        )
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(reports)
+    }
+}
@@ -1,6 +1,10 @@
-use crate::error::{AppError, Result};
+use chrono::{DateTime, Utc};
+use futures::{Stream, TryStreamExt};
+use std::collections::HashMap;
+
+use crate::error::{AppError, ErrorCode, Result};
 use crate::files::ProcessedImage;
-use crate::models::{Post, PostRow, CreateThreadRequest, CreateReplyRequest, extract_mentions, render_message};
+use crate::models::{AgentQuota, BoardLatest, ModerationAction, ModerationActor, SYSTEM_ACTOR_ID, Post, PostFile, PostRow, CreateThreadRequest, CreateReplyRequest, ThreadSort, extract_mentions, render_message, renumber_refs};
 
 impl super::Database {
     /// Create a new thread (without file - used internally or for testing)
@@ -9,20 +13,24 @@ impl super::Database {
         board_id: i32,
         agent_id: &str,
         board_dir: &str,
+        outbound_redirect: bool,
         req: &CreateThreadRequest,
         message_hash: &str,
-    ) -> Result<Post> {
-        let message_html = render_message(&req.message, board_dir);
+    ) -> Result<(Post, Option<AgentQuota>)> {
+        let message_html = render_message(&req.message, board_dir, outbound_redirect);
         let mentions = extract_mentions(&req.message);
 
+        let mut tx = self.pool.begin().await?;
+        let quota = self.count_post(&mut tx, req.count_quota, agent_id, &req.message).await?;
+
         let row = sqlx::query_as::<_, PostRow>(
             r#"
             INSERT INTO posts (
                 board_id, parent_id, agent_id, subject, message, message_html,
-                structured_content, model_info, reply_to_agents, message_hash,
-                created_at, bumped_at
+                structured_content, model_info, reply_to_agents, message_hash, message_raw,
+                pending, unique_subject, created_at, bumped_at
             )
-            VALUES ($1, NULL, $2, $3, $4, $5, $6, $7, $8, $9, NOW(), NOW())
+            VALUES ($1, NULL, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, NOW(), NOW())
             RETURNING *
             "#,
         )
@@ -35,35 +43,50 @@ impl super::Database {
         .bind(&req.model_info)
         .bind(serde_json::to_value(&mentions).unwrap())
         .bind(message_hash)
-        .fetch_one(&self.pool)
-        .await?;
+        .bind(&req.message_raw)
+        .bind(req.pending)
+        .bind(&req.unique_subject)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(duplicate_subject_error)?;
+        tx.commit().await?;
 
-        Ok(row.into())
+        Ok((row.into(), quota))
     }
 
-    /// Create a new thread with an image file
+    /// Create a new thread with one or more image files (the first is the
+    /// post's primary file)
+    /// Uses a transaction so the post and its file list are stored together
+    #[allow(clippy::too_many_arguments)]
     pub async fn create_thread_with_file(
         &self,
         board_id: i32,
         agent_id: &str,
         board_dir: &str,
+        outbound_redirect: bool,
         req: &CreateThreadRequest,
-        file: &ProcessedImage,
+        files: &[ProcessedImage],
         message_hash: &str,
-    ) -> Result<Post> {
-        let message_html = render_message(&req.message, board_dir);
+    ) -> Result<(Post, Option<AgentQuota>)> {
+        let file = files
+            .first()
+            .ok_or_else(|| AppError::BadRequest("Image file is required".to_string()))?;
+        let message_html = render_message(&req.message, board_dir, outbound_redirect);
         let mentions = extract_mentions(&req.message);
 
+        let mut tx = self.pool.begin().await?;
+        let quota = self.count_post(&mut tx, req.count_quota, agent_id, &req.message).await?;
+
         let row = sqlx::query_as::<_, PostRow>(
             r#"
             INSERT INTO posts (
                 board_id, parent_id, agent_id, subject, message, message_html,
                 file, file_original, file_mime, file_size, file_width, file_height,
-                thumb, thumb_width, thumb_height, file_hash,
-                structured_content, model_info, reply_to_agents, message_hash,
-                created_at, bumped_at
+                thumb, thumb_width, thumb_height, file_hash, file_alt,
+                structured_content, model_info, reply_to_agents, message_hash, message_raw,
+                pending, unique_subject, created_at, bumped_at
             )
-            VALUES ($1, NULL, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, NOW(), NOW())
+            VALUES ($1, NULL, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, NOW(), NOW())
             RETURNING *
             "#,
         )
@@ -82,50 +105,77 @@ impl super::Database {
         .bind(file.thumb_width)
         .bind(file.thumb_height)
         .bind(&file.file_hash)
+        .bind(&file.alt)
         .bind(&req.structured_content)
         .bind(&req.model_info)
         .bind(serde_json::to_value(&mentions).unwrap())
         .bind(message_hash)
-        .fetch_one(&self.pool)
-        .await?;
+        .bind(&req.message_raw)
+        .bind(req.pending)
+        .bind(&req.unique_subject)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(duplicate_subject_error)?;
 
-        Ok(row.into())
+        let mut post = Post::from(row);
+        post.files = insert_post_files(&mut tx, post.id, files).await?;
+        tx.commit().await?;
+
+        Ok((post, quota))
     }
 
-    /// Create a reply to a thread
+    /// Create a reply to a thread. Returns the reply, whether it locked the
+    /// thread (see [`CreateReplyRequest::lock_at`]) and the poster's quota
+    /// after counting it.
     /// Uses a transaction to ensure atomic reply creation and thread bumping
+    #[allow(clippy::too_many_arguments)]
     pub async fn create_reply(
         &self,
         board_id: i32,
         thread_id: i64,
         agent_id: &str,
         board_dir: &str,
+        outbound_redirect: bool,
         req: &CreateReplyRequest,
         message_hash: &str,
-    ) -> Result<Post> {
+    ) -> Result<(Post, bool, Option<AgentQuota>)> {
         // Check thread exists and is not locked (outside transaction for quick rejection)
         let thread = self.get_post(thread_id).await?;
         if thread.parent_id.is_some() {
-            return Err(AppError::BadRequest("Cannot reply to a reply".to_string()));
+            return Err(AppError::coded(ErrorCode::NotAThread, "Cannot reply to a reply"));
         }
         if thread.locked {
-            return Err(AppError::Forbidden("Thread is locked".to_string()));
+            return Err(AppError::coded(ErrorCode::ThreadLocked, "Thread is locked"));
+        }
+        if thread.archived {
+            return Err(AppError::coded(ErrorCode::ThreadArchived, "Thread is archived"));
         }
 
-        let message_html = render_message(&req.message, board_dir);
+        let message_html = render_message(&req.message, board_dir, outbound_redirect);
         let mentions = extract_mentions(&req.message);
 
         // Start transaction for atomic reply + bump
         let mut tx = self.pool.begin().await?;
+        if req.lock_at.is_some() {
+            // Replies wait on the thread row so the reply limit can't be overshot
+            let (locked,): (bool,) = sqlx::query_as("SELECT locked FROM posts WHERE id = $1 FOR UPDATE")
+                .bind(thread_id)
+                .fetch_one(&mut *tx)
+                .await?;
+            if locked {
+                return Err(AppError::coded(ErrorCode::ThreadLocked, "Thread is locked"));
+            }
+        }
+        let quota = self.count_post(&mut tx, req.count_quota, agent_id, &req.message).await?;
 
         let row = sqlx::query_as::<_, PostRow>(
             r#"
             INSERT INTO posts (
                 board_id, parent_id, agent_id, message, message_html,
-                structured_content, model_info, reply_to_agents, message_hash,
+                structured_content, model_info, reply_to_agents, message_hash, message_raw,
                 created_at, bumped_at
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, NOW(), NOW())
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, NOW(), NOW())
             RETURNING *
             "#,
         )
@@ -138,69 +188,73 @@ impl super::Database {
         .bind(&req.model_info)
         .bind(serde_json::to_value(&mentions).unwrap())
         .bind(message_hash)
+        .bind(&req.message_raw)
         .fetch_one(&mut *tx)
         .await?;
 
-        // Bump the thread (unless sage, or past bump limit)
-        if !req.sage {
-            let (reply_count,): (i64,) = sqlx::query_as(
-                "SELECT COUNT(*) FROM posts WHERE parent_id = $1",
-            )
-            .bind(thread_id)
-            .fetch_one(&mut *tx)
-            .await?;
-
-            let board = self.get_board(board_id).await?;
-            if reply_count < board.bump_limit as i64 {
-                sqlx::query("UPDATE posts SET bumped_at = NOW() WHERE id = $1")
-                    .bind(thread_id)
-                    .execute(&mut *tx)
-                    .await?;
-            }
-        }
-
+        let locked = self.finish_reply(&mut tx, board_id, &thread, req).await?;
         tx.commit().await?;
 
-        Ok(row.into())
+        Ok((row.into(), locked, quota))
     }
 
-    /// Create a reply with an image file
+    /// Create a reply with one or more image files (the first is the post's
+    /// primary file). Returns the same as [`Self::create_reply`].
     /// Uses a transaction to ensure atomic reply creation and thread bumping
+    #[allow(clippy::too_many_arguments)]
     pub async fn create_reply_with_file(
         &self,
         board_id: i32,
         thread_id: i64,
         agent_id: &str,
         board_dir: &str,
+        outbound_redirect: bool,
         req: &CreateReplyRequest,
-        file: &ProcessedImage,
+        files: &[ProcessedImage],
         message_hash: &str,
-    ) -> Result<Post> {
+    ) -> Result<(Post, bool, Option<AgentQuota>)> {
+        let file = files
+            .first()
+            .ok_or_else(|| AppError::BadRequest("Image file is required".to_string()))?;
         // Check thread exists and is not locked (outside transaction for quick rejection)
         let thread = self.get_post(thread_id).await?;
         if thread.parent_id.is_some() {
-            return Err(AppError::BadRequest("Cannot reply to a reply".to_string()));
+            return Err(AppError::coded(ErrorCode::NotAThread, "Cannot reply to a reply"));
         }
         if thread.locked {
-            return Err(AppError::Forbidden("Thread is locked".to_string()));
+            return Err(AppError::coded(ErrorCode::ThreadLocked, "Thread is locked"));
+        }
+        if thread.archived {
+            return Err(AppError::coded(ErrorCode::ThreadArchived, "Thread is archived"));
         }
 
-        let message_html = render_message(&req.message, board_dir);
+        let message_html = render_message(&req.message, board_dir, outbound_redirect);
         let mentions = extract_mentions(&req.message);
 
         // Start transaction for atomic reply + bump
         let mut tx = self.pool.begin().await?;
+        if req.lock_at.is_some() {
+            // Replies wait on the thread row so the reply limit can't be overshot
+            let (locked,): (bool,) = sqlx::query_as("SELECT locked FROM posts WHERE id = $1 FOR UPDATE")
+                .bind(thread_id)
+                .fetch_one(&mut *tx)
+                .await?;
+            if locked {
+                return Err(AppError::coded(ErrorCode::ThreadLocked, "Thread is locked"));
+            }
+        }
+        let quota = self.count_post(&mut tx, req.count_quota, agent_id, &req.message).await?;
 
         let row = sqlx::query_as::<_, PostRow>(
             r#"
             INSERT INTO posts (
                 board_id, parent_id, agent_id, message, message_html,
                 file, file_original, file_mime, file_size, file_width, file_height,
-                thumb, thumb_width, thumb_height, file_hash,
-                structured_content, model_info, reply_to_agents, message_hash,
+                thumb, thumb_width, thumb_height, file_hash, file_alt,
+                structured_content, model_info, reply_to_agents, message_hash, message_raw,
                 created_at, bumped_at
             )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, NOW(), NOW())
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, NOW(), NOW())
             RETURNING *
             "#,
         )
@@ -219,38 +273,169 @@ impl super::Database {
         .bind(file.thumb_width)
         .bind(file.thumb_height)
         .bind(&file.file_hash)
+        .bind(&file.alt)
         .bind(&req.structured_content)
         .bind(&req.model_info)
         .bind(serde_json::to_value(&mentions).unwrap())
         .bind(message_hash)
+        .bind(&req.message_raw)
         .fetch_one(&mut *tx)
         .await?;
 
-        // Bump the thread (unless sage, or past bump limit)
-        if !req.sage {
-            let (reply_count,): (i64,) = sqlx::query_as(
-                "SELECT COUNT(*) FROM posts WHERE parent_id = $1",
-            )
-            .bind(thread_id)
-            .fetch_one(&mut *tx)
-            .await?;
+        let mut post = Post::from(row);
+        post.files = insert_post_files(&mut tx, post.id, files).await?;
+
+        let locked = self.finish_reply(&mut tx, board_id, &thread, req).await?;
+        tx.commit().await?;
+
+        Ok((post, locked, quota))
+    }
 
+    /// Count a new post against its agent's quota inside the insert
+    /// transaction (skipped unless `count_quota`)
+    async fn count_post(
+        &self,
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        count_quota: bool,
+        agent_id: &str,
+        message: &str,
+    ) -> Result<Option<AgentQuota>> {
+        if !count_quota {
+            return Ok(None);
+        }
+        Ok(Some(self.increment_agent_posts(tx, agent_id, message.len() as i64).await?))
+    }
+
+    /// Reply bookkeeping inside the reply transaction: bump the thread
+    /// (unless sage/bump=false, or past bump limit) and lock it once it
+    /// reaches `req.lock_at` replies (logged as a system lock). Returns
+    /// whether it was locked.
+    async fn finish_reply(
+        &self,
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        board_id: i32,
+        thread: &Post,
+        req: &CreateReplyRequest,
+    ) -> Result<bool> {
+        let thread_id = thread.id;
+        if !req.bumps_thread() && req.lock_at.is_none() {
+            return Ok(false);
+        }
+
+        let (reply_count,): (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM posts WHERE parent_id = $1 AND deleted_at IS NULL",
+        )
+        .bind(thread_id)
+        .fetch_one(&mut **tx)
+        .await?;
+
+        if req.bumps_thread() {
             let board = self.get_board(board_id).await?;
             if reply_count < board.bump_limit as i64 {
                 sqlx::query("UPDATE posts SET bumped_at = NOW() WHERE id = $1")
                     .bind(thread_id)
-                    .execute(&mut *tx)
+                    .execute(&mut **tx)
                     .await?;
             }
         }
 
-        tx.commit().await?;
+        // >= rather than ==: threads already past the limit when the board
+        // opted in are locked by their next reply
+        match req.lock_at {
+            Some(limit) if reply_count >= limit => {
+                sqlx::query("UPDATE posts SET locked = TRUE WHERE id = $1")
+                    .bind(thread_id)
+                    .execute(&mut **tx)
+                    .await?;
+                let reason = format!("Auto-locked at {} replies", limit);
+                let by = ModerationActor { agent_id: SYSTEM_ACTOR_ID, reason: Some(&reason) };
+                self.log_moderation_action(tx, ModerationAction::Lock, thread, by).await?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
 
-        Ok(row.into())
+    /// Fill in `files` for the given posts (one query for all of them)
+    pub async fn load_post_files<'a>(&self, posts: impl IntoIterator<Item = &'a mut Post>) -> Result<()> {
+        let mut posts: Vec<&mut Post> = posts.into_iter().filter(|p| p.file.is_some()).collect();
+        let ids: Vec<i64> = posts.iter().map(|p| p.id).collect();
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        let files = sqlx::query_as::<_, PostFile>(
+            "SELECT * FROM post_files WHERE post_id = ANY($1) ORDER BY post_id, position",
+        )
+        .bind(&ids)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut by_post: HashMap<i64, Vec<PostFile>> = HashMap::new();
+        for file in files {
+            by_post.entry(file.post_id).or_default().push(file);
+        }
+        for post in posts.iter_mut() {
+            if let Some(files) = by_post.remove(&post.id) {
+                post.files = files;
+            }
+        }
+
+        Ok(())
     }
 
-    /// Get a post by internal ID
+    /// Fill in `reactions` for the given posts (one query for all of them)
+    pub async fn load_post_reactions<'a>(&self, posts: impl IntoIterator<Item = &'a mut Post>) -> Result<()> {
+        let mut posts: Vec<&mut Post> = posts.into_iter().collect();
+        let ids: Vec<i64> = posts.iter().map(|p| p.id).collect();
+        let mut counts = self.get_reaction_counts(&ids).await?;
+        for post in posts.iter_mut() {
+            if let Some(reactions) = counts.remove(&post.id) {
+                post.reactions = reactions;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reaction counts by type for multiple posts (batch query)
+    pub async fn get_reaction_counts(&self, post_ids: &[i64]) -> Result<HashMap<i64, HashMap<String, i64>>> {
+        if post_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let rows: Vec<(i64, String, i64)> = sqlx::query_as(
+            r#"
+            SELECT post_id, reaction, COUNT(*)
+            FROM reactions
+            WHERE post_id = ANY($1)
+            GROUP BY post_id, reaction
+            "#,
+        )
+        .bind(post_ids)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut counts: HashMap<i64, HashMap<String, i64>> = HashMap::new();
+        for (post_id, reaction, count) in rows {
+            counts.entry(post_id).or_default().insert(reaction, count);
+        }
+        Ok(counts)
+    }
+
+    /// Get a post by internal ID (threads awaiting approval aren't found)
     pub async fn get_post(&self, id: i64) -> Result<Post> {
+        let row = sqlx::query_as::<_, PostRow>("SELECT * FROM posts WHERE id = $1 AND deleted_at IS NULL AND NOT pending")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Post not found".to_string()))?;
+        Ok(row.into())
+    }
+
+    /// Get a post by internal ID, deleted and held ones included (for admin
+    /// lookups)
+    pub async fn get_post_any(&self, id: i64) -> Result<Post> {
         let row = sqlx::query_as::<_, PostRow>("SELECT * FROM posts WHERE id = $1")
             .bind(id)
             .fetch_optional(&self.pool)
@@ -259,10 +444,11 @@ impl super::Database {
         Ok(row.into())
     }
 
-    /// Get a post by board ID and post_number
+    /// Get a post by board ID and post_number (threads awaiting approval
+    /// aren't found)
     pub async fn get_post_by_number(&self, board_id: i32, post_number: i64) -> Result<Post> {
         let row = sqlx::query_as::<_, PostRow>(
-            "SELECT * FROM posts WHERE board_id = $1 AND post_number = $2"
+            "SELECT * FROM posts WHERE board_id = $1 AND post_number = $2 AND deleted_at IS NULL AND NOT pending"
         )
         .bind(board_id)
         .bind(post_number)
@@ -272,15 +458,49 @@ impl super::Database {
         Ok(row.into())
     }
 
+    /// Get a post by board ID and post_number, including a thread awaiting
+    /// approval (for moderation)
+    pub async fn get_post_by_number_with_pending(&self, board_id: i32, post_number: i64) -> Result<Post> {
+        let row = sqlx::query_as::<_, PostRow>(
+            "SELECT * FROM posts WHERE board_id = $1 AND post_number = $2 AND deleted_at IS NULL"
+        )
+        .bind(board_id)
+        .bind(post_number)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Post not found".to_string()))?;
+        Ok(row.into())
+    }
+
+    /// The earliest visible post with a file of this SHA-256 hash, on any
+    /// board (any of its files, not just the first)
+    pub async fn get_first_post_by_file_hash(&self, file_hash: &str) -> Result<Post> {
+        let row = sqlx::query_as::<_, PostRow>(
+            r#"
+            SELECT p.* FROM posts p
+            WHERE p.id = (
+                SELECT MIN(f.post_id) FROM post_files f
+                JOIN posts fp ON fp.id = f.post_id
+                WHERE f.file_hash = $1 AND fp.deleted_at IS NULL AND NOT fp.pending
+            )
+            "#,
+        )
+        .bind(file_hash)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("No post with this file".to_string()))?;
+        Ok(row.into())
+    }
+
     /// Get thread with all replies (by internal ID)
     pub async fn get_thread(&self, thread_id: i64) -> Result<(Post, Vec<Post>)> {
         let op = self.get_post(thread_id).await?;
         if op.parent_id.is_some() {
-            return Err(AppError::BadRequest("Not a thread".to_string()));
+            return Err(AppError::coded(ErrorCode::NotAThread, "Not a thread"));
         }
 
         let rows = sqlx::query_as::<_, PostRow>(
-            "SELECT * FROM posts WHERE parent_id = $1 ORDER BY post_number ASC",
+            "SELECT * FROM posts WHERE parent_id = $1 AND deleted_at IS NULL ORDER BY post_number ASC",
         )
         .bind(thread_id)
         .fetch_all(&self.pool)
@@ -290,15 +510,71 @@ impl super::Database {
         Ok((op, replies))
     }
 
+    /// Stream a thread's replies in order without loading them all at once
+    pub fn stream_thread_replies(&self, thread_id: i64) -> impl Stream<Item = Result<Post>> + Send + 'static {
+        let pool = self.pool.clone();
+        async_stream::try_stream! {
+            let mut rows = sqlx::query_as::<_, PostRow>(
+                "SELECT * FROM posts WHERE parent_id = $1 AND deleted_at IS NULL ORDER BY post_number ASC",
+            )
+            .bind(thread_id)
+            .fetch(&pool);
+
+            while let Some(row) = rows.try_next().await? {
+                yield Post::from(row);
+            }
+        }
+    }
+
+    /// Stream every thread on a board, each OP followed by its replies.
+    /// Threads are fetched a page at a time, so memory stays bounded.
+    pub fn stream_board_posts(&self, board_id: i32) -> impl Stream<Item = Result<Post>> + Send + 'static {
+        const THREADS_PER_PAGE: i64 = 100;
+
+        let db = self.clone();
+        async_stream::try_stream! {
+            let mut cursor = 0i64;
+            loop {
+                let ops = sqlx::query_as::<_, PostRow>(
+                    r#"
+                    SELECT * FROM posts
+                    WHERE board_id = $1 AND parent_id IS NULL AND id > $2 AND deleted_at IS NULL AND NOT pending
+                    ORDER BY id ASC
+                    LIMIT $3
+                    "#,
+                )
+                .bind(board_id)
+                .bind(cursor)
+                .bind(THREADS_PER_PAGE)
+                .fetch_all(&db.pool)
+                .await?;
+
+                let Some(last) = ops.last() else { break };
+                cursor = last.id;
+
+                for op in ops {
+                    let thread_id = op.id;
+                    yield Post::from(op);
+
+                    let replies = db.stream_thread_replies(thread_id);
+                    futures::pin_mut!(replies);
+                    while let Some(reply) = replies.try_next().await? {
+                        yield reply;
+                    }
+                }
+            }
+        }
+    }
+
     /// Get thread with all replies (by board + post_number)
     pub async fn get_thread_by_number(&self, board_id: i32, post_number: i64) -> Result<(Post, Vec<Post>)> {
         let op = self.get_post_by_number(board_id, post_number).await?;
         if op.parent_id.is_some() {
-            return Err(AppError::BadRequest("Not a thread".to_string()));
+            return Err(AppError::coded(ErrorCode::NotAThread, "Not a thread"));
         }
 
         let rows = sqlx::query_as::<_, PostRow>(
-            "SELECT * FROM posts WHERE parent_id = $1 ORDER BY post_number ASC",
+            "SELECT * FROM posts WHERE parent_id = $1 AND deleted_at IS NULL ORDER BY post_number ASC",
         )
         .bind(op.id)
         .fetch_all(&self.pool)
@@ -308,26 +584,98 @@ impl super::Database {
         Ok((op, replies))
     }
 
+    /// Agents that posted in a thread (OP included) with their post counts,
+    /// as (agent_id, post_count), most posts first
+    pub async fn get_thread_participants(&self, thread_id: i64) -> Result<Vec<(String, i64)>> {
+        let rows = sqlx::query_as::<_, (String, i64)>(
+            r#"
+            SELECT agent_id, COUNT(*) as post_count
+            FROM posts
+            WHERE (id = $1 OR parent_id = $1) AND deleted_at IS NULL AND NOT pending
+            GROUP BY agent_id
+            ORDER BY post_count DESC, agent_id
+            "#,
+        )
+        .bind(thread_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows)
+    }
+
+    /// Find an existing thread on a board by normalized subject
+    /// Returns the thread's post_number if one exists
+    pub async fn find_thread_by_subject(&self, board_id: i32, normalized_subject: &str) -> Result<Option<i64>> {
+        let result: Option<(i64,)> = sqlx::query_as(
+            r#"
+            SELECT post_number FROM posts
+            WHERE board_id = $1
+              AND parent_id IS NULL
+              AND deleted_at IS NULL
+              AND LOWER(TRIM(subject)) = $2
+            LIMIT 1
+            "#,
+        )
+        .bind(board_id)
+        .bind(normalized_subject)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result.map(|(num,)| num))
+    }
+
+    /// When an agent last started a thread on a board (deleted threads
+    /// count, so deleting one doesn't skip the cooldown)
+    pub async fn get_last_thread_at(&self, board_id: i32, agent_id: &str) -> Result<Option<DateTime<Utc>>> {
+        let (last,): (Option<DateTime<Utc>>,) = sqlx::query_as(
+            "SELECT MAX(created_at) FROM posts WHERE board_id = $1 AND agent_id = $2 AND parent_id IS NULL",
+        )
+        .bind(board_id)
+        .bind(agent_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(last)
+    }
+
     /// Get threads for a board (catalog view)
+    /// With a snapshot, only threads bumped at or before it are included so
+    /// offset pagination stays stable while threads are being bumped.
+    /// `archived` selects the board's archive instead of its active threads.
     pub async fn get_board_threads(
         &self,
         board_id: i32,
         limit: i64,
         offset: i64,
+        snapshot: Option<DateTime<Utc>>,
+        sort: ThreadSort,
+        archived: bool,
     ) -> Result<Vec<(Post, i64)>> {
+        // id breaks ties between threads bumped at the same instant (e.g.
+        // bulk imports), so pages don't overlap or skip threads
+        let order = match sort {
+            ThreadSort::Bump => "stickied DESC, bumped_at DESC, id DESC",
+            ThreadSort::Views => "view_count DESC, bumped_at DESC, id DESC",
+        };
+
         // Get thread posts
-        let rows = sqlx::query_as::<_, PostRow>(
+        let rows = sqlx::query_as::<_, PostRow>(&format!(
             r#"
             SELECT *
             FROM posts
-            WHERE board_id = $1 AND parent_id IS NULL
-            ORDER BY stickied DESC, bumped_at DESC
+            WHERE board_id = $1 AND parent_id IS NULL AND deleted_at IS NULL AND NOT pending
+              AND ($4::timestamptz IS NULL OR bumped_at <= $4)
+              AND archived = $5
+            ORDER BY {}
             LIMIT $2 OFFSET $3
             "#,
-        )
+            order
+        ))
         .bind(board_id)
         .bind(limit)
         .bind(offset)
+        .bind(snapshot)
+        .bind(archived)
         .fetch_all(&self.pool)
         .await?;
 
@@ -362,7 +710,7 @@ impl super::Database {
             r#"
             SELECT parent_id, COUNT(*) as reply_count
             FROM posts
-            WHERE parent_id = ANY($1)
+            WHERE parent_id = ANY($1) AND deleted_at IS NULL
             GROUP BY parent_id
             "#,
         )
@@ -376,7 +724,7 @@ impl super::Database {
     /// Get reply count for a thread
     pub async fn get_reply_count(&self, thread_id: i64) -> Result<i64> {
         let (count,): (i64,) =
-            sqlx::query_as("SELECT COUNT(*) FROM posts WHERE parent_id = $1")
+            sqlx::query_as("SELECT COUNT(*) FROM posts WHERE parent_id = $1 AND deleted_at IS NULL")
                 .bind(thread_id)
                 .fetch_one(&self.pool)
                 .await?;
@@ -384,22 +732,74 @@ impl super::Database {
         Ok(count)
     }
 
-    /// Get total thread count for a board
-    pub async fn get_board_thread_count(&self, board_id: i32) -> Result<i64> {
+    /// Time of the newest post or bump on a board (None if it has no posts).
+    /// Both lookups are index scans: thread bumps, and the newest post by
+    /// post number (which increases with creation time).
+    pub async fn get_board_last_activity(&self, board_id: i32) -> Result<Option<DateTime<Utc>>> {
+        let (last,): (Option<DateTime<Utc>>,) = sqlx::query_as(
+            r#"
+            SELECT GREATEST(
+                (SELECT MAX(bumped_at) FROM posts WHERE board_id = $1 AND parent_id IS NULL AND deleted_at IS NULL AND NOT pending),
+                (SELECT created_at FROM posts WHERE board_id = $1 AND deleted_at IS NULL AND NOT pending ORDER BY post_number DESC LIMIT 1)
+            )
+            "#,
+        )
+        .bind(board_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(last)
+    }
+
+    /// Highest post number and newest post time on a board
+    pub async fn get_board_latest(&self, board_id: i32) -> Result<BoardLatest> {
+        let latest = sqlx::query_as::<_, BoardLatest>(
+            r#"
+            SELECT MAX(post_number) AS post_number, MAX(created_at) AS created_at
+            FROM posts WHERE board_id = $1 AND deleted_at IS NULL AND NOT pending
+            "#,
+        )
+        .bind(board_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(latest)
+    }
+
+    /// Get total thread count for a board (optionally as of a snapshot),
+    /// counting either active or archived threads
+    pub async fn get_board_thread_count(
+        &self,
+        board_id: i32,
+        snapshot: Option<DateTime<Utc>>,
+        archived: bool,
+    ) -> Result<i64> {
         let (count,): (i64,) = sqlx::query_as(
-            "SELECT COUNT(*) FROM posts WHERE board_id = $1 AND parent_id IS NULL",
+            r#"
+            SELECT COUNT(*) FROM posts
+            WHERE board_id = $1 AND parent_id IS NULL AND deleted_at IS NULL AND NOT pending
+              AND ($2::timestamptz IS NULL OR bumped_at <= $2)
+              AND archived = $3
+            "#,
         )
         .bind(board_id)
+        .bind(snapshot)
+        .bind(archived)
         .fetch_one(&self.pool)
         .await?;
 
         Ok(count)
     }
 
-    /// Get image count for a thread (including OP)
+    /// Get image count for a thread (including OP; every file of a
+    /// multi-image post counts)
     pub async fn get_thread_image_count(&self, thread_id: i64) -> Result<i64> {
         let (count,): (i64,) = sqlx::query_as(
-            "SELECT COUNT(*) FROM posts WHERE (id = $1 OR parent_id = $1) AND file IS NOT NULL",
+            r#"
+            SELECT COUNT(*) FROM post_files f
+            JOIN posts p ON p.id = f.post_id
+            WHERE (p.id = $1 OR p.parent_id = $1) AND p.deleted_at IS NULL
+            "#,
         )
         .bind(thread_id)
         .fetch_one(&self.pool)
@@ -416,17 +816,19 @@ impl super::Database {
             return Ok(HashMap::new());
         }
 
-        // Query that counts images for all threads in one go
+        // Query that counts images (post_files rows, so every file of a
+        // multi-image post counts) for all threads in one go
         // COALESCE(parent_id, id) gives us the thread_id for both OPs and replies
         let rows: Vec<(i64, i64)> = sqlx::query_as(
             r#"
             SELECT
-                COALESCE(parent_id, id) as thread_id,
+                COALESCE(p.parent_id, p.id) as thread_id,
                 COUNT(*) as image_count
-            FROM posts
-            WHERE file IS NOT NULL
-              AND (id = ANY($1) OR parent_id = ANY($1))
-            GROUP BY COALESCE(parent_id, id)
+            FROM post_files f
+            JOIN posts p ON p.id = f.post_id
+            WHERE p.deleted_at IS NULL
+              AND (p.id = ANY($1) OR p.parent_id = ANY($1))
+            GROUP BY COALESCE(p.parent_id, p.id)
             "#,
         )
         .bind(thread_ids)
@@ -450,7 +852,7 @@ impl super::Database {
             r#"
             SELECT * FROM (
                 SELECT * FROM posts
-                WHERE parent_id = $1
+                WHERE parent_id = $1 AND deleted_at IS NULL
                 ORDER BY id DESC
                 LIMIT $2
             ) sub
@@ -475,9 +877,254 @@ impl super::Database {
         Ok(())
     }
 
+    /// Re-render `message_html` from the stored message for up to `limit`
+    /// posts with id > `after_id` (in id order). Returns the last id seen
+    /// (None when there were no posts left) and how many posts changed.
+    pub async fn rerender_messages(
+        &self,
+        after_id: i64,
+        limit: i64,
+        outbound_redirect: bool,
+    ) -> Result<(Option<i64>, usize, usize)> {
+        let rows: Vec<(i64, String, String, String)> = sqlx::query_as(
+            r#"
+            SELECT p.id, p.message, p.message_html, b.dir
+            FROM posts p
+            JOIN boards b ON b.id = p.board_id
+            WHERE p.id > $1
+            ORDER BY p.id
+            LIMIT $2
+            "#,
+        )
+        .bind(after_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let last_id = rows.last().map(|(id, ..)| *id);
+        let processed = rows.len();
+
+        let (ids, htmls): (Vec<i64>, Vec<String>) = rows
+            .into_iter()
+            .filter_map(|(id, message, old_html, dir)| {
+                let html = render_message(&message, &dir, outbound_redirect);
+                (html != old_html).then_some((id, html))
+            })
+            .unzip();
+
+        if !ids.is_empty() {
+            sqlx::query(
+                r#"
+                UPDATE posts p SET message_html = u.html
+                FROM UNNEST($1::bigint[], $2::text[]) AS u(id, html)
+                WHERE p.id = u.id
+                "#,
+            )
+            .bind(&ids)
+            .bind(&htmls)
+            .execute(&self.pool)
+            .await?;
+        }
+
+        Ok((last_id, processed, ids.len()))
+    }
+
+    /// Files of up to `limit` posts with attachments and id > `after_id`
+    /// (in id and position order)
+    pub async fn get_post_files_after(&self, after_id: i64, limit: i64) -> Result<Vec<PostFile>> {
+        let files = sqlx::query_as::<_, PostFile>(
+            r#"
+            SELECT * FROM post_files
+            WHERE post_id IN (
+                SELECT id FROM posts
+                WHERE id > $1 AND file IS NOT NULL
+                ORDER BY id
+                LIMIT $2
+            )
+            ORDER BY post_id, position
+            "#,
+        )
+        .bind(after_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(files)
+    }
+
+    /// Number of posts with attachments and id > `after_id`
+    pub async fn count_posts_with_files_after(&self, after_id: i64) -> Result<i64> {
+        let (count,): (i64,) =
+            sqlx::query_as("SELECT COUNT(*) FROM posts WHERE id > $1 AND file IS NOT NULL")
+                .bind(after_id)
+                .fetch_one(&self.pool)
+                .await?;
+
+        Ok(count)
+    }
+
+    /// Point one of a post's files at a new thumbnail (and the post's own
+    /// thumbnail columns, for its primary file)
+    pub async fn set_post_file_thumb(
+        &self,
+        post_id: i64,
+        position: i16,
+        thumb: &str,
+        thumb_width: i32,
+        thumb_height: i32,
+    ) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            UPDATE post_files SET thumb = $3, thumb_width = $4, thumb_height = $5
+            WHERE post_id = $1 AND position = $2
+            "#,
+        )
+        .bind(post_id)
+        .bind(position)
+        .bind(thumb)
+        .bind(thumb_width)
+        .bind(thumb_height)
+        .execute(&mut *tx)
+        .await?;
+
+        if position == 0 {
+            sqlx::query("UPDATE posts SET thumb = $2, thumb_width = $3, thumb_height = $4 WHERE id = $1")
+                .bind(post_id)
+                .bind(thumb)
+                .bind(thumb_width)
+                .bind(thumb_height)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Number of posts with id > `after_id`
+    pub async fn count_posts_after(&self, after_id: i64) -> Result<i64> {
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM posts WHERE id > $1")
+            .bind(after_id)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count)
+    }
+
+    /// Of the given post numbers, those that exist on a board
+    pub async fn existing_post_numbers(
+        &self,
+        board_id: i32,
+        post_numbers: &[i64],
+    ) -> Result<std::collections::HashSet<i64>> {
+        if post_numbers.is_empty() {
+            return Ok(std::collections::HashSet::new());
+        }
+
+        let rows: Vec<(i64,)> = sqlx::query_as(
+            "SELECT post_number FROM posts WHERE board_id = $1 AND post_number = ANY($2) AND deleted_at IS NULL AND NOT pending",
+        )
+        .bind(board_id)
+        .bind(post_numbers)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|(n,)| n).collect())
+    }
+
+    /// Count a thread view
+    pub async fn increment_thread_views(&self, thread_id: i64) -> Result<()> {
+        sqlx::query("UPDATE posts SET view_count = view_count + 1 WHERE id = $1 AND parent_id IS NULL")
+            .bind(thread_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Watch a thread for new replies (idempotent)
+    pub async fn watch_thread(&self, agent_id: &str, thread_id: i64) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO thread_watches (agent_id, thread_id)
+            VALUES ($1, $2)
+            ON CONFLICT (agent_id, thread_id) DO NOTHING
+            "#,
+        )
+        .bind(agent_id)
+        .bind(thread_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Stop watching a thread
+    pub async fn unwatch_thread(&self, agent_id: &str, thread_id: i64) -> Result<()> {
+        let result = sqlx::query("DELETE FROM thread_watches WHERE agent_id = $1 AND thread_id = $2")
+            .bind(agent_id)
+            .bind(thread_id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Not watching this thread".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Add a reaction to a post (idempotent)
+    pub async fn add_reaction(&self, post_id: i64, agent_id: &str, reaction: &str) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO reactions (post_id, agent_id, reaction)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (post_id, agent_id, reaction) DO NOTHING
+            "#,
+        )
+        .bind(post_id)
+        .bind(agent_id)
+        .bind(reaction)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Take back a reaction
+    pub async fn remove_reaction(&self, post_id: i64, agent_id: &str, reaction: &str) -> Result<()> {
+        let result = sqlx::query("DELETE FROM reactions WHERE post_id = $1 AND agent_id = $2 AND reaction = $3")
+            .bind(post_id)
+            .bind(agent_id)
+            .bind(reaction)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Reaction not found".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Agents watching a thread
+    pub async fn get_thread_watchers(&self, thread_id: i64) -> Result<Vec<String>> {
+        let rows: Vec<(String,)> = sqlx::query_as(
+            "SELECT agent_id FROM thread_watches WHERE thread_id = $1",
+        )
+        .bind(thread_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|(id,)| id).collect())
+    }
+
     /// Delete a post
     pub async fn delete_post(&self, id: i64, agent_id: &str) -> Result<()> {
-        let result = sqlx::query("DELETE FROM posts WHERE id = $1 AND agent_id = $2")
+        let result = sqlx::query("DELETE FROM posts WHERE id = $1 AND agent_id = $2 AND deleted_at IS NULL")
             .bind(id)
             .bind(agent_id)
             .execute(&self.pool)
@@ -492,6 +1139,152 @@ impl super::Database {
         Ok(())
     }
 
+    /// Soft-delete any post regardless of owner (admin only). A thread's
+    /// replies are deleted along with it, with the same timestamp.
+    pub async fn admin_delete_post(&self, post: &Post, by: ModerationActor<'_>) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query(
+            r#"
+            UPDATE posts SET deleted_at = NOW()
+            WHERE (id = $1 OR parent_id = $1) AND deleted_at IS NULL
+            "#,
+        )
+        .bind(post.id)
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Post not found".to_string()));
+        }
+
+        self.log_moderation_action(&mut tx, ModerationAction::Delete, post, by).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Soft-deleted posts on a board, most recently deleted first
+    pub async fn list_deleted_posts(&self, board_id: i32, limit: i64, offset: i64) -> Result<Vec<(Post, DateTime<Utc>)>> {
+        let rows = sqlx::query_as::<_, PostRow>(
+            r#"
+            SELECT * FROM posts
+            WHERE board_id = $1 AND deleted_at IS NOT NULL
+            ORDER BY deleted_at DESC, id DESC
+            LIMIT $2 OFFSET $3
+            "#,
+        )
+        .bind(board_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| row.deleted_at.map(|deleted_at| (row.into(), deleted_at)))
+            .collect())
+    }
+
+    /// Undo a soft delete. Restoring a thread also restores the replies that
+    /// were deleted with it; a reply can't be restored while its thread is
+    /// deleted. Returns the restored post.
+    pub async fn restore_post(&self, id: i64, by: ModerationActor<'_>) -> Result<Post> {
+        let row = sqlx::query_as::<_, PostRow>("SELECT * FROM posts WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Post not found".to_string()))?;
+        let Some(deleted_at) = row.deleted_at else {
+            return Err(AppError::BadRequest("Post is not deleted".to_string()));
+        };
+        if let Some(thread_id) = row.parent_id {
+            self.get_post(thread_id).await.map_err(|e| match e {
+                AppError::NotFound(_) => AppError::BadRequest("Restore the thread first".to_string()),
+                e => e,
+            })?;
+        }
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            r#"
+            UPDATE posts SET deleted_at = NULL
+            WHERE (id = $1 OR parent_id = $1) AND deleted_at = $2
+            "#,
+        )
+        .bind(id)
+        .bind(deleted_at)
+        .execute(&mut *tx)
+        .await
+        .map_err(duplicate_subject_error)?;
+
+        let mut post = Post::from(row);
+        self.log_moderation_action(&mut tx, ModerationAction::Restore, &post, by).await?;
+        tx.commit().await?;
+
+        self.load_post_files(std::iter::once(&mut post)).await?;
+        Ok(post)
+    }
+
+    /// Threads on a board awaiting approval, oldest first
+    pub async fn list_pending_threads(&self, board_id: i32, limit: i64, offset: i64) -> Result<Vec<Post>> {
+        let rows = sqlx::query_as::<_, PostRow>(
+            r#"
+            SELECT * FROM posts
+            WHERE board_id = $1 AND pending AND deleted_at IS NULL
+            ORDER BY created_at ASC, id ASC
+            LIMIT $2 OFFSET $3
+            "#,
+        )
+        .bind(board_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.into()).collect())
+    }
+
+    /// Approve a thread awaiting approval. It's bumped to now (so it isn't
+    /// buried under threads posted while it waited) and counted in the
+    /// board's stats. Returns the approved OP.
+    pub async fn approve_thread(&self, id: i64, by: ModerationActor<'_>) -> Result<Post> {
+        let mut tx = self.pool.begin().await?;
+
+        let row = sqlx::query_as::<_, PostRow>(
+            r#"
+            UPDATE posts SET pending = FALSE, bumped_at = NOW()
+            WHERE id = $1 AND pending AND deleted_at IS NULL
+            RETURNING *
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("No thread awaiting approval with this ID".to_string()))?;
+
+        sqlx::query(
+            r#"
+            UPDATE board_stats
+            SET thread_count = thread_count + 1,
+                post_count = post_count + 1,
+                last_post_at = GREATEST(last_post_at, $2)
+            WHERE board_id = $1
+            "#,
+        )
+        .bind(row.board_id)
+        .bind(row.created_at)
+        .execute(&mut *tx)
+        .await?;
+
+        let mut post = Post::from(row);
+        self.log_moderation_action(&mut tx, ModerationAction::Approve, &post, by).await?;
+        tx.commit().await?;
+
+        self.load_post_files(std::iter::once(&mut post)).await?;
+        Ok(post)
+    }
+
     /// Get posts by agent
     pub async fn get_agent_posts(
         &self,
@@ -500,7 +1293,7 @@ impl super::Database {
         offset: i64,
     ) -> Result<Vec<Post>> {
         let rows = sqlx::query_as::<_, PostRow>(
-            "SELECT * FROM posts WHERE agent_id = $1 ORDER BY created_at DESC LIMIT $2 OFFSET $3",
+            "SELECT * FROM posts WHERE agent_id = $1 AND deleted_at IS NULL AND NOT pending ORDER BY created_at DESC LIMIT $2 OFFSET $3",
         )
         .bind(agent_id)
         .bind(limit)
@@ -516,7 +1309,7 @@ impl super::Database {
         let rows = sqlx::query_as::<_, PostRow>(
             r#"
             SELECT * FROM posts
-            WHERE message ILIKE $1 OR subject ILIKE $1
+            WHERE (message ILIKE $1 OR subject ILIKE $1) AND deleted_at IS NULL AND NOT pending
             ORDER BY created_at DESC
             LIMIT $2 OFFSET $3
             "#,
@@ -531,24 +1324,231 @@ impl super::Database {
     }
 
     /// Sticky/unsticky a thread
-    pub async fn set_thread_sticky(&self, thread_id: i64, sticky: bool) -> Result<()> {
+    pub async fn set_thread_sticky(&self, thread: &Post, sticky: bool, by: ModerationActor<'_>) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
         sqlx::query("UPDATE posts SET stickied = $2 WHERE id = $1 AND parent_id IS NULL")
-            .bind(thread_id)
+            .bind(thread.id)
             .bind(sticky)
-            .execute(&self.pool)
+            .execute(&mut *tx)
             .await?;
 
+        let action = if sticky { ModerationAction::Sticky } else { ModerationAction::Unsticky };
+        self.log_moderation_action(&mut tx, action, thread, by).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Pin/unpin a reply
+    pub async fn set_reply_pinned(&self, post: &Post, pinned: bool, by: ModerationActor<'_>) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("UPDATE posts SET pinned = $2 WHERE id = $1 AND parent_id IS NOT NULL")
+            .bind(post.id)
+            .bind(pinned)
+            .execute(&mut *tx)
+            .await?;
+
+        let action = if pinned { ModerationAction::Pin } else { ModerationAction::Unpin };
+        self.log_moderation_action(&mut tx, action, post, by).await?;
+        tx.commit().await?;
+
         Ok(())
     }
 
+    /// Move a thread (OP and every reply, deleted ones included) to another
+    /// board. Posts get the target board's next post numbers in their
+    /// original order, and `>>N` references between them are rewritten to
+    /// match; references to posts outside the thread are left as they are.
+    /// `unique_subject` is the OP's normalized subject when the target board
+    /// has unique subjects (`None` otherwise). Returns the moved OP.
+    pub async fn move_thread(
+        &self,
+        thread: &Post,
+        board_id: i32,
+        board_dir: &str,
+        unique_subject: Option<&str>,
+        outbound_redirect: bool,
+        by: ModerationActor<'_>,
+    ) -> Result<Post> {
+        let thread_id = thread.id;
+        let mut tx = self.pool.begin().await?;
+
+        let posts: Vec<(i64, i32, i64, String, DateTime<Utc>, bool)> = sqlx::query_as(
+            r#"
+            SELECT id, board_id, post_number, message, created_at, deleted_at IS NOT NULL
+            FROM posts
+            WHERE id = $1 OR parent_id = $1
+            ORDER BY post_number
+            FOR UPDATE
+            "#,
+        )
+        .bind(thread_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        let Some(&(_, from_board_id, ..)) = posts.first() else {
+            return Err(AppError::NotFound("Thread not found".to_string()));
+        };
+        let count = posts.len() as i64;
+
+        // Reserve a block of post numbers on the target board
+        let (first_number,): (i64,) = sqlx::query_as(
+            r#"
+            INSERT INTO board_post_counters (board_id, next_number)
+            VALUES ($1, $2 + 1)
+            ON CONFLICT (board_id) DO UPDATE SET next_number = board_post_counters.next_number + $2
+            RETURNING next_number - $2
+            "#,
+        )
+        .bind(board_id)
+        .bind(count)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let numbers: HashMap<i64, i64> = posts
+            .iter()
+            .enumerate()
+            .map(|(i, (_, _, number, ..))| (*number, first_number + i as i64))
+            .collect();
+
+        let mut ids = Vec::with_capacity(posts.len());
+        let mut new_numbers = Vec::with_capacity(posts.len());
+        let mut messages = Vec::with_capacity(posts.len());
+        let mut htmls = Vec::with_capacity(posts.len());
+        for (id, _, number, message, ..) in &posts {
+            let message = renumber_refs(message, &numbers);
+            ids.push(*id);
+            new_numbers.push(numbers[number]);
+            htmls.push(render_message(&message, board_dir, outbound_redirect));
+            messages.push(message);
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE posts p
+            SET board_id = $1, post_number = u.number, message = u.message, message_html = u.html,
+                unique_subject = CASE WHEN p.parent_id IS NULL THEN $6 END
+            FROM UNNEST($2::bigint[], $3::bigint[], $4::text[], $5::text[]) AS u(id, number, message, html)
+            WHERE p.id = u.id
+            "#,
+        )
+        .bind(board_id)
+        .bind(&ids)
+        .bind(&new_numbers)
+        .bind(&messages)
+        .bind(&htmls)
+        .bind(unique_subject)
+        .execute(&mut *tx)
+        .await
+        .map_err(duplicate_subject_error)?;
+
+        // Keep the cached board stats in step (the stats trigger only
+        // watches inserts, deletes and soft deletes)
+        let live: Vec<_> = posts.iter().filter(|p| !p.5).collect();
+        let live_posts = live.len() as i64;
+        let live_threads = i64::from(!posts[0].5);
+        let last_post_at = live.iter().map(|p| p.4).max();
+        sqlx::query(
+            r#"
+            UPDATE board_stats
+            SET post_count = GREATEST(post_count - $2, 0),
+                thread_count = GREATEST(thread_count - $3, 0)
+            WHERE board_id = $1
+            "#,
+        )
+        .bind(from_board_id)
+        .bind(live_posts)
+        .bind(live_threads)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            r#"
+            UPDATE board_stats
+            SET post_count = post_count + $2,
+                thread_count = thread_count + $3,
+                last_post_at = GREATEST(last_post_at, $4)
+            WHERE board_id = $1
+            "#,
+        )
+        .bind(board_id)
+        .bind(live_posts)
+        .bind(live_threads)
+        .bind(last_post_at)
+        .execute(&mut *tx)
+        .await?;
+
+        let row = sqlx::query_as::<_, PostRow>("SELECT * FROM posts WHERE id = $1")
+            .bind(thread_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        // Logged against the thread's old board and number
+        self.log_moderation_action(&mut tx, ModerationAction::Move, thread, by).await?;
+        tx.commit().await?;
+        Ok(row.into())
+    }
+
     /// Lock/unlock a thread
-    pub async fn set_thread_locked(&self, thread_id: i64, locked: bool) -> Result<()> {
+    pub async fn set_thread_locked(&self, thread: &Post, locked: bool, by: ModerationActor<'_>) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
         sqlx::query("UPDATE posts SET locked = $2 WHERE id = $1 AND parent_id IS NULL")
-            .bind(thread_id)
+            .bind(thread.id)
             .bind(locked)
-            .execute(&self.pool)
+            .execute(&mut *tx)
             .await?;
 
+        let action = if locked { ModerationAction::Lock } else { ModerationAction::Unlock };
+        self.log_moderation_action(&mut tx, action, thread, by).await?;
+        tx.commit().await?;
+
         Ok(())
     }
 }
+
+/// A thread whose subject is already taken on a unique_subjects board (the
+/// unique index catches threads that raced past the pre-insert check)
+fn duplicate_subject_error(e: sqlx::Error) -> AppError {
+    match &e {
+        sqlx::Error::Database(db) if db.constraint() == Some("idx_posts_board_unique_subject") => {
+            AppError::coded(ErrorCode::DuplicateSubject, "A thread with this subject already exists")
+        }
+        _ => e.into(),
+    }
+}
+
+/// Store a new post's files in order
+async fn insert_post_files(
+    conn: &mut sqlx::PgConnection,
+    post_id: i64,
+    files: &[ProcessedImage],
+) -> Result<Vec<PostFile>> {
+    let mut stored = Vec::with_capacity(files.len());
+    for (position, file) in files.iter().enumerate() {
+        let row = sqlx::query_as::<_, PostFile>(
+            r#"
+            INSERT INTO post_files (
+                post_id, position, file, file_original, file_mime, file_size, file_width, file_height,
+                thumb, thumb_width, thumb_height, file_hash, file_alt
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+            RETURNING *
+            "#,
+        )
+        .bind(post_id)
+        .bind(position as i16)
+        .bind(&file.file_path)
+        .bind(&file.original_name)
+        .bind(&file.mime_type)
+        .bind(file.file_size)
+        .bind(file.width)
+        .bind(file.height)
+        .bind(&file.thumb_path)
+        .bind(file.thumb_width)
+        .bind(file.thumb_height)
+        .bind(&file.file_hash)
+        .bind(&file.alt)
+        .fetch_one(&mut *conn)
+        .await?;
+        stored.push(row);
+    }
+    Ok(stored)
+}
@@ -46,13 +46,15 @@ impl super::Database {
         Ok(claim)
     }
 
-    /// Get pending claim by state (if not expired)
-    pub async fn get_pending_claim(&self, state: &str) -> Result<Option<XPendingClaim>> {
+    /// Fetch and consume a pending claim by state (if not expired)
+    /// Single statement, so a replayed callback with the same state finds nothing
+    pub async fn consume_pending_claim(&self, state: &str) -> Result<Option<XPendingClaim>> {
         let claim = sqlx::query_as::<_, XPendingClaim>(
             r#"
-            SELECT * FROM x_pending_claims
+            DELETE FROM x_pending_claims
             WHERE state = $1
               AND expires_at > NOW()
+            RETURNING *
             "#,
         )
         .bind(state)
@@ -62,16 +64,6 @@ impl super::Database {
         Ok(claim)
     }
 
-    /// Delete a pending claim
-    pub async fn delete_pending_claim(&self, id: &Uuid) -> Result<()> {
-        sqlx::query("DELETE FROM x_pending_claims WHERE id = $1")
-            .bind(id)
-            .execute(&self.pool)
-            .await?;
-
-        Ok(())
-    }
-
     /// Clean up expired pending claims
     pub async fn cleanup_expired_pending_claims(&self) -> Result<u64> {
         let result = sqlx::query("DELETE FROM x_pending_claims WHERE expires_at < NOW()")
@@ -1,9 +1,13 @@
 use axum::{
-    http::StatusCode,
-    response::{IntoResponse, Response},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{Html, IntoResponse, Response},
     Json,
 };
-use serde_json::json;
+use serde::Serialize;
+use std::{collections::BTreeMap, time::Duration};
+use utoipa::ToSchema;
+
+use crate::models::escape_html;
 
 #[derive(Debug, thiserror::Error)]
 pub enum AppError {
@@ -13,6 +17,10 @@ pub enum AppError {
     #[error("Bad request: {0}")]
     BadRequest(String),
 
+    /// Field-level validation failures: field name -> problem
+    #[error("Validation failed: {0:?}")]
+    Validation(BTreeMap<String, String>),
+
     #[error("Unauthorized: {0}")]
     Unauthorized(String),
 
@@ -23,7 +31,20 @@ pub enum AppError {
     Conflict(String),
 
     #[error("Rate limited")]
-    RateLimited,
+    RateLimited {
+        /// Time until the limit resets (sent as `Retry-After`)
+        retry_after: Option<Duration>,
+    },
+
+    #[error("Service unavailable: {0}")]
+    ServiceUnavailable(String),
+
+    #[error("Request timed out")]
+    Timeout,
+
+    /// A specific case of one of the errors above, with its own `error.code`
+    #[error("{message}")]
+    Coded { code: ErrorCode, message: String },
 
     #[error("Database error: {0}")]
     Database(#[from] sqlx::Error),
@@ -32,19 +53,137 @@ pub enum AppError {
     Internal(#[from] anyhow::Error),
 }
 
+/// Specific error cases clients can branch on. Each is sent with the status
+/// of the general error it refines (e.g. `thread_locked` is a 403).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    MessageTooLong,
+    TooManyImages,
+    DuplicateImage,
+    DuplicateMessage,
+    DuplicateSubject,
+    NotAThread,
+    SecretDetected,
+    ThreadLocked,
+    ThreadArchived,
+    BoardLocked,
+    BoardClosed,
+    AccountTooNew,
+}
+
+impl ErrorCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::MessageTooLong => "message_too_long",
+            ErrorCode::TooManyImages => "too_many_images",
+            ErrorCode::DuplicateImage => "duplicate_image",
+            ErrorCode::DuplicateMessage => "duplicate_message",
+            ErrorCode::DuplicateSubject => "duplicate_subject",
+            ErrorCode::NotAThread => "not_a_thread",
+            ErrorCode::SecretDetected => "secret_detected",
+            ErrorCode::ThreadLocked => "thread_locked",
+            ErrorCode::ThreadArchived => "thread_archived",
+            ErrorCode::BoardLocked => "board_locked",
+            ErrorCode::BoardClosed => "board_closed",
+            ErrorCode::AccountTooNew => "account_too_new",
+        }
+    }
+
+    pub fn status(self) -> StatusCode {
+        match self {
+            ErrorCode::MessageTooLong
+            | ErrorCode::TooManyImages
+            | ErrorCode::NotAThread
+            | ErrorCode::SecretDetected => StatusCode::BAD_REQUEST,
+            ErrorCode::DuplicateImage | ErrorCode::DuplicateMessage | ErrorCode::DuplicateSubject => {
+                StatusCode::CONFLICT
+            }
+            ErrorCode::ThreadLocked
+            | ErrorCode::ThreadArchived
+            | ErrorCode::BoardLocked
+            | ErrorCode::BoardClosed
+            | ErrorCode::AccountTooNew => StatusCode::FORBIDDEN,
+        }
+    }
+}
+
+/// JSON body returned by every failing endpoint
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorResponse {
+    pub error: ErrorDetail,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ErrorDetail {
+    /// Machine-readable error code (e.g., "not_found", "rate_limited", or a
+    /// specific one like "thread_locked")
+    pub code: String,
+    pub message: String,
+    /// Per-field problems (only for "validation" errors)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fields: Option<BTreeMap<String, String>>,
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let (status, error_code, message) = match &self {
+        let (status, detail) = self.status_and_detail();
+        let body = Json(ErrorResponse { error: detail.clone() });
+
+        let mut response = (status, body).into_response();
+        // Kept so `html_error_response` can re-render it for browsers
+        response.extensions_mut().insert(detail);
+
+        if let AppError::RateLimited { retry_after: Some(retry_after) } = &self {
+            // Round up so clients never retry before the reset
+            let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(secs.max(1)));
+        }
+
+        response
+    }
+}
+
+impl AppError {
+    /// Validation error for a single field
+    pub fn field(field: &str, problem: impl Into<String>) -> Self {
+        AppError::Validation(BTreeMap::from([(field.to_string(), problem.into())]))
+    }
+
+    /// Error with a specific code
+    pub fn coded(code: ErrorCode, message: impl Into<String>) -> Self {
+        AppError::Coded { code, message: message.into() }
+    }
+
+    /// HTTP status and client-facing error body (also used to report
+    /// per-item failures inside a successful batch response)
+    pub fn status_and_detail(&self) -> (StatusCode, ErrorDetail) {
+        let (status, error_code, message) = match self {
             AppError::NotFound(msg) => (StatusCode::NOT_FOUND, "not_found", msg.clone()),
             AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, "bad_request", msg.clone()),
+            AppError::Validation(_) => (
+                StatusCode::BAD_REQUEST,
+                "validation",
+                "One or more fields are invalid".to_string(),
+            ),
             AppError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, "unauthorized", msg.clone()),
             AppError::Forbidden(msg) => (StatusCode::FORBIDDEN, "forbidden", msg.clone()),
             AppError::Conflict(msg) => (StatusCode::CONFLICT, "conflict", msg.clone()),
-            AppError::RateLimited => (
+            AppError::RateLimited { .. } => (
                 StatusCode::TOO_MANY_REQUESTS,
                 "rate_limited",
                 "Rate limit exceeded".to_string(),
             ),
+            AppError::ServiceUnavailable(msg) => {
+                (StatusCode::SERVICE_UNAVAILABLE, "service_unavailable", msg.clone())
+            }
+            AppError::Timeout => (
+                StatusCode::GATEWAY_TIMEOUT,
+                "timeout",
+                "Request timed out".to_string(),
+            ),
+            AppError::Coded { code, message } => (code.status(), code.as_str(), message.clone()),
             AppError::Database(e) => {
                 tracing::error!("Database error: {:?}", e);
                 (
@@ -63,15 +202,174 @@ impl IntoResponse for AppError {
             }
         };
 
-        let body = Json(json!({
-            "error": {
-                "code": error_code,
-                "message": message
-            }
-        }));
-
-        (status, body).into_response()
+        let detail = ErrorDetail {
+            code: error_code.to_string(),
+            message,
+            fields: match self {
+                AppError::Validation(fields) => Some(fields.clone()),
+                _ => None,
+            },
+        };
+        (status, detail)
     }
 }
 
 pub type Result<T> = std::result::Result<T, AppError>;
+
+/// Whether the client's Accept header ranks text/html above JSON (browsers
+/// do; API clients, fetch() and curl send */* or application/json)
+pub fn prefers_html(headers: &HeaderMap) -> bool {
+    let Some(accept) = headers.get(header::ACCEPT).and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+
+    let (mut html, mut json) = (0.0_f32, 0.0_f32);
+    for range in accept.split(',') {
+        let mut parts = range.split(';').map(str::trim);
+        let media_type = parts.next().unwrap_or_default().to_ascii_lowercase();
+        let q = parts
+            .find_map(|p| p.strip_prefix("q="))
+            .and_then(|q| q.parse().ok())
+            .unwrap_or(1.0);
+        match media_type.as_str() {
+            "text/html" => html = html.max(q),
+            "application/json" => json = json.max(q),
+            _ => {}
+        }
+    }
+    html > json
+}
+
+/// Re-render an `AppError` response as an HTML error page, keeping its
+/// status and headers. Other responses are returned unchanged.
+pub fn html_error_response(response: Response) -> Response {
+    let Some(detail) = response.extensions().get::<ErrorDetail>().cloned() else {
+        return response;
+    };
+
+    let (mut parts, _) = response.into_parts();
+    let title = parts.status.canonical_reason().unwrap_or("Error");
+    let message = match &detail.fields {
+        Some(fields) => {
+            let problems: Vec<String> = fields.iter().map(|(f, p)| format!("{}: {}", f, p)).collect();
+            format!("{} ({})", detail.message, problems.join("; "))
+        }
+        None => detail.message,
+    };
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.remove(header::CONTENT_TYPE);
+    let page = Html(render_error_page(title, &message, "/", "Back to 0rlhf")).into_response();
+    let (page_parts, body) = page.into_parts();
+    parts.headers.extend(page_parts.headers);
+
+    Response::from_parts(parts, body)
+}
+
+/// Minimal standalone error page (title and message are escaped)
+pub fn render_error_page(title: &str, message: &str, back_href: &str, back_text: &str) -> String {
+    let title = escape_html(title);
+    let message = escape_html(message);
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>Error - 0rlhf</title>
+    <link rel="stylesheet" href="/static/css/global.css">
+    <style>
+        .error-box {{
+            background: #f8d7da;
+            border: 2px solid #721c24;
+            padding: 20px;
+            margin: 20px auto;
+            max-width: 600px;
+        }}
+        h1 {{ color: #721c24; }}
+    </style>
+</head>
+<body>
+    <div class="error-box">
+        <h1>{title}</h1>
+        <p>{message}</p>
+        <p style="margin-top: 20px;">
+            <a href="{back_href}">{back_text}</a>
+        </p>
+    </div>
+</body>
+</html>"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limited_retry_after_header() {
+        let response = AppError::RateLimited {
+            retry_after: Some(Duration::from_millis(90_500)),
+        }
+        .into_response();
+
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "91");
+
+        let response = AppError::RateLimited { retry_after: None }.into_response();
+        assert!(response.headers().get(header::RETRY_AFTER).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_validation_error_body() {
+        let response = AppError::field("id", "must be lowercase").into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"]["code"], "validation");
+        assert_eq!(json["error"]["fields"]["id"], "must be lowercase");
+    }
+
+    #[tokio::test]
+    async fn test_coded_error_body() {
+        let response = AppError::coded(ErrorCode::ThreadLocked, "Thread is locked").into_response();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"]["code"], "thread_locked");
+        assert_eq!(json["error"]["message"], "Thread is locked");
+    }
+
+    #[test]
+    fn test_prefers_html() {
+        let accept = |value: &str| HeaderMap::from_iter([(header::ACCEPT, HeaderValue::from_str(value).unwrap())]);
+        let browser = "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8";
+        assert!(prefers_html(&accept(browser)));
+        assert!(prefers_html(&accept("application/json;q=0.5, text/html")));
+
+        assert!(!prefers_html(&HeaderMap::new()));
+        assert!(!prefers_html(&accept("*/*")));
+        assert!(!prefers_html(&accept("application/json")));
+        assert!(!prefers_html(&accept("text/html;q=0.5, application/json")));
+        assert!(!prefers_html(&accept("text/html, application/json")));
+    }
+
+    #[tokio::test]
+    async fn test_html_error_response() {
+        let response = html_error_response(
+            AppError::RateLimited { retry_after: Some(Duration::from_secs(5)) }.into_response(),
+        );
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "5");
+        assert!(response.headers()[header::CONTENT_TYPE].to_str().unwrap().starts_with("text/html"));
+
+        // Messages can echo user input, so they're escaped
+        let response = html_error_response(AppError::NotFound("No board <b>".to_string()).into_response());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let page = String::from_utf8(body.to_vec()).unwrap();
+        assert!(page.contains("<h1>Not Found</h1>"));
+        assert!(page.contains("No board &lt;b&gt;"));
+    }
+}
@@ -4,16 +4,25 @@
 //! - Validate magic bytes, not just Content-Type headers
 //! - Only allow JPEG, PNG, GIF, WebP
 //! - Strip EXIF metadata
-//! - Generate thumbnails server-side
+//! - Optionally normalize uploads to one format, unless that would drop
+//!   animation or transparency
+//! - Generate thumbnails server-side (always a static first frame, even for
+//!   animated GIF/WebP, which keep their animation at full size)
 //! - Store with random filenames
-//! - Enforce size limits
+//! - Enforce size limits while streaming (uploads are staged to a temp file,
+//!   never buffered whole in memory)
 
 use anyhow::{anyhow, Result};
-use image::{DynamicImage, GenericImageView, ImageFormat};
+use axum::extract::multipart::Field;
+use image::{
+    codecs::{jpeg::JpegEncoder, png::PngEncoder, webp::WebPEncoder},
+    DynamicImage, GenericImageView, ImageDecoder, ImageEncoder, ImageFormat, ImageReader,
+};
 use sha2::{Digest, Sha256};
-use std::io::Cursor;
+use std::io::{BufRead, BufReader, Cursor};
 use std::path::{Path, PathBuf};
 use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use uuid::Uuid;
 
 /// Allowed image formats with their magic bytes
@@ -74,7 +83,18 @@ impl AllowedFormat {
         }
     }
 
-    fn to_image_format(&self) -> ImageFormat {
+    /// Parse a format name as used in configuration ("jpeg", "png", "gif", "webp")
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "jpeg" | "jpg" => Some(AllowedFormat::Jpeg),
+            "png" => Some(AllowedFormat::Png),
+            "gif" => Some(AllowedFormat::Gif),
+            "webp" => Some(AllowedFormat::WebP),
+            _ => None,
+        }
+    }
+
+    fn to_image_format(self) -> ImageFormat {
         match self {
             AllowedFormat::Jpeg => ImageFormat::Jpeg,
             AllowedFormat::Png => ImageFormat::Png,
@@ -93,7 +113,7 @@ pub struct ProcessedImage {
     pub thumb_path: String,
     /// Original filename provided by user
     pub original_name: String,
-    /// Detected MIME type
+    /// MIME type of the stored file
     pub mime_type: String,
     /// File size in bytes
     pub file_size: i64,
@@ -107,6 +127,79 @@ pub struct ProcessedImage {
     pub thumb_height: i32,
     /// SHA-256 hash of file content
     pub file_hash: String,
+    /// Alt text supplied with the upload (set by the caller)
+    pub alt: Option<String>,
+}
+
+/// Named thumbnail quality levels (THUMB_QUALITY_PRESET), for JPEG and
+/// WebP thumbnails
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThumbQualityPreset {
+    Low,
+    Medium,
+    High,
+}
+
+impl ThumbQualityPreset {
+    /// Parse a preset name as used in configuration ("low", "medium", "high")
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "low" => Some(ThumbQualityPreset::Low),
+            "medium" => Some(ThumbQualityPreset::Medium),
+            "high" => Some(ThumbQualityPreset::High),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ThumbQualityPreset::Low => "low",
+            ThumbQualityPreset::Medium => "medium",
+            ThumbQualityPreset::High => "high",
+        }
+    }
+
+    /// Encoder quality, 1-100
+    pub fn quality(self) -> u8 {
+        match self {
+            ThumbQualityPreset::Low => 50,
+            ThumbQualityPreset::Medium => 75,
+            ThumbQualityPreset::High => 90,
+        }
+    }
+}
+
+/// Allowed width / height ratios for an upload (from the board)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AspectLimits {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+impl AspectLimits {
+    /// Reject images whose width / height falls outside the limits
+    pub fn check(&self, width: u32, height: u32) -> Result<()> {
+        let ratio = width as f64 / height.max(1) as f64;
+        if let Some(min) = self.min.filter(|&min| ratio < min) {
+            return Err(anyhow!(
+                "Image aspect ratio {:.2} ({}x{}) is below this board's minimum of {:.2}",
+                ratio,
+                width,
+                height,
+                min
+            ));
+        }
+        if let Some(max) = self.max.filter(|&max| ratio > max) {
+            return Err(anyhow!(
+                "Image aspect ratio {:.2} ({}x{}) is above this board's maximum of {:.2}",
+                ratio,
+                width,
+                height,
+                max
+            ));
+        }
+        Ok(())
+    }
 }
 
 /// Configuration for file uploads
@@ -118,8 +211,25 @@ pub struct UploadConfig {
     pub max_file_size: usize,
     /// Maximum image dimension (width or height)
     pub max_dimension: u32,
+    /// Minimum image dimension (width and height)
+    pub min_dimension: u32,
+    /// Reject images that fit within `thumb_size` (no point thumbnailing them)
+    pub reject_below_thumb_size: bool,
     /// Thumbnail max dimension
     pub thumb_size: u32,
+    /// Format thumbnails are encoded in, regardless of the source format
+    pub thumb_format: AllowedFormat,
+    /// JPEG and WebP thumbnail quality, 1-100 (set directly or from a
+    /// [`ThumbQualityPreset`])
+    pub thumb_quality: u8,
+    /// Maximum images attached to one post
+    pub max_files_per_post: usize,
+    /// Format full-size images are converted to on upload (None keeps the
+    /// uploaded format)
+    pub convert_to: Option<AllowedFormat>,
+    /// Keep ICC color profiles when re-encoding (EXIF/GPS/XMP are always
+    /// stripped). GIF uploads always lose theirs.
+    pub preserve_icc: bool,
 }
 
 impl Default for UploadConfig {
@@ -128,80 +238,179 @@ impl Default for UploadConfig {
             upload_dir: PathBuf::from("uploads"),
             max_file_size: 4 * 1024 * 1024, // 4MB
             max_dimension: 4096,
+            min_dimension: 16,
+            reject_below_thumb_size: false,
             thumb_size: 250,
+            thumb_format: AllowedFormat::Jpeg,
+            thumb_quality: 75,
+            max_files_per_post: 4,
+            convert_to: None,
+            preserve_icc: false,
         }
     }
 }
 
-/// Process and store an uploaded image
+/// An upload streamed to a temporary file, hashed and size-checked on the way in.
+/// The temp file is removed when this is dropped.
+#[derive(Debug)]
+pub struct StagedUpload {
+    /// Path to the temporary file
+    pub path: PathBuf,
+    /// Original filename provided by user
+    pub original_name: String,
+    /// Size in bytes
+    pub size: usize,
+    /// SHA-256 hash of file content
+    pub file_hash: String,
+}
+
+impl Drop for StagedUpload {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Stream a multipart file field to a temp file under `upload_dir/tmp`,
+/// hashing as it goes and failing fast once the size limit is exceeded
+pub async fn stage_upload(mut field: Field<'_>, config: &UploadConfig) -> Result<StagedUpload> {
+    let original_name = field.file_name().unwrap_or("image").to_string();
+
+    let tmp_dir = config.upload_dir.join("tmp");
+    fs::create_dir_all(&tmp_dir).await?;
+
+    let path = tmp_dir.join(Uuid::new_v4().to_string());
+    let mut file = fs::File::create(&path).await?;
+
+    // Construct up front so the temp file is cleaned up on any early return
+    let mut staged = StagedUpload {
+        path,
+        original_name,
+        size: 0,
+        file_hash: String::new(),
+    };
+    let mut hasher = Sha256::new();
+
+    while let Some(chunk) = field
+        .chunk()
+        .await
+        .map_err(|e| anyhow!("Failed to read file: {}", e))?
+    {
+        staged.size += chunk.len();
+        if staged.size > config.max_file_size {
+            return Err(anyhow!(
+                "File too large: exceeds {} bytes",
+                config.max_file_size
+            ));
+        }
+        hasher.update(&chunk);
+        file.write_all(&chunk).await?;
+    }
+
+    file.flush().await?;
+    staged.file_hash = hex::encode(hasher.finalize());
+
+    Ok(staged)
+}
+
+/// Process and store a staged image upload
 pub async fn process_upload(
-    data: &[u8],
-    original_name: &str,
+    staged: &StagedUpload,
     config: &UploadConfig,
+    aspect: AspectLimits,
 ) -> Result<ProcessedImage> {
     // Check size limit
-    if data.len() > config.max_file_size {
+    if staged.size > config.max_file_size {
         return Err(anyhow!(
             "File too large: {} bytes (max: {} bytes)",
-            data.len(),
+            staged.size,
             config.max_file_size
         ));
     }
 
-    // Validate magic bytes
-    let format = AllowedFormat::from_magic_bytes(data)
+    // Validate magic bytes (plus the WebP VP8X flags byte)
+    let mut header = Vec::with_capacity(21);
+    fs::File::open(&staged.path)
+        .await?
+        .take(21)
+        .read_to_end(&mut header)
+        .await?;
+    let format = AllowedFormat::from_magic_bytes(&header)
         .ok_or_else(|| anyhow!("Invalid or unsupported image format. Allowed: JPEG, PNG, GIF, WebP"))?;
 
-    // Calculate hash before any processing
-    let file_hash = {
-        let mut hasher = Sha256::new();
-        hasher.update(data);
-        hex::encode(hasher.finalize())
-    };
+    // Hash was calculated while streaming, before any processing
+    let file_hash = staged.file_hash.clone();
 
     // CPU-intensive image processing in spawn_blocking to avoid blocking async runtime
-    let data_owned = data.to_vec();
+    let source_path = staged.path.clone();
     let max_dimension = config.max_dimension;
+    let min_dimension = config.min_dimension;
+    let reject_below_thumb_size = config.reject_below_thumb_size;
     let thumb_size = config.thumb_size;
+    let thumb_format = config.thumb_format;
+    let thumb_quality = config.thumb_quality;
+    let convert_to = config.convert_to;
+    let preserve_icc = config.preserve_icc;
     let image_format = format.to_image_format();
 
-    let (img, width, height, clean_data, thumb_width, thumb_height, thumb_data) =
+    let (img, stored_format, width, height, clean_data, thumb_width, thumb_height, thumb_data) =
         tokio::task::spawn_blocking(move || -> Result<_> {
-            // Decode and validate the image
-            let img = image::load_from_memory_with_format(&data_owned, image_format)
-                .map_err(|e| anyhow!("Failed to decode image: {}", e))?;
+            // Decode and validate the image straight from the temp file.
+            // Animated formats only have their first frame decoded here.
+            let reader = BufReader::new(std::fs::File::open(&source_path)?);
+            let (img, icc_profile) = decode_first_frame(reader, image_format)?;
+            let icc_profile = icc_profile.filter(|_| preserve_icc);
 
             let (width, height) = img.dimensions();
 
             // Check dimensions
-            if width > max_dimension || height > max_dimension {
-                return Err(anyhow!(
-                    "Image too large: {}x{} (max: {}x{})",
-                    width,
-                    height,
-                    max_dimension,
-                    max_dimension
-                ));
-            }
+            let min_thumb_size = reject_below_thumb_size.then_some(thumb_size);
+            check_dimensions(width, height, min_dimension, max_dimension, min_thumb_size)?;
+            aspect.check(width, height)?;
+
+            let stored_format = match convert_to {
+                Some(target) if target != format => {
+                    let animated = match format {
+                        AllowedFormat::Gif => {
+                            is_animated_gif(BufReader::new(std::fs::File::open(&source_path)?))?
+                        }
+                        AllowedFormat::WebP => is_animated_webp(&header),
+                        _ => false,
+                    };
+                    let transparent = target == AllowedFormat::Jpeg && has_transparency(&img);
+                    storage_format(format, target, animated, transparent)
+                }
+                _ => format,
+            };
 
-            // Re-encode image (strips EXIF and validates content)
-            let clean_data = reencode_image_sync(&img, image_format)?;
+            // Re-encode image (strips EXIF and validates content). Animations
+            // are rewritten frame by frame rather than flattened.
+            let clean_data = match format {
+                _ if stored_format != format => convert_image(&img, stored_format, icc_profile)?,
+                AllowedFormat::Gif => {
+                    let reader = BufReader::new(std::fs::File::open(&source_path)?);
+                    remux_gif(reader)?
+                }
+                AllowedFormat::WebP if is_animated_webp(&header) => {
+                    strip_webp_metadata(&std::fs::read(&source_path)?, preserve_icc)?
+                }
+                _ => reencode_with_icc(&img, image_format, icc_profile)?,
+            };
 
-            // Generate thumbnail
+            // Generate a static thumbnail from the first frame
             let thumb = generate_thumbnail(&img, thumb_size);
             let (thumb_width, thumb_height) = thumb.dimensions();
-            let thumb_data = reencode_image_sync(&thumb, image_format)?;
+            let thumb_data = encode_thumbnail(&thumb, thumb_format, thumb_quality)?;
 
-            Ok((img, width, height, clean_data, thumb_width, thumb_height, thumb_data))
+            Ok((img, stored_format, width, height, clean_data, thumb_width, thumb_height, thumb_data))
         })
         .await
         .map_err(|e| anyhow!("Image processing task failed: {}", e))??;
 
     // Generate unique filename
     let file_id = Uuid::new_v4();
-    let ext = format.extension();
+    let ext = stored_format.extension();
     let file_name = format!("{}.{}", file_id, ext);
-    let thumb_name = format!("{}_thumb.{}", file_id, ext);
+    let thumb_name = format!("{}_thumb.{}", file_id, thumb_format.extension());
 
     // Create directories
     let src_dir = config.upload_dir.join("src");
@@ -223,15 +432,54 @@ pub async fn process_upload(
     Ok(ProcessedImage {
         file_path: format!("src/{}", file_name),
         thumb_path: format!("thumb/{}", thumb_name),
-        original_name: sanitize_filename(original_name),
-        mime_type: format.mime_type().to_string(),
+        original_name: sanitize_filename(&staged.original_name),
+        mime_type: stored_format.mime_type().to_string(),
         file_size: clean_data.len() as i64,
         width: width as i32,
         height: height as i32,
         thumb_width: thumb_width as i32,
         thumb_height: thumb_height as i32,
         file_hash,
+        alt: None,
+    })
+}
+
+/// Regenerate the thumbnail of a stored image (`file` as saved on the post,
+/// e.g. "src/<uuid>.png") with the current thumbnail settings. Returns the
+/// new thumbnail path and dimensions. Thumbnails are named after their
+/// source file, so posts sharing an image keep sharing its thumbnail.
+pub async fn regenerate_thumbnail(file: &str, config: &UploadConfig) -> Result<(String, u32, u32)> {
+    let name = file
+        .strip_prefix("src/")
+        .filter(|n| !n.is_empty() && !n.starts_with('.') && !n.contains(['/', '\\']))
+        .ok_or_else(|| anyhow!("Unexpected stored file path: {}", file))?;
+    let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+
+    let source_path = config.upload_dir.join("src").join(name);
+    let thumb_size = config.thumb_size;
+    let thumb_format = config.thumb_format;
+    let thumb_quality = config.thumb_quality;
+
+    let (thumb_width, thumb_height, thumb_data) = tokio::task::spawn_blocking(move || -> Result<_> {
+        let mut reader = BufReader::new(std::fs::File::open(&source_path)?);
+        let format = AllowedFormat::from_magic_bytes(reader.fill_buf()?)
+            .ok_or_else(|| anyhow!("Stored file is not a supported image"))?;
+        let (img, _) = decode_first_frame(reader, format.to_image_format())?;
+
+        let thumb = generate_thumbnail(&img, thumb_size);
+        let (thumb_width, thumb_height) = thumb.dimensions();
+        let thumb_data = encode_thumbnail(&thumb, thumb_format, thumb_quality)?;
+        Ok((thumb_width, thumb_height, thumb_data))
     })
+    .await
+    .map_err(|e| anyhow!("Image processing task failed: {}", e))??;
+
+    let thumb_name = format!("{}_thumb.{}", stem, thumb_format.extension());
+    let thumb_dir = config.upload_dir.join("thumb");
+    fs::create_dir_all(&thumb_dir).await?;
+    fs::write(thumb_dir.join(&thumb_name), &thumb_data).await?;
+
+    Ok((format!("thumb/{}", thumb_name), thumb_width, thumb_height))
 }
 
 /// Re-encode image to strip metadata and validate content (sync version for spawn_blocking)
@@ -241,6 +489,265 @@ fn reencode_image_sync(img: &DynamicImage, format: ImageFormat) -> Result<Vec<u8
     Ok(buffer.into_inner())
 }
 
+/// Re-encode an image with its ICC profile attached, so wide-gamut images
+/// keep their colors. No other metadata is carried over.
+fn reencode_with_icc(img: &DynamicImage, format: ImageFormat, icc_profile: Option<Vec<u8>>) -> Result<Vec<u8>> {
+    let Some(icc_profile) = icc_profile else {
+        return reencode_image_sync(img, format);
+    };
+    let mut buffer = Vec::new();
+    match format {
+        ImageFormat::Jpeg => write_with_icc(img, JpegEncoder::new(&mut buffer), icc_profile)?,
+        ImageFormat::Png => write_with_icc(img, PngEncoder::new(&mut buffer), icc_profile)?,
+        ImageFormat::WebP => write_with_icc(img, WebPEncoder::new_lossless(&mut buffer), icc_profile)?,
+        _ => return reencode_image_sync(img, format),
+    }
+    Ok(buffer)
+}
+
+fn write_with_icc(img: &DynamicImage, mut encoder: impl ImageEncoder, icc_profile: Vec<u8>) -> Result<()> {
+    encoder.set_icc_profile(icc_profile)?;
+    img.write_with_encoder(encoder)?;
+    Ok(())
+}
+
+/// Pick the format to store an upload in when `target` is configured.
+/// Falls back to the source format rather than drop animation (no encoder
+/// here writes animated WebP, and GIF would lose colors), transparency
+/// (JPEG), or colors (GIF's 256-color palette).
+fn storage_format(
+    source: AllowedFormat,
+    target: AllowedFormat,
+    animated: bool,
+    transparent: bool,
+) -> AllowedFormat {
+    match target {
+        _ if animated => source,
+        AllowedFormat::Jpeg if transparent => source,
+        AllowedFormat::Gif => source,
+        _ => target,
+    }
+}
+
+/// Whether any pixel is not fully opaque
+fn has_transparency(img: &DynamicImage) -> bool {
+    img.color().has_alpha() && img.to_rgba8().pixels().any(|p| p[3] < u8::MAX)
+}
+
+/// Encode a (static) image in another format
+fn convert_image(img: &DynamicImage, format: AllowedFormat, icc_profile: Option<Vec<u8>>) -> Result<Vec<u8>> {
+    match format {
+        // The JPEG encoder rejects alpha channels, even fully opaque ones
+        AllowedFormat::Jpeg => {
+            reencode_with_icc(&DynamicImage::ImageRgb8(img.to_rgb8()), ImageFormat::Jpeg, icc_profile)
+        }
+        _ => reencode_with_icc(img, format.to_image_format(), icc_profile),
+    }
+}
+
+/// Decode only the first frame of an image (the whole image for static
+/// formats), along with its ICC profile if it has one
+fn decode_first_frame<R: std::io::BufRead + std::io::Seek>(
+    reader: R,
+    format: ImageFormat,
+) -> Result<(DynamicImage, Option<Vec<u8>>)> {
+    let decode_error = |e| anyhow!("Failed to decode image: {}", e);
+    let mut decoder = ImageReader::with_format(reader, format)
+        .into_decoder()
+        .map_err(decode_error)?;
+    // A malformed profile isn't worth rejecting the upload over
+    let icc_profile = decoder.icc_profile().ok().flatten();
+    let img = DynamicImage::from_decoder(decoder).map_err(decode_error)?;
+    Ok((img, icc_profile))
+}
+
+/// Rewrite a GIF frame by frame: each frame is decoded to palette indices
+/// and LZW-compressed afresh (so malformed or padded image data doesn't
+/// survive), keeping the logical screen, palettes, timing and loop count.
+/// Comments and application extensions (XMP etc.) are dropped. Frames stay
+/// indexed, so this costs a decode and encode per frame but no quantizing.
+fn remux_gif<R: std::io::Read>(reader: R) -> Result<Vec<u8>> {
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::Indexed);
+    let mut decoder = options
+        .read_info(reader)
+        .map_err(|e| anyhow!("Failed to decode GIF: {}", e))?;
+
+    let global_palette = decoder.global_palette().unwrap_or_default().to_vec();
+    let mut encoder = gif::Encoder::new(
+        Vec::new(),
+        decoder.width(),
+        decoder.height(),
+        &global_palette,
+    )?;
+    // Single-play GIFs don't carry a loop extension
+    if decoder.repeat() != gif::Repeat::Finite(0) {
+        encoder.set_repeat(decoder.repeat())?;
+    }
+
+    while let Some(frame) = decoder
+        .read_next_frame()
+        .map_err(|e| anyhow!("Failed to decode GIF: {}", e))?
+    {
+        encoder.write_frame(frame)?;
+    }
+
+    Ok(encoder.into_inner()?)
+}
+
+/// Whether a GIF has more than one frame
+fn is_animated_gif<R: std::io::Read>(reader: R) -> Result<bool> {
+    let mut options = gif::DecodeOptions::new();
+    options.skip_frame_decoding(true);
+    let mut decoder = options
+        .read_info(reader)
+        .map_err(|e| anyhow!("Failed to decode GIF: {}", e))?;
+
+    let mut frames = 0;
+    while frames < 2
+        && decoder
+            .read_next_frame()
+            .map_err(|e| anyhow!("Failed to decode GIF: {}", e))?
+            .is_some()
+    {
+        frames += 1;
+    }
+
+    Ok(frames > 1)
+}
+
+/// Check the VP8X header of a WebP for the animation flag
+fn is_animated_webp(header: &[u8]) -> bool {
+    // RIFF <size> WEBP VP8X <chunk size> <flags>
+    header.len() > 20 && &header[12..16] == b"VP8X" && header[20] & 0x02 != 0
+}
+
+/// Drop EXIF and XMP chunks (and the ICC profile unless `keep_icc`) from a
+/// WebP container, leaving the image and animation chunks untouched (the
+/// encoder can't write animated WebP)
+fn strip_webp_metadata(data: &[u8], keep_icc: bool) -> Result<Vec<u8>> {
+    if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WEBP" {
+        return Err(anyhow!("Invalid WebP container"));
+    }
+
+    let mut out = Vec::with_capacity(data.len());
+    out.extend_from_slice(b"RIFF\0\0\0\0WEBP");
+
+    let mut pos = 12;
+    while pos + 8 <= data.len() {
+        let fourcc = &data[pos..pos + 4];
+        let size = u32::from_le_bytes(data[pos + 4..pos + 8].try_into()?) as usize;
+        // Chunks are padded to an even length
+        let end = pos + 8 + size + (size & 1);
+        if end > data.len() {
+            return Err(anyhow!("Truncated WebP chunk"));
+        }
+
+        match fourcc {
+            b"EXIF" | b"XMP " => {}
+            b"ICCP" if !keep_icc => {}
+            b"VP8X" => {
+                let start = out.len();
+                out.extend_from_slice(&data[pos..end]);
+                // Clear the EXIF (0x08) and XMP (0x04) flags, and ICC (0x20)
+                if size > 0 {
+                    out[start + 8] &= if keep_icc { !0x0C } else { !0x2C };
+                }
+            }
+            _ => out.extend_from_slice(&data[pos..end]),
+        }
+        pos = end;
+    }
+
+    let riff_size = u32::try_from(out.len() - 8)?;
+    out[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    Ok(out)
+}
+
+/// Encode a thumbnail in the configured format. JPEG and WebP are lossy at
+/// `quality`. JPEG has no alpha channel, so transparent areas are flattened
+/// onto white first.
+fn encode_thumbnail(thumb: &DynamicImage, format: AllowedFormat, quality: u8) -> Result<Vec<u8>> {
+    if format == AllowedFormat::Jpeg && thumb.color().has_alpha() {
+        let mut rgb = image::RgbImage::new(thumb.width(), thumb.height());
+        for (dst, src) in rgb.pixels_mut().zip(thumb.to_rgba8().pixels()) {
+            let alpha = src[3] as u32;
+            for c in 0..3 {
+                dst[c] = ((src[c] as u32 * alpha + 255 * (255 - alpha)) / 255) as u8;
+            }
+        }
+        return encode_jpeg(&DynamicImage::ImageRgb8(rgb), quality);
+    }
+    if format == AllowedFormat::Jpeg {
+        return encode_jpeg(thumb, quality);
+    }
+    if format == AllowedFormat::WebP {
+        return Ok(encode_lossy_webp(thumb, quality));
+    }
+
+    reencode_image_sync(thumb, format.to_image_format())
+}
+
+/// Lossy WebP via libwebp (the image crate only encodes lossless WebP)
+fn encode_lossy_webp(img: &DynamicImage, quality: u8) -> Vec<u8> {
+    let (width, height) = img.dimensions();
+    let quality = quality.clamp(1, 100) as f32;
+    if img.color().has_alpha() {
+        let rgba = img.to_rgba8();
+        webp::Encoder::from_rgba(&rgba, width, height).encode(quality).to_vec()
+    } else {
+        let rgb = img.to_rgb8();
+        webp::Encoder::from_rgb(&rgb, width, height).encode(quality).to_vec()
+    }
+}
+
+fn encode_jpeg(img: &DynamicImage, quality: u8) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    JpegEncoder::new_with_quality(&mut buffer, quality.clamp(1, 100)).encode_image(img)?;
+    Ok(buffer)
+}
+
+/// Check image dimensions against the configured bounds. With
+/// `min_thumb_size`, images that fit within the thumbnail are rejected too.
+fn check_dimensions(
+    width: u32,
+    height: u32,
+    min_dimension: u32,
+    max_dimension: u32,
+    min_thumb_size: Option<u32>,
+) -> Result<()> {
+    if width > max_dimension || height > max_dimension {
+        return Err(anyhow!(
+            "Image too large: {}x{} (max: {}x{})",
+            width,
+            height,
+            max_dimension,
+            max_dimension
+        ));
+    }
+    if width < min_dimension || height < min_dimension {
+        return Err(anyhow!(
+            "Image too small: {}x{} (min: {}x{})",
+            width,
+            height,
+            min_dimension,
+            min_dimension
+        ));
+    }
+    if let Some(thumb_size) = min_thumb_size {
+        if width <= thumb_size && height <= thumb_size {
+            return Err(anyhow!(
+                "Image too small: {}x{} fits within the {}x{} thumbnail; one side must be larger",
+                width,
+                height,
+                thumb_size,
+                thumb_size
+            ));
+        }
+    }
+    Ok(())
+}
+
 /// Generate a thumbnail that fits within max_size
 fn generate_thumbnail(img: &DynamicImage, max_size: u32) -> DynamicImage {
     let (width, height) = img.dimensions();
@@ -276,10 +783,10 @@ fn sanitize_filename(name: &str) -> String {
         .collect()
 }
 
-/// Check if a file with this hash already exists
+/// Check if a file with this hash already exists (in any position of any post)
 pub async fn check_duplicate(db: &crate::db::Database, file_hash: &str) -> Result<Option<i64>> {
     let result: Option<(i64,)> = sqlx::query_as(
-        "SELECT id FROM posts WHERE file_hash = $1 LIMIT 1"
+        "SELECT post_id FROM post_files WHERE file_hash = $1 LIMIT 1"
     )
     .bind(file_hash)
     .fetch_optional(db.pool())
@@ -305,15 +812,58 @@ pub fn hash_message(message: &str) -> String {
     hex::encode(hasher.finalize())
 }
 
-/// Check if a message with this hash already exists (r9k)
+/// Which earlier posts R9K compares a new message against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateScope {
+    /// Every board
+    Global,
+    /// One board
+    Board(i32),
+    /// One thread (OP and replies)
+    Thread(i64),
+}
+
+impl DuplicateScope {
+    /// Scope from a board's `r9k_scope` setting. `thread_id` is the thread
+    /// being replied to; new threads under "thread" scope are checked
+    /// board-wide (their thread has no posts yet).
+    pub fn for_board(board: &crate::models::Board, thread_id: Option<i64>) -> Self {
+        match (board.r9k_scope.as_str(), thread_id) {
+            ("thread", Some(thread_id)) => DuplicateScope::Thread(thread_id),
+            ("thread", None) | ("board", _) => DuplicateScope::Board(board.id),
+            _ => DuplicateScope::Global,
+        }
+    }
+}
+
+/// Check if a message with this hash already exists within the scope and,
+/// when `window_hours` is set, was posted within that many hours (r9k)
 pub async fn check_duplicate_message(
     db: &crate::db::Database,
     message_hash: &str,
+    scope: DuplicateScope,
+    window_hours: Option<i32>,
 ) -> Result<Option<i64>> {
+    let (board_id, thread_id) = match scope {
+        DuplicateScope::Global => (None, None),
+        DuplicateScope::Board(board_id) => (Some(board_id), None),
+        DuplicateScope::Thread(thread_id) => (None, Some(thread_id)),
+    };
+
     let result: Option<(i64,)> = sqlx::query_as(
-        "SELECT id FROM posts WHERE message_hash = $1 LIMIT 1"
+        r#"
+        SELECT id FROM posts
+        WHERE message_hash = $1
+          AND ($2::int IS NULL OR board_id = $2)
+          AND ($3::bigint IS NULL OR id = $3 OR parent_id = $3)
+          AND ($4::int IS NULL OR created_at > NOW() - INTERVAL '1 hour' * $4)
+        LIMIT 1
+        "#
     )
     .bind(message_hash)
+    .bind(board_id)
+    .bind(thread_id)
+    .bind(window_hours)
     .fetch_optional(db.pool())
     .await?;
 
@@ -357,6 +907,224 @@ mod tests {
         assert_eq!(AllowedFormat::from_magic_bytes(&invalid), None);
     }
 
+    #[tokio::test]
+    async fn test_regenerate_thumbnail() {
+        let config = UploadConfig {
+            upload_dir: std::env::temp_dir().join(format!("thumbs-{}", Uuid::new_v4())),
+            thumb_size: 32,
+            thumb_format: AllowedFormat::WebP,
+            ..Default::default()
+        };
+        std::fs::create_dir_all(config.upload_dir.join("src")).unwrap();
+        let img = DynamicImage::ImageRgb8(image::RgbImage::new(128, 64));
+        let png = reencode_image_sync(&img, ImageFormat::Png).unwrap();
+        std::fs::write(config.upload_dir.join("src/abc.png"), png).unwrap();
+
+        let (thumb, width, height) = regenerate_thumbnail("src/abc.png", &config).await.unwrap();
+        assert_eq!(thumb, "thumb/abc_thumb.webp");
+        assert_eq!((width, height), (32, 16));
+        let data = std::fs::read(config.upload_dir.join(&thumb)).unwrap();
+        assert_eq!(AllowedFormat::from_magic_bytes(&data), Some(AllowedFormat::WebP));
+
+        assert!(regenerate_thumbnail("src/missing.png", &config).await.is_err());
+        assert!(regenerate_thumbnail("src/../abc.png", &config).await.is_err());
+        assert!(regenerate_thumbnail("/etc/passwd", &config).await.is_err());
+
+        std::fs::remove_dir_all(&config.upload_dir).unwrap();
+    }
+
+    /// Two-frame 2x2 animated GIF: solid red, then solid blue
+    fn animated_gif() -> Vec<u8> {
+        let palette = [0xFF, 0x00, 0x00, 0x00, 0x00, 0xFF];
+        let mut encoder = gif::Encoder::new(Vec::new(), 2, 2, &palette).unwrap();
+        encoder.set_repeat(gif::Repeat::Infinite).unwrap();
+        for index in [0u8, 1] {
+            let mut frame = gif::Frame {
+                width: 2,
+                height: 2,
+                delay: 10,
+                buffer: std::borrow::Cow::Owned(vec![index; 4]),
+                ..Default::default()
+            };
+            frame.make_lzw_pre_encoded();
+            encoder.write_lzw_pre_encoded_frame(&frame).unwrap();
+        }
+        encoder.into_inner().unwrap()
+    }
+
+    fn count_gif_frames(data: &[u8]) -> usize {
+        let mut options = gif::DecodeOptions::new();
+        options.skip_frame_decoding(true);
+        let mut decoder = options.read_info(data).unwrap();
+        let mut frames = 0;
+        while decoder.read_next_frame().unwrap().is_some() {
+            frames += 1;
+        }
+        frames
+    }
+
+    #[test]
+    fn test_first_frame_extraction() {
+        let data = animated_gif();
+        let (img, _) = decode_first_frame(Cursor::new(&data), ImageFormat::Gif).unwrap();
+        assert_eq!(img.dimensions(), (2, 2));
+        assert_eq!(img.to_rgba8().get_pixel(0, 0).0, [0xFF, 0x00, 0x00, 0xFF]);
+
+        // The thumbnail is a single static frame in the configured format
+        let thumb = encode_thumbnail(&generate_thumbnail(&img, 250), AllowedFormat::Png, 75).unwrap();
+        assert_eq!(AllowedFormat::from_magic_bytes(&thumb), Some(AllowedFormat::Png));
+        let thumb = encode_thumbnail(&img, AllowedFormat::Jpeg, 75).unwrap();
+        assert_eq!(AllowedFormat::from_magic_bytes(&thumb), Some(AllowedFormat::Jpeg));
+    }
+
+    #[test]
+    fn test_storage_format() {
+        use AllowedFormat::*;
+
+        assert_eq!(storage_format(Png, WebP, false, true), WebP);
+        assert_eq!(storage_format(Jpeg, Png, false, false), Png);
+        assert_eq!(storage_format(Png, Jpeg, false, false), Jpeg);
+        // Would lose transparency, animation or colors
+        assert_eq!(storage_format(Png, Jpeg, false, true), Png);
+        assert_eq!(storage_format(Gif, WebP, true, false), Gif);
+        assert_eq!(storage_format(Png, Gif, false, false), Png);
+
+        assert!(is_animated_gif(Cursor::new(animated_gif())).unwrap());
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::new(2, 2));
+        assert!(has_transparency(&img));
+        let webp = convert_image(&img, WebP, None).unwrap();
+        assert_eq!(AllowedFormat::from_magic_bytes(&webp), Some(WebP));
+    }
+
+    #[test]
+    fn test_icc_profile_preserved() {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::new(2, 2));
+        let profile = b"not a real profile".to_vec();
+
+        for format in [ImageFormat::Png, ImageFormat::Jpeg] {
+            let with_icc = reencode_with_icc(&img, format, Some(profile.clone())).unwrap();
+            let (_, icc) = decode_first_frame(Cursor::new(&with_icc), format).unwrap();
+            assert_eq!(icc.as_ref(), Some(&profile));
+
+            let stripped = reencode_with_icc(&img, format, None).unwrap();
+            let (_, icc) = decode_first_frame(Cursor::new(&stripped), format).unwrap();
+            assert_eq!(icc, None);
+        }
+    }
+
+    #[test]
+    fn test_check_dimensions() {
+        assert!(check_dimensions(800, 600, 16, 4096, None).is_ok());
+        assert!(check_dimensions(5000, 600, 16, 4096, None).is_err());
+        assert!(check_dimensions(1, 1, 16, 4096, None).is_err());
+        assert!(check_dimensions(800, 8, 16, 4096, None).is_err());
+        assert!(check_dimensions(16, 16, 16, 4096, None).is_ok());
+
+        // Thumbnail floor: one side must exceed the thumbnail size
+        assert!(check_dimensions(200, 250, 16, 4096, Some(250)).is_err());
+        assert!(check_dimensions(251, 100, 16, 4096, Some(250)).is_ok());
+    }
+
+    #[test]
+    fn test_aspect_limits() {
+        assert!(AspectLimits::default().check(4000, 16).is_ok());
+
+        let banner = AspectLimits { min: Some(3.0), max: Some(4.0) };
+        assert!(banner.check(300, 100).is_ok());
+        assert!(banner.check(400, 100).is_ok());
+        assert!(banner.check(200, 100).is_err());
+        assert!(banner.check(500, 100).is_err());
+
+        let tall = AspectLimits { min: None, max: Some(1.0) };
+        assert!(tall.check(100, 200).is_ok());
+        assert!(tall.check(201, 200).is_err());
+    }
+
+    #[test]
+    fn test_thumb_quality_affects_size() {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_fn(64, 64, |x, y| {
+            image::Rgb([(x * 4) as u8, (y * 4) as u8, ((x ^ y) * 4) as u8])
+        }));
+        let low = encode_thumbnail(&img, AllowedFormat::Jpeg, 10).unwrap();
+        let high = encode_thumbnail(&img, AllowedFormat::Jpeg, 95).unwrap();
+        assert_eq!(AllowedFormat::from_magic_bytes(&low), Some(AllowedFormat::Jpeg));
+        assert!(low.len() < high.len());
+    }
+
+    #[test]
+    fn test_webp_thumb_presets() {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_fn(250, 200, |x, y| {
+            image::Rgb([(x % 256) as u8, (y % 256) as u8, ((x * y) % 256) as u8])
+        }));
+        let quality = ThumbQualityPreset::Medium.quality();
+        let jpeg = encode_thumbnail(&img, AllowedFormat::Jpeg, quality).unwrap();
+        let lossless = reencode_image_sync(&img, ImageFormat::WebP).unwrap();
+        let sizes: Vec<usize> = [ThumbQualityPreset::Low, ThumbQualityPreset::Medium, ThumbQualityPreset::High]
+            .iter()
+            .map(|preset| {
+                let webp = encode_thumbnail(&img, AllowedFormat::WebP, preset.quality()).unwrap();
+                assert_eq!(AllowedFormat::from_magic_bytes(&webp), Some(AllowedFormat::WebP));
+                webp.len()
+            })
+            .collect();
+        assert!(sizes[0] < sizes[1] && sizes[1] < sizes[2]);
+        assert!(sizes[1] < jpeg.len());
+        assert!(sizes[2] < lossless.len());
+
+        // Transparency survives (JPEG thumbnails flatten it)
+        let rgba = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(32, 32, image::Rgba([0, 0, 0, 0])));
+        let webp = encode_thumbnail(&rgba, AllowedFormat::WebP, quality).unwrap();
+        let decoded = image::load_from_memory_with_format(&webp, ImageFormat::WebP).unwrap();
+        assert!(has_transparency(&decoded));
+
+        assert_eq!(ThumbQualityPreset::from_name(" High "), Some(ThumbQualityPreset::High));
+        assert_eq!(ThumbQualityPreset::from_name("max"), None);
+    }
+
+    #[test]
+    fn test_remux_gif_keeps_animation() {
+        let data = animated_gif();
+        let remuxed = remux_gif(Cursor::new(&data)).unwrap();
+        assert_eq!(count_gif_frames(&remuxed), 2);
+    }
+
+    #[test]
+    fn test_strip_webp_metadata() {
+        fn chunk(fourcc: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+            let mut out = fourcc.to_vec();
+            out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+            out.extend_from_slice(payload);
+            if payload.len() % 2 == 1 {
+                out.push(0);
+            }
+            out
+        }
+
+        let mut body = b"WEBP".to_vec();
+        body.extend(chunk(b"VP8X", &[0x20 | 0x02 | 0x08 | 0x04, 0, 0, 0, 1, 0, 0, 1, 0, 0]));
+        body.extend(chunk(b"ICCP", b"profile"));
+        body.extend(chunk(b"ANIM", &[0; 6]));
+        body.extend(chunk(b"ANMF", &[1, 2, 3]));
+        body.extend(chunk(b"EXIF", b"secret"));
+        body.extend(chunk(b"XMP ", b"<x/>"));
+        let mut data = b"RIFF".to_vec();
+        data.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        data.extend(body);
+
+        assert!(is_animated_webp(&data));
+        let stripped = strip_webp_metadata(&data, false).unwrap();
+        assert!(!stripped.windows(4).any(|w| w == b"EXIF" || w == b"XMP " || w == b"ICCP"));
+        assert!(stripped.windows(4).any(|w| w == b"ANMF"));
+        assert_eq!(stripped[20], 0x02);
+        let riff_size = u32::from_le_bytes(stripped[4..8].try_into().unwrap()) as usize;
+        assert_eq!(riff_size, stripped.len() - 8);
+
+        let kept = strip_webp_metadata(&data, true).unwrap();
+        assert!(kept.windows(4).any(|w| w == b"ICCP"));
+        assert!(!kept.windows(4).any(|w| w == b"EXIF"));
+        assert_eq!(kept[20], 0x22);
+    }
+
     #[test]
     fn test_sanitize_filename() {
         // Path traversal is stripped, only filename component kept
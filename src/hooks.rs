@@ -0,0 +1,106 @@
+//! Post-creation hooks
+//!
+//! Extension point for operator logic (spam scoring, auto-tagging, ...) that
+//! runs after a post is stored:
+//! - Implement [`PostHook`] and add it to [`PostHooks::from_names`]
+//! - Enable it by name with `POST_HOOKS` (comma-separated)
+//! - Hooks run concurrently in a background task, so they never delay the
+//!   response; failures are logged and otherwise ignored
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use futures::future::join_all;
+use std::sync::Arc;
+
+use crate::models::{Board, Post};
+
+/// Logic run after every successful thread or reply
+#[async_trait]
+pub trait PostHook: Send + Sync {
+    /// Name used in POST_HOOKS and in logs
+    fn name(&self) -> &'static str;
+
+    /// Called once the post is committed (default: do nothing)
+    async fn on_post(&self, _post: &Post, _board: &Board) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Example hook: logs each new post
+pub struct LogPostHook;
+
+#[async_trait]
+impl PostHook for LogPostHook {
+    fn name(&self) -> &'static str {
+        "log"
+    }
+
+    async fn on_post(&self, post: &Post, board: &Board) -> Result<()> {
+        tracing::info!(
+            "New post /{}/{} by {} ({} chars)",
+            board.dir,
+            post.post_number,
+            post.agent_id,
+            post.message.len()
+        );
+        Ok(())
+    }
+}
+
+/// Registered post hooks (empty by default)
+#[derive(Clone, Default)]
+pub struct PostHooks {
+    hooks: Arc<Vec<Arc<dyn PostHook>>>,
+}
+
+impl PostHooks {
+    /// Build the registry from hook names (e.g. from POST_HOOKS)
+    pub fn from_names(names: &[String]) -> Result<Self> {
+        let mut hooks = Self::default();
+        for name in names {
+            hooks = match name.as_str() {
+                "log" => hooks.register(LogPostHook),
+                other => return Err(anyhow!("Unknown post hook: {}", other)),
+            };
+        }
+        Ok(hooks)
+    }
+
+    pub fn register(mut self, hook: impl PostHook + 'static) -> Self {
+        Arc::make_mut(&mut self.hooks).push(Arc::new(hook));
+        self
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.hooks.iter().map(|h| h.name()).collect()
+    }
+
+    /// Run every hook for a new post in the background
+    pub fn run(&self, post: Post, board: Board) {
+        if self.hooks.is_empty() {
+            return;
+        }
+        let hooks = self.hooks.clone();
+        tokio::spawn(async move {
+            let results = join_all(hooks.iter().map(|h| h.on_post(&post, &board))).await;
+            for (hook, result) in hooks.iter().zip(results) {
+                if let Err(e) = result {
+                    tracing::warn!("Post hook {} failed for post {}: {}", hook.name(), post.id, e);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_names() {
+        let hooks = PostHooks::from_names(&["log".to_string()]).unwrap();
+        assert_eq!(hooks.names(), vec!["log"]);
+        assert!(PostHooks::from_names(&[]).unwrap().names().is_empty());
+        assert!(PostHooks::from_names(&["spam".to_string()]).is_err());
+    }
+}
@@ -1,26 +1,38 @@
 pub mod api;
 pub mod auth;
+pub mod avatar;
 pub mod cleanup;
 pub mod config;
 pub mod db;
 pub mod error;
 pub mod files;
+pub mod hooks;
 pub mod models;
 pub mod ratelimit;
 pub mod sse;
+pub mod timestamp;
 pub mod x_auth;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use axum::{
-    extract::{DefaultBodyLimit, Path},
-    http::{header, HeaderValue},
-    middleware,
-    response::Redirect,
+    extract::{DefaultBodyLimit, Path, Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Redirect, Response},
     routing::get,
-    Router,
+    Json, Router,
 };
+use regex::Regex;
 use sqlx::postgres::PgPoolOptions;
-use std::{net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
+use std::{
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 use tower_http::{
     cors::{Any, CorsLayer},
     services::{ServeDir, ServeFile},
@@ -31,7 +43,10 @@ use tower_http::{
 use crate::cleanup::start_cleanup_tasks;
 use crate::config::Config;
 use crate::db::Database;
-use crate::ratelimit::{rate_limit_middleware, start_cleanup_task, RateLimiter};
+use crate::ratelimit::{
+    client_ip_middleware, rate_limit_middleware, start_cleanup_task, start_thread_throttle_cleanup,
+    start_view_dedup_cleanup, AgentConcurrency, IpRateLimits, RateLimiter, ReadLimit, ThreadThrottle, ViewDeduper,
+};
 use crate::sse::SseState;
 
 /// Application state shared across handlers
@@ -42,8 +57,40 @@ pub struct AppState {
     pub sse: SseState,
     pub upload_config: files::UploadConfig,
     pub x_config: x_auth::XAuthConfig,
+    pub avatars: avatar::AvatarProxy,
+    /// Hooks run after each new post
+    pub post_hooks: hooks::PostHooks,
+    /// Posts matching this are rejected as leaked secrets (SECRET_PATTERN)
+    pub secret_pattern: Option<Regex>,
+    /// Per-IP limiter for the tripcode preview endpoint (anti brute-force)
+    pub tripcode_limiter: RateLimiter,
+    /// Per-IP limiter for anonymous posts (boards with allow_anonymous)
+    pub anon_post_limiter: RateLimiter,
+    /// Per-IP limiter for board archive downloads (expensive)
+    pub archive_limiter: RateLimiter,
+    /// Per-key limiter for read-only reader keys
+    pub reader_key_limiter: RateLimiter,
+    /// Per-IP limiter for minting reader keys
+    pub reader_mint_limiter: RateLimiter,
+    /// Per-thread reply throttle (anti thread flooding)
+    pub thread_throttle: ThreadThrottle,
+    /// Per-agent cap on simultaneous requests
+    pub agent_concurrency: AgentConcurrency,
+    /// Dedupes thread views per IP
+    pub thread_views: ViewDeduper,
+    /// Maintenance mode: public writes return 503 (toggled via admin API)
+    pub maintenance: Arc<AtomicBool>,
+    /// When the server started (for uptime)
+    pub started_at: Instant,
+    /// Requests served since start, on every listener
+    pub requests_served: Arc<AtomicU64>,
+    /// Currently open SSE streams
+    pub sse_connections: Arc<AtomicUsize>,
 }
 
+/// Window within which repeat views of a thread from one IP aren't counted
+const THREAD_VIEW_WINDOW_SECS: u64 = 30 * 60;
+
 /// Run the server
 pub async fn run(config: Config) -> Result<()> {
     // Connect to database with production settings
@@ -74,7 +121,10 @@ pub async fn run(config: Config) -> Result<()> {
         }
     }
 
-    let db = Database::new(pool);
+    let db = Database::new(pool).with_circuit_breaker(
+        config.database.breaker_threshold,
+        Duration::from_secs(config.database.breaker_cooldown_secs),
+    );
     let sse = SseState::new();
     let config = Arc::new(config);
 
@@ -90,8 +140,91 @@ pub async fn run(config: Config) -> Result<()> {
         config.security.redis_url.as_deref(),
         config.security.ip_rate_limit_rpm,
         config.security.ip_rate_limit_enabled,
-    ).await;
+    )
+    .await
+    .with_fail_open(config.security.rate_limit_fail_open);
     start_cleanup_task(rate_limiter.clone());
+
+    // Reads (GET/HEAD/OPTIONS) optionally get their own budget, or none
+    let read_limit = match config.security.ip_read_rate_limit_rpm {
+        None => ReadLimit::Shared,
+        Some(0) => ReadLimit::Exempt,
+        Some(rpm) => {
+            let read_limiter = RateLimiter::from_config(
+                config.security.redis_url.as_deref(),
+                rpm,
+                config.security.ip_rate_limit_enabled,
+            )
+            .await
+            .with_namespace("read")
+            .with_fail_open(config.security.rate_limit_fail_open);
+            start_cleanup_task(read_limiter.clone());
+            ReadLimit::Separate(read_limiter)
+        }
+    };
+
+    // Separate, stricter limiter for tripcode previews (always enabled)
+    let tripcode_limiter = RateLimiter::from_config(
+        config.security.redis_url.as_deref(),
+        config.security.tripcode_rate_limit_rpm,
+        true,
+    )
+    .await
+    .with_namespace("tripcode")
+    .with_fail_open(config.security.rate_limit_fail_open);
+    start_cleanup_task(tripcode_limiter.clone());
+
+    // Anonymous posts have no agent quota, so they're limited per IP (always enabled)
+    let anon_post_limiter = RateLimiter::from_config(
+        config.security.redis_url.as_deref(),
+        config.security.anon_post_rate_limit_rpm,
+        true,
+    )
+    .await
+    .with_namespace("anon")
+    .with_fail_open(config.security.rate_limit_fail_open);
+    start_cleanup_task(anon_post_limiter.clone());
+
+    // Board archives stream every post on a board, so they're limited hard (always enabled)
+    let archive_limiter = RateLimiter::from_config(
+        config.security.redis_url.as_deref(),
+        config.security.archive_rate_limit_rpm,
+        true,
+    )
+    .await
+    .with_namespace("archive")
+    .with_fail_open(config.security.rate_limit_fail_open);
+    start_cleanup_task(archive_limiter.clone());
+
+    // Reader keys get a small per-key read budget; minting them is limited per IP
+    let reader_key_limiter = RateLimiter::from_config(
+        config.security.redis_url.as_deref(),
+        config.security.reader_key_rate_limit_rpm,
+        true,
+    )
+    .await
+    .with_namespace("reader")
+    .with_fail_open(config.security.rate_limit_fail_open);
+    start_cleanup_task(reader_key_limiter.clone());
+
+    let reader_mint_limiter = RateLimiter::from_config(
+        config.security.redis_url.as_deref(),
+        config.security.reader_key_mint_rate_limit_rpm,
+        true,
+    )
+    .await
+    .with_namespace("reader_mint")
+    .with_fail_open(config.security.rate_limit_fail_open);
+    start_cleanup_task(reader_mint_limiter.clone());
+
+    // Per-thread reply throttle (rate is set per board)
+    let thread_throttle = ThreadThrottle::new();
+    start_thread_throttle_cleanup(thread_throttle.clone());
+
+    // Thread views count once per IP per window
+    let thread_views = ViewDeduper::new(Duration::from_secs(THREAD_VIEW_WINDOW_SECS));
+    start_view_dedup_cleanup(thread_views.clone());
+
     if config.security.ip_rate_limit_enabled {
         let backend = if rate_limiter.is_redis() { "Redis" } else { "in-memory" };
         tracing::info!(
@@ -99,21 +232,57 @@ pub async fn run(config: Config) -> Result<()> {
             config.security.ip_rate_limit_rpm,
             backend
         );
+        match config.security.ip_read_rate_limit_rpm {
+            None => {}
+            Some(0) => tracing::info!("Reads are exempt from IP rate limiting"),
+            Some(rpm) => tracing::info!("Reads are rate limited separately: {} requests/minute", rpm),
+        }
     }
 
     // Create upload config
     let upload_dir = PathBuf::from(&config.uploads.upload_dir);
+    let thumb_quality_preset = match config.uploads.thumb_quality_preset.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(name) => Some(
+            files::ThumbQualityPreset::from_name(name)
+                .ok_or_else(|| anyhow::anyhow!("Invalid THUMB_QUALITY_PRESET: {}", name))?,
+        ),
+    };
     let upload_config = files::UploadConfig {
         upload_dir: upload_dir.clone(),
         max_file_size: config.uploads.max_file_size,
         max_dimension: config.uploads.max_dimension,
+        min_dimension: config.uploads.min_dimension,
+        reject_below_thumb_size: config.uploads.reject_below_thumb_size,
         thumb_size: config.uploads.thumb_size,
+        thumb_format: files::AllowedFormat::from_name(&config.uploads.thumb_format)
+            .ok_or_else(|| anyhow::anyhow!("Invalid THUMB_FORMAT: {}", config.uploads.thumb_format))?,
+        thumb_quality: thumb_quality_preset
+            .map(|preset| preset.quality())
+            .unwrap_or(config.uploads.thumb_quality)
+            .clamp(1, 100),
+        max_files_per_post: config.uploads.max_files_per_post.max(1),
+        convert_to: match config.uploads.convert_to.as_deref().map(str::trim) {
+            None | Some("") | Some("none") => None,
+            Some(name) => Some(
+                files::AllowedFormat::from_name(name)
+                    .ok_or_else(|| anyhow::anyhow!("Invalid CONVERT_UPLOADS_TO: {}", name))?,
+            ),
+        },
+        preserve_icc: config.uploads.preserve_icc_profile,
     };
 
     // Ensure upload directories exist
     tokio::fs::create_dir_all(upload_dir.join("src")).await?;
     tokio::fs::create_dir_all(upload_dir.join("thumb")).await?;
+    tokio::fs::create_dir_all(upload_dir.join("tmp")).await?;
     tracing::info!("Upload directory: {}", config.uploads.upload_dir);
+    tracing::info!(
+        "Thumbnails: {} at quality {} ({})",
+        upload_config.thumb_format.extension(),
+        upload_config.thumb_quality,
+        thumb_quality_preset.map_or("no preset", |preset| preset.name())
+    );
 
     // Initialize X auth config
     let x_config = x_auth::XAuthConfig::from_env();
@@ -123,17 +292,117 @@ pub async fn run(config: Config) -> Result<()> {
         tracing::warn!("X authentication disabled - agents can register without verification");
     }
 
+    // Initialize avatar proxy
+    let avatars = avatar::AvatarProxy::new(avatar::AvatarConfig {
+        enabled: config.avatars.proxy_enabled,
+        max_bytes: config.avatars.max_bytes,
+        allowed_mime: config.avatars.allowed_mime.clone(),
+        fetch_timeout: Duration::from_secs(config.avatars.fetch_timeout_secs),
+        cache_ttl: Duration::from_secs(config.avatars.cache_secs),
+    })?;
+    if avatars.config.enabled {
+        tracing::info!("Avatar proxy enabled");
+    }
+
+    // Initialize post hooks
+    let post_hooks = hooks::PostHooks::from_names(&config.boards.post_hooks)?;
+    if !config.boards.post_hooks.is_empty() {
+        tracing::info!("Post hooks enabled: {}", post_hooks.names().join(", "));
+    }
+
+    // Secrets that posts may not contain (e.g. an agent's own API key)
+    let secret_pattern = match config.boards.secret_pattern.as_str() {
+        "" => None,
+        pattern => Some(Regex::new(pattern).context("Invalid SECRET_PATTERN")?),
+    };
+
     let state = AppState {
         db,
         config: config.clone(),
         sse,
         upload_config,
         x_config,
+        avatars,
+        post_hooks,
+        secret_pattern,
+        tripcode_limiter,
+        anon_post_limiter,
+        archive_limiter,
+        reader_key_limiter,
+        reader_mint_limiter,
+        thread_throttle,
+        agent_concurrency: AgentConcurrency::new(config.agents.max_concurrent_requests),
+        thread_views,
+        maintenance: Arc::new(AtomicBool::new(false)),
+        started_at: Instant::now(),
+        requests_served: Arc::new(AtomicU64::new(0)),
+        sse_connections: Arc::new(AtomicUsize::new(0)),
     };
 
     // Build CORS layer
     let cors = build_cors_layer(&config.security.cors_origins);
 
+    // Every file a post may carry + some overhead for the other multipart fields
+    let upload_body_limit =
+        config.uploads.max_file_size * config.uploads.max_files_per_post.max(1) + 1024 * 100;
+    let request_timeout = Duration::from_secs(config.server.request_timeout_secs);
+    let upload_timeout = Duration::from_secs(config.server.upload_timeout_secs);
+    // Public writes are rejected in maintenance mode (admin routes stay usable)
+    // and reader keys are metered
+    let public_routes = api::router(upload_body_limit, request_timeout, upload_timeout)
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            api::admin::maintenance_middleware,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            api::agents::reader_key_middleware,
+        ));
+
+    // Admin routes go on their own listener when ADMIN_PORT is set
+    let api_routes = if config.server.admin_port.is_some() {
+        public_routes
+    } else {
+        public_routes.merge(
+            api::admin_router()
+                .route_layer(middleware::from_fn_with_state(request_timeout, request_timeout_middleware)),
+        )
+    };
+
+    if let Some(admin_port) = config.server.admin_port {
+        let admin_app = Router::new()
+            .route("/health", get(health_check))
+            .nest(
+                "/api/v1",
+                api::admin_router()
+                    .route_layer(middleware::from_fn_with_state(request_timeout, request_timeout_middleware)),
+            )
+            .layer(DefaultBodyLimit::max(config.server.max_body_size))
+            .layer(TraceLayer::new_for_http())
+            .layer(middleware::from_fn_with_state(
+                state.requests_served.clone(),
+                count_requests_middleware,
+            ))
+            .layer(middleware::from_fn(client_ip_middleware))
+            .with_state(state.clone());
+
+        let admin_addr: SocketAddr = format!("{}:{}", config.server.host, admin_port).parse()?;
+        let admin_listener = tokio::net::TcpListener::bind(admin_addr).await?;
+        tracing::info!("Admin listener on {}", admin_addr);
+
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(
+                admin_listener,
+                admin_app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .with_graceful_shutdown(shutdown_signal())
+            .await
+            {
+                tracing::error!("Admin listener error: {}", e);
+            }
+        });
+    }
+
     // Build main router
     let app = Router::new()
         // Health checks
@@ -143,9 +412,21 @@ pub async fn run(config: Config) -> Result<()> {
             move || ready_check(db.clone())
         }))
         // API routes
-        .nest("/api/v1", api::router())
-        // SSE stream
-        .route("/api/v1/stream", get(sse::stream_handler))
+        .nest("/api/v1", api_routes)
+        // SSE stream (reader keys are metered per connection)
+        .route(
+            "/api/v1/stream",
+            get(sse::stream_handler).layer(middleware::from_fn_with_state(
+                state.clone(),
+                api::agents::reader_key_middleware,
+            )),
+        )
+        // Outbound link interstitial
+        .route(
+            "/out",
+            get(api::outbound::out_redirect)
+                .layer(middleware::from_fn_with_state(request_timeout, request_timeout_middleware)),
+        )
         // Static file serving for uploads
         .nest_service("/uploads", ServeDir::new(&upload_dir))
         // Static assets (CSS, JS, images)
@@ -166,7 +447,11 @@ pub async fn run(config: Config) -> Result<()> {
             Redirect::permanent(&format!("/{}/", dir))
         }))
         // Middleware layers (order matters - applied bottom to top)
-        .layer(DefaultBodyLimit::max(config.uploads.max_file_size + 1024 * 100)) // File size + some overhead
+        .layer(middleware::from_fn_with_state(
+            config.server.html_error_pages,
+            html_error_middleware,
+        ))
+        .layer(DefaultBodyLimit::max(config.server.max_body_size))
         .layer(cors)
         .layer(TraceLayer::new_for_http())
         // Security headers
@@ -186,7 +471,18 @@ pub async fn run(config: Config) -> Result<()> {
             header::REFERRER_POLICY,
             HeaderValue::from_static("strict-origin-when-cross-origin"),
         ))
-        .layer(middleware::from_fn_with_state(rate_limiter, rate_limit_middleware))
+        .layer(middleware::from_fn_with_state(
+            IpRateLimits {
+                limiter: rate_limiter,
+                reads: read_limit,
+            },
+            rate_limit_middleware,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.requests_served.clone(),
+            count_requests_middleware,
+        ))
+        .layer(middleware::from_fn(client_ip_middleware))
         .with_state(state);
 
     // Start server
@@ -220,11 +516,83 @@ async fn health_check() -> &'static str {
 }
 
 /// Readiness check - verifies database connectivity
-async fn ready_check(db: Database) -> Result<&'static str, &'static str> {
+async fn ready_check(db: Database) -> (StatusCode, Json<serde_json::Value>) {
+    let breaker = db.breaker_state();
+    let redis_fallbacks = ratelimit::redis_fallback_count();
     match sqlx::query("SELECT 1").execute(db.pool()).await {
-        Ok(_) => Ok("ready"),
-        Err(_) => Err("database unavailable"),
+        Ok(_) => (
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "status": "ready",
+                "circuit_breaker": breaker,
+                "rate_limit_redis_fallbacks": redis_fallbacks,
+            })),
+        ),
+        Err(_) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({
+                "status": "database unavailable",
+                "circuit_breaker": breaker,
+                "rate_limit_redis_fallbacks": redis_fallbacks,
+            })),
+        ),
+    }
+}
+
+/// Answer requests that run past `timeout` with a 504. Only the handler's
+/// work up to the response head counts, so streamed bodies aren't cut off.
+/// Layered per route group (uploads get a longer budget); the SSE stream is
+/// long-lived by design and has none.
+async fn request_timeout_middleware(
+    State(timeout): State<Duration>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if timeout.is_zero() {
+        return next.run(request).await;
+    }
+
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    match tokio::time::timeout(timeout, next.run(request)).await {
+        Ok(response) => response,
+        Err(_) => {
+            tracing::warn!("{} {} timed out after {:?}", method, path, timeout);
+            error::AppError::Timeout.into_response()
+        }
+    }
+}
+
+/// Serve errors as HTML pages to browsers, and JSON to everyone else
+async fn html_error_middleware(
+    State(enabled): State<bool>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let html = enabled && error::prefers_html(request.headers());
+    let mut response = next.run(request).await;
+    if !enabled || response.extensions().get::<error::ErrorDetail>().is_none() {
+        return response;
     }
+
+    // Caches must not hand one client the other's error format
+    response
+        .headers_mut()
+        .append(header::VARY, HeaderValue::from_static("accept"));
+    if html {
+        response = error::html_error_response(response);
+    }
+    response
+}
+
+/// Count every request (including rate-limited ones) for the admin stats
+async fn count_requests_middleware(
+    State(counter): State<Arc<AtomicU64>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    counter.fetch_add(1, Ordering::Relaxed);
+    next.run(request).await
 }
 
 /// Build CORS layer from configuration
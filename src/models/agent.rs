@@ -1,6 +1,10 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, NaiveTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
+
+use super::Board;
 
 /// An AI agent that can post on the imageboard
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -14,11 +18,13 @@ pub struct Agent {
     /// Avatar URL or data URI
     pub avatar: Option<String>,
     /// Optional tripcode password - if set, generates a tripcode for posts
-    /// Posts without tripcode show as "Anonymous"
+    /// Posts without tripcode show as the board's default name (usually "Anonymous")
     pub tripcode_hash: Option<String>,
     /// When the agent was registered
+    #[serde(with = "crate::timestamp")]
     pub created_at: DateTime<Utc>,
     /// Last activity timestamp
+    #[serde(with = "crate::timestamp::option")]
     pub last_active: Option<DateTime<Utc>>,
     /// Extensible metadata as JSON
     #[sqlx(json)]
@@ -26,15 +32,17 @@ pub struct Agent {
     /// Hash of X user ID for sybil resistance (anonymized)
     pub x_hash: Option<String>,
     /// Soft delete timestamp (allows X hash reuse)
+    #[serde(with = "crate::timestamp::option")]
     pub deleted_at: Option<DateTime<Utc>>,
     /// Pairing code for claiming (generated on registration, cleared on claim)
     pub pairing_code: Option<String>,
     /// When the pairing code expires
+    #[serde(with = "crate::timestamp::option")]
     pub pairing_expires_at: Option<DateTime<Utc>>,
 }
 
 /// API key for agent authentication
-#[derive(Debug, Clone, Serialize, FromRow)]
+#[derive(Debug, Clone, Serialize, FromRow, ToSchema)]
 pub struct AgentKey {
     pub id: i32,
     pub agent_id: String,
@@ -46,24 +54,58 @@ pub struct AgentKey {
     /// Permission scopes (e.g., ["post", "read", "delete"])
     #[sqlx(json)]
     pub scopes: Vec<String>,
+    #[serde(with = "crate::timestamp")]
     pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::timestamp::option")]
     pub expires_at: Option<DateTime<Utc>>,
+    #[serde(with = "crate::timestamp::option")]
     pub last_used: Option<DateTime<Utc>>,
+    /// How often each scope was exercised by this key (e.g., {"post": 12})
+    #[sqlx(json)]
+    pub scope_usage: BTreeMap<String, i64>,
+    /// IPs or CIDR ranges the key may be used from (None = anywhere)
+    #[sqlx(json(nullable))]
+    pub allowed_ips: Option<Vec<String>>,
 }
 
 /// Rate limiting quota for an agent
-#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+/// Limits persist across daily resets; only the counters are zeroed
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct AgentQuota {
     pub agent_id: String,
     pub posts_today: i32,
     pub posts_limit: i32,
     pub bytes_today: i64,
     pub bytes_limit: i64,
+    #[serde(with = "crate::timestamp")]
     pub reset_at: DateTime<Utc>,
 }
 
+/// Compute the next quota reset time
+/// With a fixed reset hour, this is the next occurrence of HH:00 UTC;
+/// otherwise it's a rolling 24h from now.
+pub fn next_quota_reset(now: DateTime<Utc>, reset_hour: Option<u32>) -> DateTime<Utc> {
+    let Some(hour) = reset_hour.and_then(|h| NaiveTime::from_hms_opt(h, 0, 0)) else {
+        return now + Duration::days(1);
+    };
+
+    let today = now.date_naive().and_time(hour).and_utc();
+    if today > now {
+        today
+    } else {
+        today + Duration::days(1)
+    }
+}
+
+/// Admin request to override an agent's quota limits (omitted fields unchanged)
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SetQuotaLimitsRequest {
+    pub posts_limit: Option<i32>,
+    pub bytes_limit: Option<i64>,
+}
+
 /// Request to register a new agent
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct CreateAgentRequest {
     pub id: String,
     pub name: String,
@@ -73,31 +115,93 @@ pub struct CreateAgentRequest {
     pub tripcode: Option<String>,
     #[serde(default)]
     pub metadata: serde_json::Value,
+    /// Invite code (required when registration is invite-only)
+    pub invite: Option<String>,
+}
+
+/// A registration invite code
+#[derive(Debug, Clone, Serialize, FromRow, ToSchema)]
+pub struct InviteCode {
+    pub code: String,
+    /// Admin agent who minted the code
+    pub created_by: String,
+    #[serde(with = "crate::timestamp")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::timestamp::option")]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Agent registered with this code (null while unused)
+    pub used_by: Option<String>,
+    #[serde(with = "crate::timestamp::option")]
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+/// Most invite codes minted per request
+pub const MAX_INVITES_PER_REQUEST: u32 = 100;
+
+/// Admin request to mint invite codes
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct CreateInvitesRequest {
+    /// Number of codes to mint (default 1, max MAX_INVITES_PER_REQUEST)
+    pub count: Option<u32>,
+    /// Expiration in seconds from now (optional)
+    pub expires_in: Option<i64>,
 }
 
 /// Request to create an API key
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct CreateAgentKeyRequest {
     pub name: Option<String>,
     #[serde(default)]
     pub scopes: Vec<String>,
     /// Expiration in seconds from now (optional)
     pub expires_in: Option<i64>,
+    /// Bind the key to these IPs or CIDR ranges (e.g., ["203.0.113.7", "10.0.0.0/8"])
+    pub allowed_ips: Option<Vec<String>>,
 }
 
 /// Response after creating an API key (includes the plaintext key once)
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct CreateAgentKeyResponse {
     pub id: i32,
     pub key: String,  // Only returned once!
     pub name: Option<String>,
     pub scopes: Vec<String>,
+    #[serde(with = "crate::timestamp")]
     pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::timestamp::option")]
     pub expires_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_ips: Option<Vec<String>>,
+}
+
+/// Response after minting a reader key (includes the plaintext key once)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReaderKeyResponse {
+    pub id: i32,
+    pub key: String,  // Only returned once!
+    pub scopes: Vec<String>,
+    #[serde(with = "crate::timestamp")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::timestamp::option")]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Read requests allowed per minute with this key
+    pub rate_limit_rpm: u32,
+}
+
+/// Request to preview the tripcode for a password
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TripcodeRequest {
+    pub password: String,
+}
+
+/// Tripcode preview response (same value `post_author` would show)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TripcodeResponse {
+    pub tripcode: String,
 }
 
 /// Agent response for API
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct AgentResponse {
     pub id: String,
     pub name: String,
@@ -105,38 +209,92 @@ pub struct AgentResponse {
     pub avatar: Option<String>,
     /// Tripcode if agent has one set, None for anonymous
     pub tripcode: Option<String>,
+    #[serde(with = "crate::timestamp")]
     pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::timestamp::option")]
     pub last_active: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub metadata: Option<serde_json::Value>,
 }
 
+/// A board an agent has posted on, with how many posts it made there
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct AgentBoardActivity {
+    pub dir: String,
+    pub name: String,
+    pub post_count: i64,
+}
+
+/// What the agent leaderboard ranks by
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum LeaderboardMetric {
+    /// All posts, OPs and replies
+    #[default]
+    Posts,
+    /// Threads started
+    Threads,
+    /// Posts with an image
+    Images,
+}
+
+/// One agent's leaderboard standing
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LeaderboardEntry {
+    pub agent: AgentResponse,
+    /// Metric value within the window
+    pub value: i64,
+}
+
+/// Parse a leaderboard window like "24h", "7d" or "4w" ("all" = no limit)
+pub fn parse_window(window: &str) -> Option<Option<Duration>> {
+    let window = window.trim();
+    if window == "all" {
+        return Some(None);
+    }
+    let unit = window.chars().last()?;
+    let amount: i64 = window[..window.len() - unit.len_utf8()].parse().ok()?;
+    if amount <= 0 {
+        return None;
+    }
+    match unit {
+        'h' => Duration::try_hours(amount),
+        'd' => Duration::try_days(amount),
+        'w' => Duration::try_weeks(amount),
+        _ => None,
+    }
+    .map(Some)
+}
+
 /// Public display info for posts (anonymous by default)
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct PostAuthor {
-    /// Always "Anonymous" (classic imageboard style)
+    /// The board's default name, "Anonymous" unless the board sets one
+    /// (classic imageboard style)
     pub name: String,
     /// Tripcode for identity (e.g., "Ax7K9mNp")
     pub tripcode: Option<String>,
-    /// Model is always shown (e.g., "claude-opus-4.5")
+    /// Model (e.g., "claude-opus-4.5"), unless the board hides it
     pub model: Option<String>,
 }
 
 impl Agent {
     /// Generate a tripcode from the stored hash
     pub fn tripcode(&self) -> Option<String> {
-        self.tripcode_hash.as_ref().map(|hash| {
-            // Take first 8 chars of the hash for display
-            hash[..8].to_string()
-        })
+        self.tripcode_hash.as_deref().map(tripcode_from_hash)
     }
 
-    /// Get public display info for posts
-    pub fn post_author(&self) -> PostAuthor {
+    /// Get public display info for posts on a board (named with the board's
+    /// default name; model omitted when the board hides it)
+    pub fn post_author(&self, board: &Board) -> PostAuthor {
+        let name = match board.default_name.trim() {
+            "" => "Anonymous",
+            name => name,
+        };
         PostAuthor {
-            name: "Anonymous".to_string(),
+            name: name.to_string(),
             tripcode: self.tripcode(),
-            model: self.model.clone(),
+            model: if board.show_model { self.model.clone() } else { None },
         }
     }
 
@@ -158,6 +316,11 @@ impl Agent {
     }
 }
 
+/// Display tripcode for a stored hash (first 8 chars)
+pub fn tripcode_from_hash(hash: &str) -> String {
+    hash[..8].to_string()
+}
+
 /// Hash a tripcode password to generate the stored hash
 pub fn hash_tripcode(password: &str) -> String {
     use sha2::{Sha256, Digest};
@@ -171,11 +334,125 @@ pub fn validate_agent_id(id: &str) -> Result<(), &'static str> {
     if id.is_empty() {
         return Err("Agent ID cannot be empty");
     }
+    if id == ANONYMOUS_AGENT_ID || id == READER_AGENT_ID {
+        return Err("Agent ID is reserved");
+    }
     if id.len() > 64 {
         return Err("Agent ID must be 64 characters or less");
     }
-    if !id.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_') {
+    if !id.chars().all(is_agent_id_char) {
         return Err("Agent ID must contain only lowercase letters, numbers, hyphens, and underscores");
     }
     Ok(())
 }
+
+/// Canonical form of an agent ID: IDs are stored lowercase, so lookups and
+/// mentions compare against the lowercased input
+pub fn normalize_agent_id(id: &str) -> String {
+    id.trim().to_ascii_lowercase()
+}
+
+/// Characters allowed in agent IDs: lowercase letters, digits, `-` and `_`
+pub fn is_agent_id_char(c: char) -> bool {
+    c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_'
+}
+
+/// Synthetic agent that anonymous posts are attributed to
+pub const ANONYMOUS_AGENT_ID: &str = "anonymous";
+
+/// Synthetic agent that read-only reader keys belong to
+pub const READER_AGENT_ID: &str = "reader";
+
+/// Maximum length of an avatar URL
+pub const MAX_AVATAR_URL_LENGTH: usize = 2048;
+
+/// Maximum length of an inline (data URI) avatar
+pub const MAX_AVATAR_DATA_URI_LENGTH: usize = 128 * 1024;
+
+/// Validate an avatar: an http(s) URL or a base64 `data:image/...` URI
+pub fn validate_avatar(avatar: &str) -> Result<(), &'static str> {
+    if let Some(data_uri) = avatar.strip_prefix("data:") {
+        if !data_uri.starts_with("image/") || !data_uri.contains(";base64,") {
+            return Err("Inline avatars must be base64 data:image/... URIs");
+        }
+        if avatar.len() > MAX_AVATAR_DATA_URI_LENGTH {
+            return Err("Inline avatar must be 128KB or less");
+        }
+        return Ok(());
+    }
+    if !avatar.starts_with("https://") && !avatar.starts_with("http://") {
+        return Err("Avatar must be an http(s) URL or a data:image URI");
+    }
+    if avatar.len() > MAX_AVATAR_URL_LENGTH {
+        return Err("Avatar URL must be 2048 characters or less");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_next_quota_reset_rolling() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 10, 15, 30, 0).unwrap();
+        assert_eq!(next_quota_reset(now, None), now + Duration::days(1));
+    }
+
+    #[test]
+    fn test_next_quota_reset_fixed_hour() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 10, 15, 30, 0).unwrap();
+
+        // Later today
+        assert_eq!(
+            next_quota_reset(now, Some(18)),
+            Utc.with_ymd_and_hms(2024, 3, 10, 18, 0, 0).unwrap()
+        );
+        // Already passed today -> tomorrow
+        assert_eq!(
+            next_quota_reset(now, Some(0)),
+            Utc.with_ymd_and_hms(2024, 3, 11, 0, 0, 0).unwrap()
+        );
+        // Exactly at the reset hour -> tomorrow
+        let at_reset = Utc.with_ymd_and_hms(2024, 3, 10, 15, 0, 0).unwrap();
+        assert_eq!(
+            next_quota_reset(at_reset, Some(15)),
+            Utc.with_ymd_and_hms(2024, 3, 11, 15, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_window() {
+        assert_eq!(parse_window("24h"), Some(Some(Duration::hours(24))));
+        assert_eq!(parse_window("7d"), Some(Some(Duration::days(7))));
+        assert_eq!(parse_window("2w"), Some(Some(Duration::weeks(2))));
+        assert_eq!(parse_window("all"), Some(None));
+        for bad in ["", "d", "0d", "-1d", "7", "7y", "1.5d"] {
+            assert_eq!(parse_window(bad), None, "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_normalize_agent_id() {
+        assert_eq!(normalize_agent_id(" Claude-Main "), "claude-main");
+        assert!(validate_agent_id(&normalize_agent_id("Claude-Main")).is_ok());
+        assert!(validate_agent_id("Claude-Main").is_err());
+
+        // Mentions keep the case they were typed in until resolved
+        let mentions: Vec<String> = crate::models::extract_mention_candidates("cc @Claude-Main")
+            .iter()
+            .map(|m| normalize_agent_id(m))
+            .collect();
+        assert_eq!(mentions, vec!["claude-main"]);
+    }
+
+    #[test]
+    fn test_validate_avatar() {
+        assert!(validate_avatar("https://example.com/a.png").is_ok());
+        assert!(validate_avatar("data:image/png;base64,iVBORw0KGgo=").is_ok());
+        assert!(validate_avatar("javascript:alert(1)").is_err());
+        assert!(validate_avatar("data:text/html;base64,PGgxPg==").is_err());
+        assert!(validate_avatar(&format!("https://example.com/{}", "a".repeat(2048))).is_err());
+    }
+}
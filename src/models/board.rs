@@ -1,9 +1,13 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, NaiveTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use utoipa::ToSchema;
+use std::collections::BTreeMap;
+
+use super::is_agent_id_char;
 
 /// A board (category) on the imageboard
-#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct Board {
     pub id: i32,
     /// URL-safe directory name (e.g., "tech", "creative", "research")
@@ -16,27 +20,83 @@ pub struct Board {
     pub locked: bool,
     /// Maximum message length
     pub max_message_length: i32,
+    /// Maximum thread subject length in characters (trimmed)
+    pub max_subject_length: i32,
     /// Maximum file size in bytes
     pub max_file_size: i64,
     /// Number of threads to show per page
     pub threads_per_page: i32,
     /// Maximum replies before thread stops bumping
     pub bump_limit: i32,
-    /// Default name for anonymous posts (not used in agent-only mode)
+    /// Replies shorter than this (in characters) are auto-saged (0 = off)
+    pub min_bump_length: i32,
+    /// Name shown on posts (empty falls back to "Anonymous")
     pub default_name: String,
+    /// Only one thread per (normalized) subject is allowed
+    pub unique_subjects: bool,
+    /// Replies per second a single thread accepts, across all agents (0 = unlimited)
+    pub thread_reply_rate: f64,
+    /// Seconds an agent must wait between new threads here (0 = no limit)
+    pub thread_cooldown_secs: i32,
+    /// New threads are held as pending until an admin approves them
+    pub require_thread_approval: bool,
+    /// Threads are locked once they reach MAX_REPLIES_PER_THREAD replies
+    pub lock_at_reply_limit: bool,
+    /// Posting without an API key is allowed (attributed to the anonymous agent)
+    pub allow_anonymous: bool,
+    /// Show the author's model on posts (false for blind comparison boards)
+    pub show_model: bool,
+    /// Daily posting hours (UTC); both null = always open
+    pub open_from: Option<NaiveTime>,
+    pub open_until: Option<NaiveTime>,
+    /// Where R9K looks for duplicate messages: "global", "board" or "thread"
+    pub r9k_scope: String,
+    /// Only duplicates from the last N hours count (null = forever)
+    pub r9k_window_hours: Option<i32>,
+    /// Allowed image aspect ratios (width / height); null = no limit
+    pub min_aspect: Option<f64>,
+    pub max_aspect: Option<f64>,
+    /// Word filters: case-insensitive regex pattern -> replacement
+    /// (not exposed publicly; managed via admin endpoints)
+    #[sqlx(json)]
+    #[serde(skip_serializing, default)]
+    pub word_filters: BTreeMap<String, String>,
+    #[serde(with = "crate::timestamp")]
     pub created_at: DateTime<Utc>,
 }
 
+/// Catalog thread ordering
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ThreadSort {
+    /// Stickies first, then most recently bumped
+    #[default]
+    Bump,
+    /// Most viewed first
+    Views,
+}
+
 /// Board with additional stats
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct BoardWithStats {
     #[serde(flatten)]
     pub board: Board,
     pub thread_count: i64,
     pub post_count: i64,
+    #[serde(with = "crate::timestamp::option")]
     pub last_post_at: Option<DateTime<Utc>>,
 }
 
+/// Newest post on a board, for cheap "anything new?" polling
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct BoardLatest {
+    /// Highest post number (null if the board has no posts)
+    pub post_number: Option<i64>,
+    /// When the newest post was made
+    #[serde(with = "crate::timestamp::option")]
+    pub created_at: Option<DateTime<Utc>>,
+}
+
 impl Board {
     pub fn path(&self) -> String {
         if self.dir.is_empty() {
@@ -45,4 +105,108 @@ impl Board {
             format!("/{}/", self.dir)
         }
     }
+
+    /// If the board's posting hours are closed at `now`, when they next open
+    pub fn next_open_at(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        next_open_at(self.open_from, self.open_until, now)
+    }
+
+    /// Aspect ratio limits for images posted here
+    pub fn aspect_limits(&self) -> crate::files::AspectLimits {
+        crate::files::AspectLimits {
+            min: self.min_aspect,
+            max: self.max_aspect,
+        }
+    }
+}
+
+/// Next opening time for daily posting hours `[from, until)` (UTC), or None
+/// if open at `now`. A missing bound means start/end of day; a window with
+/// `from >= until` wraps past midnight.
+pub fn next_open_at(
+    from: Option<NaiveTime>,
+    until: Option<NaiveTime>,
+    now: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    if from.is_none() && until.is_none() {
+        return None;
+    }
+
+    let from = from.unwrap_or(NaiveTime::MIN);
+    let t = now.time();
+    let open = match until {
+        None => t >= from,
+        Some(until) if from < until => t >= from && t < until,
+        Some(until) => t >= from || t < until,
+    };
+    if open {
+        return None;
+    }
+
+    let today = now.date_naive();
+    let day = if t < from { today } else { today + Duration::days(1) };
+    Some(day.and_time(from).and_utc())
+}
+
+/// Maximum board directory length (the `boards.dir` column)
+pub const MAX_BOARD_DIR_LENGTH: usize = 32;
+
+/// Check that a board directory taken from a URL could exist before looking
+/// it up. Board directories use the same characters as agent IDs.
+pub fn validate_board_dir(dir: &str) -> Result<(), &'static str> {
+    if dir.is_empty() || dir.len() > MAX_BOARD_DIR_LENGTH {
+        return Err("Board directory must be 1-32 characters");
+    }
+    if !dir.chars().all(is_agent_id_char) {
+        return Err("Board directory may only contain lowercase letters, numbers, hyphens, and underscores");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn hm(h: u32, m: u32) -> Option<NaiveTime> {
+        NaiveTime::from_hms_opt(h, m, 0)
+    }
+
+    #[test]
+    fn test_next_open_at() {
+        let at = |h| Utc.with_ymd_and_hms(2024, 3, 10, h, 0, 0).unwrap();
+
+        // No schedule
+        assert_eq!(next_open_at(None, None, at(3)), None);
+
+        // 09:00-17:00
+        assert_eq!(next_open_at(hm(9, 0), hm(17, 0), at(12)), None);
+        assert_eq!(next_open_at(hm(9, 0), hm(17, 0), at(8)), Some(at(9)));
+        assert_eq!(
+            next_open_at(hm(9, 0), hm(17, 0), at(17)),
+            Some(Utc.with_ymd_and_hms(2024, 3, 11, 9, 0, 0).unwrap())
+        );
+
+        // 22:00-02:00 wraps midnight
+        assert_eq!(next_open_at(hm(22, 0), hm(2, 0), at(23)), None);
+        assert_eq!(next_open_at(hm(22, 0), hm(2, 0), at(1)), None);
+        assert_eq!(next_open_at(hm(22, 0), hm(2, 0), at(12)), Some(at(22)));
+
+        // Open-ended bounds
+        assert_eq!(next_open_at(hm(20, 0), None, at(12)), Some(at(20)));
+        assert_eq!(
+            next_open_at(None, hm(6, 0), at(12)),
+            Some(Utc.with_ymd_and_hms(2024, 3, 11, 0, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_validate_board_dir() {
+        for ok in ["g", "tech", "meta-ai", "b_2", "a".repeat(32).as_str()] {
+            assert!(validate_board_dir(ok).is_ok(), "{} should be valid", ok);
+        }
+        for bad in ["", "Tech", "../etc", "wp-admin.php", "a b", "a".repeat(33).as_str()] {
+            assert!(validate_board_dir(bad).is_err(), "{:?} should be rejected", bad);
+        }
+    }
 }
@@ -1,7 +1,9 @@
 mod agent;
 mod board;
+mod moderation;
 mod post;
 
 pub use agent::*;
 pub use board::*;
+pub use moderation::*;
 pub use post::*;
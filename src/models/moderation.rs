@@ -0,0 +1,111 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use utoipa::ToSchema;
+
+/// Moderation actions recorded in the audit log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModerationAction {
+    Delete,
+    Restore,
+    Lock,
+    Unlock,
+    Sticky,
+    Unsticky,
+    Move,
+    Approve,
+    Pin,
+    Unpin,
+}
+
+impl ModerationAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ModerationAction::Delete => "delete",
+            ModerationAction::Restore => "restore",
+            ModerationAction::Lock => "lock",
+            ModerationAction::Unlock => "unlock",
+            ModerationAction::Sticky => "sticky",
+            ModerationAction::Unsticky => "unsticky",
+            ModerationAction::Move => "move",
+            ModerationAction::Approve => "approve",
+            ModerationAction::Pin => "pin",
+            ModerationAction::Unpin => "unpin",
+        }
+    }
+}
+
+/// Who took a moderation action and why, logged along with the action
+#[derive(Debug, Clone, Copy)]
+pub struct ModerationActor<'a> {
+    pub agent_id: &'a str,
+    pub reason: Option<&'a str>,
+}
+
+/// A single moderation log entry
+#[derive(Debug, Clone, Serialize, FromRow, ToSchema)]
+pub struct ModerationLogEntry {
+    pub id: i64,
+    /// Action taken (delete, restore, lock, unlock, sticky, unsticky, move,
+    /// approve, pin, unpin)
+    pub action: String,
+    pub board_id: Option<i32>,
+    /// Internal ID of the target post
+    pub post_id: Option<i64>,
+    /// Per-board post number of the target post
+    pub post_number: Option<i64>,
+    /// Internal ID of the thread the target belongs to
+    pub thread_id: Option<i64>,
+    /// Admin agent who performed the action
+    pub actor_agent_id: String,
+    pub reason: Option<String>,
+    #[serde(with = "crate::timestamp")]
+    pub created_at: DateTime<Utc>,
+}
+
+/// Request body for lock/sticky/pin moderation endpoints
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ModerationToggleRequest {
+    pub enabled: bool,
+    pub reason: Option<String>,
+}
+
+/// Request body for moving a thread to another board
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct MoveThreadRequest {
+    /// Directory of the board to move the thread to
+    pub board: String,
+    pub reason: Option<String>,
+}
+
+/// Actor recorded in the moderation log for automatic actions
+pub const SYSTEM_ACTOR_ID: &str = "system";
+
+/// Maximum report reason length
+pub const MAX_REPORT_REASON_LENGTH: usize = 500;
+
+/// Request body for reporting a post
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct ReportRequest {
+    /// Why the post should be looked at (max 500 characters)
+    pub reason: Option<String>,
+}
+
+/// A report in the moderation queue
+#[derive(Debug, Clone, Serialize, FromRow, ToSchema)]
+pub struct PostReport {
+    pub id: i64,
+    pub board_dir: String,
+    /// Internal ID of the reported post
+    pub post_id: i64,
+    /// Per-board post number of the reported post
+    pub post_number: i64,
+    /// Internal ID of the thread the post belongs to
+    pub thread_id: i64,
+    pub reporter_agent_id: String,
+    pub reason: Option<String>,
+    /// Reports on this post so far, from all agents
+    pub post_report_count: i64,
+    #[serde(with = "crate::timestamp")]
+    pub created_at: DateTime<Utc>,
+}
@@ -1,8 +1,12 @@
 use chrono::{DateTime, Utc};
+use regex::{NoExpand, Regex, RegexBuilder};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use utoipa::ToSchema;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::LazyLock;
 
-use super::PostAuthor;
+use super::{validate_board_dir, AgentResponse, PostAuthor};
 
 /// A post on the imageboard - internal DB representation
 #[derive(Debug, Clone, FromRow)]
@@ -16,6 +20,8 @@ pub struct PostRow {
     pub subject: Option<String>,
     pub message: String,
     pub message_html: String,
+    /// Original message before word filters (NULL if unchanged)
+    pub message_raw: Option<String>,
     pub file: Option<String>,
     pub file_original: Option<String>,
     pub file_mime: Option<String>,
@@ -26,6 +32,7 @@ pub struct PostRow {
     pub thumb_width: Option<i32>,
     pub thumb_height: Option<i32>,
     pub file_hash: Option<String>,
+    pub file_alt: Option<String>,
     /// R9K message hash (SHA-256 of normalized message)
     pub message_hash: Option<String>,
     /// Stored as JSONB, can be NULL
@@ -38,10 +45,23 @@ pub struct PostRow {
     pub bumped_at: DateTime<Utc>,
     pub stickied: bool,
     pub locked: bool,
+    pub view_count: i64,
+    /// Set when a moderator deleted the post (hidden until restored)
+    #[sqlx(default)]
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// Set when pruning archived the thread (OPs only)
+    #[sqlx(default)]
+    pub archived: bool,
+    /// Set while a new thread awaits admin approval (OPs only)
+    #[sqlx(default)]
+    pub pending: bool,
+    /// Set when a moderator pinned the reply (replies only)
+    #[sqlx(default)]
+    pub pinned: bool,
 }
 
 /// A post on the imageboard
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Post {
     pub id: i64,
     /// Board ID
@@ -58,6 +78,9 @@ pub struct Post {
     pub message: String,
     /// Rendered HTML message
     pub message_html: String,
+    /// Original message before word filters were applied (if any changed it)
+    #[serde(skip_serializing)]
+    pub message_raw: Option<String>,
     /// Attached file path
     pub file: Option<String>,
     /// Original filename
@@ -78,6 +101,8 @@ pub struct Post {
     pub thumb_height: Option<i32>,
     /// SHA-256 hash of file
     pub file_hash: Option<String>,
+    /// Image alt text / caption
+    pub file_alt: Option<String>,
     /// R9K message hash (SHA-256 of normalized message)
     pub message_hash: Option<String>,
     /// Structured content (tool outputs, code blocks, etc.)
@@ -87,13 +112,95 @@ pub struct Post {
     /// Agent IDs mentioned in this post
     pub reply_to_agents: Vec<String>,
     /// When the post was created
+    #[serde(with = "crate::timestamp")]
     pub created_at: DateTime<Utc>,
     /// When the thread was last bumped (for OPs)
+    #[serde(with = "crate::timestamp")]
     pub bumped_at: DateTime<Utc>,
     /// Whether the thread is stickied
     pub stickied: bool,
     /// Whether the thread is locked
     pub locked: bool,
+    /// Whether the thread was archived by pruning (read-only)
+    pub archived: bool,
+    /// Whether the thread is awaiting admin approval (hidden until approved)
+    pub pending: bool,
+    /// Whether a moderator pinned the reply to the top of its thread
+    pub pinned: bool,
+    /// Thread views (OPs only, deduped per IP)
+    pub view_count: i64,
+    /// All attached files in order (loaded separately from `post_files`;
+    /// empty until loaded)
+    #[serde(default)]
+    pub files: Vec<PostFile>,
+    /// Reaction counts by type (loaded separately from `reactions`; empty
+    /// until loaded)
+    #[serde(default)]
+    pub reactions: HashMap<String, i64>,
+}
+
+/// One of a post's attached files
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct PostFile {
+    pub post_id: i64,
+    /// Order within the post (0 = the post's primary file)
+    pub position: i16,
+    pub file: String,
+    pub file_original: Option<String>,
+    pub file_mime: Option<String>,
+    pub file_size: Option<i64>,
+    pub file_width: Option<i32>,
+    pub file_height: Option<i32>,
+    pub thumb: Option<String>,
+    pub thumb_width: Option<i32>,
+    pub thumb_height: Option<i32>,
+    pub file_hash: Option<String>,
+    pub file_alt: Option<String>,
+}
+
+impl PostFile {
+    pub fn to_file_info(&self) -> FileInfo {
+        FileInfo {
+            url: self.file.clone(),
+            original_name: self.file_original.clone(),
+            mime: self.file_mime.clone(),
+            size: self.file_size,
+            width: self.file_width,
+            height: self.file_height,
+            thumb_url: self.thumb.clone(),
+            thumb_width: self.thumb_width,
+            thumb_height: self.thumb_height,
+            alt: self.file_alt.clone().or_else(|| self.file_original.clone()),
+            sha256: self.file_hash.clone(),
+        }
+    }
+}
+
+impl Post {
+    /// File info for every attached file. Falls back to the post's own file
+    /// columns when `files` hasn't been loaded.
+    pub fn file_infos(&self) -> Vec<FileInfo> {
+        if !self.files.is_empty() {
+            return self.files.iter().map(PostFile::to_file_info).collect();
+        }
+        self.file
+            .as_ref()
+            .map(|f| FileInfo {
+                url: f.clone(),
+                original_name: self.file_original.clone(),
+                mime: self.file_mime.clone(),
+                size: self.file_size,
+                width: self.file_width,
+                height: self.file_height,
+                thumb_url: self.thumb.clone(),
+                thumb_width: self.thumb_width,
+                thumb_height: self.thumb_height,
+                alt: self.file_alt.clone().or_else(|| self.file_original.clone()),
+                sha256: self.file_hash.clone(),
+            })
+            .into_iter()
+            .collect()
+    }
 }
 
 impl From<PostRow> for Post {
@@ -110,6 +217,7 @@ impl From<PostRow> for Post {
             subject: row.subject,
             message: row.message,
             message_html: row.message_html,
+            message_raw: row.message_raw,
             file: row.file,
             file_original: row.file_original,
             file_mime: row.file_mime,
@@ -120,6 +228,7 @@ impl From<PostRow> for Post {
             thumb_width: row.thumb_width,
             thumb_height: row.thumb_height,
             file_hash: row.file_hash,
+            file_alt: row.file_alt,
             message_hash: row.message_hash,
             structured_content: row.structured_content,
             model_info: row.model_info,
@@ -128,6 +237,12 @@ impl From<PostRow> for Post {
             bumped_at: row.bumped_at,
             stickied: row.stickied,
             locked: row.locked,
+            archived: row.archived,
+            pending: row.pending,
+            pinned: row.pinned,
+            view_count: row.view_count,
+            files: Vec::new(),
+            reactions: HashMap::new(),
         }
     }
 }
@@ -137,23 +252,67 @@ impl From<PostRow> for Post {
 pub struct CreateThreadRequest {
     pub subject: Option<String>,
     pub message: String,
+    /// Original message if word filters changed it
+    #[serde(skip)]
+    pub message_raw: Option<String>,
     pub structured_content: Option<serde_json::Value>,
     pub model_info: Option<serde_json::Value>,
+    /// Hold the thread for admin approval (boards with require_thread_approval)
+    #[serde(skip)]
+    pub pending: bool,
+    /// Count the post against the agent's quota (false for anonymous posts)
+    #[serde(skip)]
+    pub count_quota: bool,
+    /// Normalized subject to hold unique on the board (boards with
+    /// unique_subjects)
+    #[serde(skip)]
+    pub unique_subject: Option<String>,
+}
+
+/// Request body for adding or removing a reaction
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ReactRequest {
+    /// Reaction type (one of the instance's REACTION_TYPES)
+    pub reaction: String,
 }
 
 /// Request to reply to a thread
 #[derive(Debug, Deserialize)]
 pub struct CreateReplyRequest {
     pub message: String,
+    /// Original message if word filters changed it
+    #[serde(skip)]
+    pub message_raw: Option<String>,
     pub structured_content: Option<serde_json::Value>,
     pub model_info: Option<serde_json::Value>,
     /// If true, don't bump the thread (sage)
     #[serde(default)]
     pub sage: bool,
+    /// Whether this reply should bump the thread (default true)
+    #[serde(default = "default_bump")]
+    pub bump: bool,
+    /// Lock the thread once it has this many replies (boards with
+    /// lock_at_reply_limit)
+    #[serde(skip)]
+    pub lock_at: Option<i64>,
+    /// Count the post against the agent's quota (false for anonymous posts)
+    #[serde(skip)]
+    pub count_quota: bool,
 }
 
-/// Post response - anonymous by default, shows model
-#[derive(Debug, Serialize)]
+fn default_bump() -> bool {
+    true
+}
+
+impl CreateReplyRequest {
+    /// Whether this reply bumps its thread (neither sage nor bump=false)
+    pub fn bumps_thread(&self) -> bool {
+        !self.sage && self.bump
+    }
+}
+
+/// Post response - anonymous by default, shows model unless the board hides it
+#[derive(Debug, Serialize, ToSchema)]
 pub struct PostResponse {
     /// Internal database ID (use post_number for display/references)
     pub id: i64,
@@ -163,23 +322,38 @@ pub struct PostResponse {
     pub board_dir: String,
     /// Parent post_number (not id) for replies
     pub parent_id: Option<i64>,
-    /// Author info: "Anonymous" or tripcode, plus model
+    /// Author info: board default name and tripcode, plus model
     pub author: PostAuthor,
     pub subject: Option<String>,
     pub message: String,
     pub message_html: String,
+    /// First attached file (same as `files[0]`, kept for older clients)
     pub file: Option<FileInfo>,
+    /// All attached files in order
+    pub files: Vec<FileInfo>,
     pub structured_content: Option<serde_json::Value>,
     pub model_info: Option<serde_json::Value>,
     pub reply_to_agents: Vec<String>,
+    #[serde(with = "crate::timestamp")]
     pub created_at: DateTime<Utc>,
+    #[serde(with = "crate::timestamp")]
     pub bumped_at: DateTime<Utc>,
     pub stickied: bool,
     pub locked: bool,
+    pub archived: bool,
+    /// Awaiting admin approval (only ever true in the response to the
+    /// request that created the thread)
+    pub pending: bool,
+    /// Pinned to the top of its thread by a moderator (replies only)
+    pub pinned: bool,
+    /// Reaction counts by type (types nobody used are left out)
+    pub reactions: HashMap<String, i64>,
     pub reply_count: Option<i64>,
+    /// Thread views (OPs only)
+    pub view_count: Option<i64>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct FileInfo {
     pub url: String,
     pub original_name: Option<String>,
@@ -190,36 +364,80 @@ pub struct FileInfo {
     pub thumb_url: Option<String>,
     pub thumb_width: Option<i32>,
     pub thumb_height: Option<i32>,
+    /// Alt text (falls back to the original filename)
+    pub alt: Option<String>,
+    /// SHA-256 (hex) of the file as uploaded, before metadata stripping;
+    /// the same image posted twice has the same hash
+    pub sha256: Option<String>,
 }
 
 /// Thread with replies
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct ThreadResponse {
     pub op: PostResponse,
+    /// Pinned replies, oldest first (they're also in `replies`)
+    pub pinned: Vec<PostResponse>,
     pub replies: Vec<PostResponse>,
     pub total_replies: i64,
+    /// Files attached across the thread, OP included
+    pub image_count: i64,
+    #[serde(with = "crate::timestamp::option")]
+    pub last_reply_at: Option<DateTime<Utc>>,
+}
+
+/// Reply graph of a thread, as an adjacency list of post numbers
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ThreadTreeResponse {
+    /// Post number of the OP, the root of the tree
+    pub op: i64,
+    /// Every post in the thread (OP first), in posting order
+    pub nodes: Vec<ReplyTreeNode>,
+}
+
+/// One post in a thread's reply graph
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReplyTreeNode {
+    pub post_number: i64,
+    /// Earlier posts in the thread this post quotes (`>>N`); replies that
+    /// quote nothing in the thread answer the OP
+    pub parents: Vec<i64>,
+    /// Later posts in the thread that quote this post
+    pub children: Vec<i64>,
+}
+
+/// An agent that posted in a thread
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ThreadParticipant {
+    pub agent: AgentResponse,
+    /// Posts by this agent in the thread (OP included)
+    pub post_count: i64,
 }
 
 /// Thread preview (for catalog)
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct ThreadPreview {
     pub op: PostResponse,
     pub reply_count: i64,
+    #[serde(with = "crate::timestamp::option")]
     pub last_reply_at: Option<DateTime<Utc>>,
     pub recent_replies: Vec<PostResponse>,
 }
 
 /// Board page response with threads and pagination
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct BoardPageResponse {
     pub board: super::BoardWithStats,
     pub threads: Vec<BoardThreadPreview>,
     pub page: i64,
     pub total_pages: i64,
+    /// Snapshot the page was computed against; pass as `?snapshot=` for
+    /// subsequent pages to avoid duplicates/skips
+    #[serde(with = "crate::timestamp")]
+    pub snapshot: DateTime<Utc>,
 }
 
 /// Thread on board page (OP with last few replies)
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct BoardThreadPreview {
     pub id: i64,
     pub op: PostResponse,
@@ -228,6 +446,7 @@ pub struct BoardThreadPreview {
     pub image_count: i64,
     pub is_locked: bool,
     pub is_sticky: bool,
+    #[serde(with = "crate::timestamp")]
     pub last_bump_at: DateTime<Utc>,
 }
 
@@ -245,13 +464,23 @@ impl Post {
 
 /// Extract @agent-id mentions from message text
 pub fn extract_mentions(message: &str) -> Vec<String> {
+    scan_mentions(message, |c| c.is_ascii_lowercase())
+}
+
+/// Extract @mentions the way `extract_mentions` does but keeping uppercase
+/// letters, so mistyped ids like `@Alice` can be resolved against known agents
+pub fn extract_mention_candidates(message: &str) -> Vec<String> {
+    scan_mentions(message, |c| c.is_ascii_alphabetic())
+}
+
+fn scan_mentions(message: &str, is_letter: impl Fn(char) -> bool) -> Vec<String> {
     let mut mentions = Vec::new();
     for word in message.split_whitespace() {
         if let Some(agent_id) = word.strip_prefix('@') {
             // Validate it looks like an agent ID
             let clean_id: String = agent_id
                 .chars()
-                .take_while(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || *c == '-' || *c == '_')
+                .take_while(|&c| is_letter(c) || c.is_ascii_digit() || c == '-' || c == '_')
                 .collect();
             if !clean_id.is_empty() && !mentions.contains(&clean_id) {
                 mentions.push(clean_id);
@@ -261,16 +490,203 @@ pub fn extract_mentions(message: &str) -> Vec<String> {
     mentions
 }
 
+/// Most boards a single crosspost may target
+pub const MAX_CROSSPOST_BOARDS: usize = 10;
+
+/// Maximum image alt text length in characters
+pub const MAX_ALT_LENGTH: usize = 250;
+
+/// Clean up image alt text: control characters become spaces, whitespace is
+/// collapsed, and the result is capped at MAX_ALT_LENGTH characters
+/// Returns None if nothing is left.
+pub fn sanitize_alt_text(alt: &str) -> Option<String> {
+    let cleaned: String = alt
+        .split(|c: char| c.is_whitespace() || c.is_control())
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .take(MAX_ALT_LENGTH)
+        .collect();
+
+    let cleaned = cleaned.trim_end().to_string();
+    (!cleaned.is_empty()).then_some(cleaned)
+}
+
+/// Normalize a thread subject for uniqueness checks: trim + lowercase
+pub fn normalize_subject(subject: &str) -> String {
+    subject.trim().to_lowercase()
+}
+
+/// Apply board word filters to a message
+/// Patterns are case-insensitive regexes; replacements are literal.
+/// Text inside [code]...[/code] blocks is left untouched.
+pub fn apply_word_filters(message: &str, filters: &BTreeMap<String, String>) -> String {
+    if filters.is_empty() {
+        return message.to_string();
+    }
+
+    let compiled: Vec<_> = filters
+        .iter()
+        .filter_map(|(pattern, replacement)| {
+            match RegexBuilder::new(pattern).case_insensitive(true).build() {
+                Ok(re) => Some((re, replacement.as_str())),
+                Err(e) => {
+                    tracing::warn!("Skipping invalid word filter {:?}: {}", pattern, e);
+                    None
+                }
+            }
+        })
+        .collect();
+
+    let filter_segment = |text: &str| {
+        compiled.iter().fold(text.to_string(), |acc, (re, replacement)| {
+            re.replace_all(&acc, NoExpand(replacement)).into_owned()
+        })
+    };
+
+    let mut result = String::new();
+    let mut remaining = message;
+
+    while let Some(start) = remaining.find("[code]") {
+        let after_tag = &remaining[start + 6..];
+        match after_tag.find("[/code]") {
+            Some(end) => {
+                result.push_str(&filter_segment(&remaining[..start]));
+                // Keep code block verbatim, including tags
+                result.push_str(&remaining[start..start + 6 + end + 7]);
+                remaining = &after_tag[end + 7..];
+            }
+            None => break,
+        }
+    }
+
+    result.push_str(&filter_segment(remaining));
+    result
+}
+
+/// Validate word filter patterns, returning the first invalid one
+pub fn validate_word_filters(filters: &BTreeMap<String, String>) -> Result<(), String> {
+    for pattern in filters.keys() {
+        if pattern.is_empty() {
+            return Err("Filter pattern cannot be empty".to_string());
+        }
+        RegexBuilder::new(pattern)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| format!("Invalid filter pattern {:?}: {}", pattern, e))?;
+    }
+    Ok(())
+}
+
+/// A `>>123` post reference link as emitted by `render_message`. User text is
+/// HTML-escaped, so only the renderer can produce this markup.
+static REF_LINK: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"<a href="/[^"]*/thread/\d+#p\d+" class="ref">&gt;&gt;(\d+)</a>"#).unwrap()
+});
+
+/// Post numbers referenced (`>>123`) in rendered message HTML
+pub fn referenced_post_numbers(html: &str) -> Vec<i64> {
+    REF_LINK
+        .captures_iter(html)
+        .filter_map(|c| c[1].parse().ok())
+        .collect()
+}
+
+/// Build a thread's reply graph from its posts' rendered HTML, given as
+/// `(post_number, message_html)` with the OP first, in posting order.
+/// Only quotes of earlier posts in the same thread become edges, so the
+/// graph has no cycles.
+pub fn build_reply_tree<'a>(posts: impl IntoIterator<Item = (i64, &'a str)>) -> Vec<ReplyTreeNode> {
+    let mut nodes: Vec<ReplyTreeNode> = Vec::new();
+    let mut index: HashMap<i64, usize> = HashMap::new();
+
+    for (post_number, html) in posts {
+        let mut parents: Vec<i64> = Vec::new();
+        for num in referenced_post_numbers(html) {
+            if index.contains_key(&num) && !parents.contains(&num) {
+                parents.push(num);
+            }
+        }
+        if parents.is_empty() {
+            if let Some(op) = nodes.first() {
+                parents.push(op.post_number);
+            }
+        }
+
+        for parent in &parents {
+            nodes[index[parent]].children.push(post_number);
+        }
+        index.insert(post_number, nodes.len());
+        nodes.push(ReplyTreeNode {
+            post_number,
+            parents,
+            children: Vec::new(),
+        });
+    }
+
+    nodes
+}
+
+/// Replace `>>123` links to posts that don't exist with plain `dead` spans.
+/// Runs on already-rendered HTML so `render_message` stays pure.
+pub fn mark_dead_refs(html: &str, valid: &HashSet<i64>) -> String {
+    REF_LINK
+        .replace_all(html, |c: &regex::Captures| {
+            match c[1].parse::<i64>() {
+                Ok(num) if valid.contains(&num) => c[0].to_string(),
+                _ => format!("<span class=\"dead\">&gt;&gt;{}</span>", &c[1]),
+            }
+        })
+        .into_owned()
+}
+
+/// Rewrite `>>N` post references (as `render_message` reads them) through
+/// `numbers`, for when a thread's posts are renumbered. References not in
+/// the map are left as they are.
+pub fn renumber_refs(message: &str, numbers: &HashMap<i64, i64>) -> String {
+    let mut out = String::with_capacity(message.len());
+    for piece in message.split_inclusive(char::is_whitespace) {
+        let word = piece.trim_end_matches(char::is_whitespace);
+        let renumbered = word
+            .strip_prefix(">>")
+            .and_then(|num| num.parse::<i64>().ok())
+            .and_then(|num| numbers.get(&num));
+        match renumbered {
+            Some(num) => {
+                out.push_str(&format!(">>{}", num));
+                out.push_str(&piece[word.len()..]);
+            }
+            None => out.push_str(piece),
+        }
+    }
+    out
+}
+
+/// Prefix a message with a `>>number` quote line, unless it already opens
+/// with that quote
+pub fn prepend_quote(message: &str, number: i64) -> String {
+    let quote = format!(">>{}", number);
+    let already_quoted = message
+        .strip_prefix(&quote)
+        .is_some_and(|rest| !rest.starts_with(|c: char| c.is_ascii_digit()));
+    if already_quoted {
+        message.to_string()
+    } else {
+        format!("{}\n{}", quote, message)
+    }
+}
+
 /// Render message text to HTML
 /// Handles:
 /// - [code]...[/code] -> code blocks
 /// - [spoiler]...[/spoiler] -> spoiler text
 /// - @mentions -> links
 /// - >>123 post references -> links
-/// - URLs -> links
+/// - URLs -> links (through the `/out` interstitial when `outbound_redirect` is set)
 /// - Newlines -> <br>
 /// - >quote lines -> green text
-pub fn render_message(message: &str, board_dir: &str) -> String {
+pub fn render_message(message: &str, board_dir: &str, outbound_redirect: bool) -> String {
     // First pass: handle code blocks (before escaping)
     let message = render_code_blocks(message);
 
@@ -326,13 +742,13 @@ pub fn render_message(message: &str, board_dir: &str) -> String {
                 }
             }
 
-            // Cross-board reference >>>/board/
-            if word.starts_with(">>>/") {
-                let parts: Vec<&str> = word[4..].split('/').collect();
-                if !parts.is_empty() && !parts[0].is_empty() {
+            // Cross-board reference >>>/board/ (links to the board page)
+            if let Some(rest) = word.strip_prefix(">>>/") {
+                let board = rest.split('/').next().unwrap_or("");
+                if validate_board_dir(board).is_ok() {
                     html.push_str(&format!(
-                        "<a href=\"/api/v1/boards/{}/catalog\" class=\"ref\">&gt;&gt;&gt;/{}/</a>",
-                        escape_html(parts[0]), escape_html(parts[0])
+                        "<a href=\"/{}/\" class=\"ref\">&gt;&gt;&gt;/{}/</a>",
+                        board, board
                     ));
                     continue;
                 }
@@ -356,9 +772,14 @@ pub fn render_message(message: &str, board_dir: &str) -> String {
 
             // URL
             if word.starts_with("http://") || word.starts_with("https://") {
+                let href = if outbound_redirect {
+                    format!("/out?url={}", urlencoding::encode(word))
+                } else {
+                    word.to_string()
+                };
                 html.push_str(&format!(
                     "<a href=\"{}\" rel=\"nofollow noopener\" target=\"_blank\">{}</a>",
-                    escape_html(word),
+                    escape_html(&href),
                     escape_html(word)
                 ));
                 continue;
@@ -429,10 +850,142 @@ fn render_spoilers(message: &str) -> String {
     result
 }
 
-fn escape_html(s: &str) -> String {
+/// Escape text for HTML element content and quoted attribute values
+pub(crate) fn escape_html(s: &str) -> String {
     s.replace('&', "&amp;")
         .replace('<', "&lt;")
         .replace('>', "&gt;")
         .replace('"', "&quot;")
         .replace('\'', "&#39;")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_word_filters_case_insensitive() {
+        let mut filters = BTreeMap::new();
+        filters.insert("tbh".to_string(), "desu".to_string());
+
+        assert_eq!(apply_word_filters("TBH this is fine tbh", &filters), "desu this is fine desu");
+    }
+
+    #[test]
+    fn test_word_filters_skip_code_blocks() {
+        let mut filters = BTreeMap::new();
+        filters.insert("foo".to_string(), "bar".to_string());
+
+        assert_eq!(
+            apply_word_filters("foo [code]let foo = 1;[/code] foo", &filters),
+            "bar [code]let foo = 1;[/code] bar"
+        );
+        // Unclosed code tag is treated as plain text
+        assert_eq!(apply_word_filters("[code]foo", &filters), "[code]bar");
+    }
+
+    #[test]
+    fn test_word_filters_literal_replacement() {
+        let mut filters = BTreeMap::new();
+        filters.insert(r"\bcat\b".to_string(), "$1dog".to_string());
+
+        assert_eq!(apply_word_filters("cat category", &filters), "$1dog category");
+    }
+
+    #[test]
+    fn test_render_outbound_redirect() {
+        let direct = render_message("see https://example.com/a?b=1", "b", false);
+        assert!(direct.contains("href=\"https://example.com/a?b=1\""));
+
+        let wrapped = render_message("see https://example.com/a?b=1", "b", true);
+        assert!(wrapped.contains("href=\"/out?url=https%3A%2F%2Fexample.com%2Fa%3Fb%3D1\""));
+        assert!(wrapped.contains(">https://example.com/a?b=1</a>"));
+    }
+
+    #[test]
+    fn test_mark_dead_refs() {
+        let html = render_message(">>1 and >>2 but not >>>/g/", "b", false);
+        assert_eq!(referenced_post_numbers(&html), vec![1, 2]);
+
+        let marked = mark_dead_refs(&html, &HashSet::from([1]));
+        assert!(marked.contains("class=\"ref\">&gt;&gt;1</a>"));
+        assert!(marked.contains("<span class=\"dead\">&gt;&gt;2</span>"));
+        assert!(marked.contains("&gt;&gt;&gt;/g/</a>"));
+    }
+
+    #[test]
+    fn test_mention_candidates() {
+        let message = "@alice @Bob-2, @bOb-2 again @alice";
+        assert_eq!(extract_mentions(message), vec!["alice", "b"]);
+        assert_eq!(extract_mention_candidates(message), vec!["alice", "Bob-2", "bOb-2"]);
+    }
+
+    #[test]
+    fn test_cross_board_ref() {
+        let html = render_message(">>>/g/ and >>>/sci/", "b", false);
+        assert!(html.contains("<a href=\"/g/\" class=\"ref\">&gt;&gt;&gt;/g/</a>"));
+        assert!(html.contains("<a href=\"/sci/\" class=\"ref\">"));
+
+        // Not a board name: left as plain text
+        let html = render_message(">>>/G\"x/ >>>/../", "b", false);
+        assert!(!html.contains("<a"));
+    }
+
+    #[test]
+    fn test_build_reply_tree() {
+        let posts = [
+            (1, render_message(">>5", "b", false)),
+            (2, render_message("first", "b", false)),
+            (3, render_message(">>2 >>2 >>1", "b", false)),
+            (4, render_message(">>9 elsewhere, >>4 myself", "b", false)),
+        ];
+        let tree = build_reply_tree(posts.iter().map(|(n, html)| (*n, html.as_str())));
+
+        let edges: Vec<(i64, Vec<i64>, Vec<i64>)> = tree
+            .into_iter()
+            .map(|n| (n.post_number, n.parents, n.children))
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                (1, vec![], vec![2, 3, 4]),
+                (2, vec![1], vec![3]),
+                (3, vec![2, 1], vec![]),
+                (4, vec![1], vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_renumber_refs() {
+        let numbers = HashMap::from([(5, 101), (6, 102)]);
+        assert_eq!(
+            renumber_refs(">>5\nyes >>6  and >>7, >>>/g/ >>5x", &numbers),
+            ">>101\nyes >>102  and >>7, >>>/g/ >>5x"
+        );
+    }
+
+    #[test]
+    fn test_prepend_quote() {
+        assert_eq!(prepend_quote("agreed", 12), ">>12\nagreed");
+        assert_eq!(prepend_quote(">>12 agreed", 12), ">>12 agreed");
+        assert_eq!(prepend_quote(">>123 agreed", 12), ">>12\n>>123 agreed");
+    }
+
+    #[test]
+    fn test_sanitize_alt_text() {
+        assert_eq!(sanitize_alt_text("  a cat\n\ton a\u{0}mat "), Some("a cat on a mat".to_string()));
+        assert_eq!(sanitize_alt_text(" \n "), None);
+        assert_eq!(sanitize_alt_text(&"é".repeat(300)).unwrap().chars().count(), MAX_ALT_LENGTH);
+    }
+
+    #[test]
+    fn test_validate_word_filters() {
+        let mut filters = BTreeMap::new();
+        filters.insert("ok".to_string(), "fine".to_string());
+        assert!(validate_word_filters(&filters).is_ok());
+
+        filters.insert("(unclosed".to_string(), "x".to_string());
+        assert!(validate_word_filters(&filters).is_err());
+    }
+}
@@ -5,21 +5,38 @@
 //! - Redis: Uses Redis INCR/EXPIRE for distributed rate limiting (multi-instance)
 //!
 //! Configure via REDIS_URL environment variable to use Redis backend.
+//! If Redis errors mid-request, limiters fail open (allow the request) unless
+//! configured to fail closed (503). Either way the fallback is logged and
+//! counted in `/ready`.
 
 use axum::{
     extract::{ConnectInfo, Request, State},
-    http::StatusCode,
+    http::{HeaderMap, Method, StatusCode},
     middleware::Next,
     response::{IntoResponse, Response},
 };
 use std::{
     collections::HashMap,
     net::{IpAddr, SocketAddr},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
     time::{Duration, Instant},
 };
 use tokio::sync::RwLock;
 
+use crate::error::AppError;
+
+/// Redis rate limit checks that failed and fell back to the failure policy,
+/// across all limiters since startup
+static REDIS_FALLBACKS: AtomicU64 = AtomicU64::new(0);
+
+/// Number of Redis rate limit failures since startup
+pub fn redis_fallback_count() -> u64 {
+    REDIS_FALLBACKS.load(Ordering::Relaxed)
+}
+
 /// Rate limiter that supports both in-memory and Redis backends
 #[derive(Clone)]
 pub struct RateLimiter {
@@ -30,13 +47,17 @@ pub struct RateLimiter {
     window_secs: u64,
     /// Whether rate limiting is enabled
     enabled: bool,
+    /// Redis key namespace, so separate limiters don't share counters
+    namespace: &'static str,
+    /// Allow requests when Redis is unreachable (otherwise reject with 503)
+    fail_open: bool,
 }
 
 #[derive(Clone)]
 enum RateLimiterInner {
     /// In-memory rate limiting (single instance only)
     Memory {
-        requests: Arc<RwLock<HashMap<IpAddr, Vec<Instant>>>>,
+        requests: Arc<RwLock<HashMap<String, Vec<Instant>>>>,
     },
     /// Redis-backed rate limiting (distributed)
     Redis {
@@ -54,6 +75,8 @@ impl RateLimiter {
             limit: requests_per_minute,
             window_secs: 60,
             enabled,
+            namespace: "ip",
+            fail_open: true,
         }
     }
 
@@ -66,9 +89,24 @@ impl RateLimiter {
             limit: requests_per_minute,
             window_secs: 60,
             enabled,
+            namespace: "ip",
+            fail_open: true,
         })
     }
 
+    /// Use a separate key namespace (for limiters other than the global IP one)
+    pub fn with_namespace(mut self, namespace: &'static str) -> Self {
+        self.namespace = namespace;
+        self
+    }
+
+    /// Set whether requests are allowed (fail open) or rejected (fail closed)
+    /// when the Redis backend errors
+    pub fn with_fail_open(mut self, fail_open: bool) -> Self {
+        self.fail_open = fail_open;
+        self
+    }
+
     /// Create rate limiter from configuration
     /// Uses Redis if REDIS_URL is configured, otherwise falls back to in-memory
     pub async fn from_config(
@@ -97,32 +135,39 @@ impl RateLimiter {
         Self::new_memory(requests_per_minute, enabled)
     }
 
-    /// Check if a request is allowed and record it
-    pub async fn check_and_record(&self, ip: IpAddr) -> bool {
+    /// Check if a request is allowed and record it. Errors only when Redis
+    /// is unavailable and the limiter fails closed.
+    pub async fn check_and_record(&self, ip: IpAddr) -> crate::error::Result<bool> {
+        self.check_and_record_key(&ip.to_string()).await
+    }
+
+    /// Like `check_and_record`, for limiters keyed by something other than
+    /// an IP (e.g. an API key id)
+    pub async fn check_and_record_key(&self, key: &str) -> crate::error::Result<bool> {
         if !self.enabled {
-            return true;
+            return Ok(true);
         }
 
         match &self.inner {
             RateLimiterInner::Memory { requests } => {
-                self.check_and_record_memory(requests, ip).await
+                Ok(self.check_and_record_memory(requests, key).await)
             }
             RateLimiterInner::Redis { conn } => {
-                self.check_and_record_redis(conn.clone(), ip).await
+                self.check_and_record_redis(conn.clone(), key).await
             }
         }
     }
 
     async fn check_and_record_memory(
         &self,
-        requests: &Arc<RwLock<HashMap<IpAddr, Vec<Instant>>>>,
-        ip: IpAddr,
+        requests: &Arc<RwLock<HashMap<String, Vec<Instant>>>>,
+        key: &str,
     ) -> bool {
         let now = Instant::now();
         let cutoff = now - Duration::from_secs(self.window_secs);
 
         let mut requests = requests.write().await;
-        let timestamps = requests.entry(ip).or_insert_with(Vec::new);
+        let timestamps = requests.entry(key.to_string()).or_insert_with(Vec::new);
 
         // Remove old timestamps outside the window
         timestamps.retain(|&t| t > cutoff);
@@ -135,8 +180,12 @@ impl RateLimiter {
         true
     }
 
-    async fn check_and_record_redis(&self, mut conn: redis::aio::MultiplexedConnection, ip: IpAddr) -> bool {
-        let key = format!("ratelimit:ip:{}", ip);
+    async fn check_and_record_redis(
+        &self,
+        mut conn: redis::aio::MultiplexedConnection,
+        key: &str,
+    ) -> crate::error::Result<bool> {
+        let key = format!("ratelimit:{}:{}", self.namespace, key);
 
         let result: Result<bool, redis::RedisError> = async {
             // Use pipeline to send INCR and EXPIRE in a single round-trip
@@ -152,11 +201,26 @@ impl RateLimiter {
         .await;
 
         match result {
-            Ok(allowed) => allowed,
+            Ok(allowed) => Ok(allowed),
             Err(e) => {
-                tracing::error!("Redis rate limit check failed: {}. Allowing request.", e);
-                // Fail open: allow requests if Redis is unavailable
-                true
+                REDIS_FALLBACKS.fetch_add(1, Ordering::Relaxed);
+                if self.fail_open {
+                    tracing::error!(
+                        namespace = self.namespace,
+                        "Redis rate limit check failed: {}. Allowing request (fail open).",
+                        e
+                    );
+                    Ok(true)
+                } else {
+                    tracing::error!(
+                        namespace = self.namespace,
+                        "Redis rate limit check failed: {}. Rejecting request (fail closed).",
+                        e
+                    );
+                    Err(AppError::ServiceUnavailable(
+                        "Rate limiting is temporarily unavailable".to_string(),
+                    ))
+                }
             }
         }
     }
@@ -183,12 +247,12 @@ impl RateLimiter {
                 let cutoff = now - Duration::from_secs(self.window_secs);
                 let requests = requests.read().await;
                 requests
-                    .get(&ip)
+                    .get(&ip.to_string())
                     .map(|ts| ts.iter().filter(|&&t| t > cutoff).count())
                     .unwrap_or(0)
             }
             RateLimiterInner::Redis { conn } => {
-                let key = format!("ratelimit:ip:{}", ip);
+                let key = format!("ratelimit:{}:{}", self.namespace, ip);
                 let mut conn = conn.clone();
                 let result: Result<usize, redis::RedisError> = async {
                     let count: Option<usize> = redis::cmd("GET")
@@ -209,9 +273,39 @@ impl RateLimiter {
     }
 }
 
+/// How the global IP limiter treats reads (GET, HEAD and OPTIONS)
+#[derive(Clone)]
+pub enum ReadLimit {
+    /// Reads and writes share one bucket
+    Shared,
+    /// Reads have their own bucket, so polling doesn't eat the write budget
+    Separate(RateLimiter),
+    /// Reads aren't limited
+    Exempt,
+}
+
+/// Limiters used by `rate_limit_middleware`
+#[derive(Clone)]
+pub struct IpRateLimits {
+    pub limiter: RateLimiter,
+    pub reads: ReadLimit,
+}
+
+impl IpRateLimits {
+    /// The limiter a request with this method counts against (None if exempt)
+    pub fn for_method(&self, method: &Method) -> Option<&RateLimiter> {
+        let is_read = matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS);
+        match &self.reads {
+            ReadLimit::Separate(reads) if is_read => Some(reads),
+            ReadLimit::Exempt if is_read => None,
+            _ => Some(&self.limiter),
+        }
+    }
+}
+
 /// Rate limit middleware
 pub async fn rate_limit_middleware(
-    State(limiter): State<RateLimiter>,
+    State(limits): State<IpRateLimits>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     request: Request,
     next: Next,
@@ -222,22 +316,42 @@ pub async fn rate_limit_middleware(
         return next.run(request).await;
     }
 
-    let ip = addr.ip();
+    let Some(limiter) = limits.for_method(request.method()) else {
+        return next.run(request).await;
+    };
+    let real_ip = client_ip(request.headers(), addr.ip());
+
+    match limiter.check_and_record(real_ip).await {
+        Ok(true) => {}
+        Ok(false) => return RateLimitResponse.into_response(),
+        Err(e) => return e.into_response(),
+    }
+
+    next.run(request).await
+}
 
-    // Check for X-Forwarded-For header (behind proxy like Railway)
-    let real_ip = request
-        .headers()
+/// Client IP resolved by `client_ip_middleware`, available as a request
+/// extension to extractors that don't see `ConnectInfo` directly
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub IpAddr);
+
+/// Resolve the client IP once per request and store it as a `ClientIp` extension
+pub async fn client_ip_middleware(mut request: Request, next: Next) -> Response {
+    if let Some(ConnectInfo(addr)) = request.extensions().get::<ConnectInfo<SocketAddr>>().copied() {
+        let ip = client_ip(request.headers(), addr.ip());
+        request.extensions_mut().insert(ClientIp(ip));
+    }
+    next.run(request).await
+}
+
+/// Resolve the client IP, preferring X-Forwarded-For (behind proxy like Railway)
+pub fn client_ip(headers: &HeaderMap, fallback: IpAddr) -> IpAddr {
+    headers
         .get("x-forwarded-for")
         .and_then(|v| v.to_str().ok())
         .and_then(|s| s.split(',').next())
         .and_then(|s| s.trim().parse::<IpAddr>().ok())
-        .unwrap_or(ip);
-
-    if !limiter.check_and_record(real_ip).await {
-        return RateLimitResponse.into_response();
-    }
-
-    next.run(request).await
+        .unwrap_or(fallback)
 }
 
 struct RateLimitResponse;
@@ -256,6 +370,206 @@ impl IntoResponse for RateLimitResponse {
     }
 }
 
+/// Per-thread reply throttle
+///
+/// A token bucket per thread, so a burst of replies from many different
+/// agents can't flood a single thread (and its SSE subscribers). This is
+/// independent of the per-agent and per-IP limits. In-memory only.
+#[derive(Clone, Default)]
+pub struct ThreadThrottle {
+    buckets: Arc<RwLock<HashMap<i64, TokenBucket>>>,
+}
+
+struct TokenBucket {
+    tokens: f64,
+    rate: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    /// Burst size: one second's worth of replies, but always at least one
+    fn capacity(rate: f64) -> f64 {
+        rate.max(1.0)
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(Self::capacity(self.rate));
+        self.updated = now;
+    }
+}
+
+impl ThreadThrottle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take a reply token for a thread whose board allows `rate` replies per
+    /// second. A rate of zero (or less) disables the throttle. When the bucket
+    /// is empty, returns how long until the next token is available.
+    pub async fn check(&self, thread_id: i64, rate: f64) -> Result<(), Duration> {
+        if rate <= 0.0 {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        let mut buckets = self.buckets.write().await;
+        let bucket = buckets.entry(thread_id).or_insert_with(|| TokenBucket {
+            tokens: TokenBucket::capacity(rate),
+            rate,
+            updated: now,
+        });
+
+        // Pick up board setting changes
+        bucket.rate = rate;
+        bucket.refill(now);
+
+        if bucket.tokens < 1.0 {
+            return Err(Duration::from_secs_f64((1.0 - bucket.tokens) / rate));
+        }
+
+        bucket.tokens -= 1.0;
+        Ok(())
+    }
+
+    /// Drop buckets that have refilled completely (they'd be recreated full)
+    pub async fn cleanup(&self) {
+        let now = Instant::now();
+        let mut buckets = self.buckets.write().await;
+        buckets.retain(|_, bucket| {
+            bucket.refill(now);
+            bucket.tokens < TokenBucket::capacity(bucket.rate)
+        });
+    }
+}
+
+/// Start background cleanup task for the thread reply throttle
+pub fn start_thread_throttle_cleanup(throttle: ThreadThrottle) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            throttle.cleanup().await;
+        }
+    });
+}
+
+/// Thread view deduplication
+///
+/// Remembers which IPs viewed which threads within a window, so reloads and
+/// polling don't inflate view counts. In-memory only.
+#[derive(Clone)]
+pub struct ViewDeduper {
+    seen: Arc<RwLock<HashMap<(IpAddr, i64), Instant>>>,
+    window: Duration,
+}
+
+impl ViewDeduper {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            seen: Arc::new(RwLock::new(HashMap::new())),
+            window,
+        }
+    }
+
+    /// Record a view; true if it's the first from this IP within the window
+    pub async fn first_view(&self, ip: IpAddr, thread_id: i64) -> bool {
+        let now = Instant::now();
+        let mut seen = self.seen.write().await;
+        match seen.get(&(ip, thread_id)) {
+            Some(&at) if now.duration_since(at) < self.window => false,
+            _ => {
+                seen.insert((ip, thread_id), now);
+                true
+            }
+        }
+    }
+
+    /// Forget views older than the window
+    pub async fn cleanup(&self) {
+        let now = Instant::now();
+        let mut seen = self.seen.write().await;
+        seen.retain(|_, at| now.duration_since(*at) < self.window);
+    }
+}
+
+/// Start background cleanup task for thread view deduplication
+pub fn start_view_dedup_cleanup(views: ViewDeduper) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            views.cleanup().await;
+        }
+    });
+}
+
+/// In-flight request counts by agent ID
+type InFlightCounts = Arc<std::sync::Mutex<HashMap<String, usize>>>;
+
+/// Per-agent in-flight request limit
+///
+/// Counts each agent's requests in progress so one misbehaving agent can't
+/// tie up the server with hundreds of simultaneous connections. Slots are
+/// taken when the API key is validated and released when the request's
+/// `AuthenticatedAgent` is dropped. In-memory only (per instance).
+#[derive(Clone, Default)]
+pub struct AgentConcurrency {
+    /// Maximum requests in flight per agent (0 = unlimited)
+    max: usize,
+    in_flight: InFlightCounts,
+}
+
+impl AgentConcurrency {
+    pub fn new(max: usize) -> Self {
+        Self {
+            max,
+            in_flight: Arc::default(),
+        }
+    }
+
+    /// Take a slot for `agent_id`, or fail with 429 when all are in use
+    pub fn try_acquire(&self, agent_id: &str) -> Result<InFlightPermit, AppError> {
+        if self.max == 0 {
+            return Ok(InFlightPermit { slot: None });
+        }
+
+        let mut in_flight = self.in_flight.lock().unwrap_or_else(|e| e.into_inner());
+        let count = in_flight.entry(agent_id.to_string()).or_insert(0);
+        if *count >= self.max {
+            return Err(AppError::RateLimited {
+                retry_after: Some(Duration::from_secs(1)),
+            });
+        }
+        *count += 1;
+
+        Ok(InFlightPermit {
+            slot: Some((self.in_flight.clone(), agent_id.to_string())),
+        })
+    }
+}
+
+/// A held in-flight slot, released on drop
+#[derive(Debug)]
+pub struct InFlightPermit {
+    slot: Option<(InFlightCounts, String)>,
+}
+
+impl Drop for InFlightPermit {
+    fn drop(&mut self) {
+        let Some((in_flight, agent_id)) = self.slot.take() else {
+            return;
+        };
+        let mut in_flight = in_flight.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(count) = in_flight.get_mut(&agent_id) {
+            *count -= 1;
+            if *count == 0 {
+                in_flight.remove(&agent_id);
+            }
+        }
+    }
+}
+
 /// Start background cleanup task for rate limiter (only needed for memory backend)
 pub fn start_cleanup_task(limiter: RateLimiter) {
     tokio::spawn(async move {
@@ -266,3 +580,88 @@ pub fn start_cleanup_task(limiter: RateLimiter) {
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_thread_throttle_bucket() {
+        let throttle = ThreadThrottle::new();
+
+        // Burst of two, then empty
+        assert!(throttle.check(1, 2.0).await.is_ok());
+        assert!(throttle.check(1, 2.0).await.is_ok());
+        let wait = throttle.check(1, 2.0).await.unwrap_err();
+        assert!(wait <= Duration::from_millis(500));
+
+        // Buckets are per thread
+        assert!(throttle.check(2, 2.0).await.is_ok());
+
+        // Zero disables
+        for _ in 0..10 {
+            assert!(throttle.check(3, 0.0).await.is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_view_dedup_per_ip_and_thread() {
+        let views = ViewDeduper::new(Duration::from_secs(60));
+        let a: IpAddr = "203.0.113.1".parse().unwrap();
+        let b: IpAddr = "203.0.113.2".parse().unwrap();
+
+        assert!(views.first_view(a, 1).await);
+        assert!(!views.first_view(a, 1).await);
+        assert!(views.first_view(a, 2).await);
+        assert!(views.first_view(b, 1).await);
+    }
+
+    #[test]
+    fn test_agent_concurrency() {
+        let limit = AgentConcurrency::new(2);
+
+        let first = limit.try_acquire("a").unwrap();
+        let _second = limit.try_acquire("a").unwrap();
+        assert!(limit.try_acquire("a").is_err());
+        // Slots are per agent
+        assert!(limit.try_acquire("b").is_ok());
+
+        drop(first);
+        assert!(limit.try_acquire("a").is_ok());
+
+        // Zero disables
+        let unlimited = AgentConcurrency::new(0);
+        let _permits: Vec<_> = (0..10).map(|_| unlimited.try_acquire("a").unwrap()).collect();
+    }
+
+    #[tokio::test]
+    async fn test_limit_by_key() {
+        let limiter = RateLimiter::new_memory(1, true).with_namespace("reader");
+        assert!(limiter.check_and_record_key("1").await.unwrap());
+        assert!(!limiter.check_and_record_key("1").await.unwrap());
+        assert!(limiter.check_and_record_key("2").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_reads_use_their_own_bucket() {
+        let ip: IpAddr = "203.0.113.1".parse().unwrap();
+        let limits = IpRateLimits {
+            limiter: RateLimiter::new_memory(1, true),
+            reads: ReadLimit::Separate(RateLimiter::new_memory(2, true).with_namespace("read")),
+        };
+
+        let post = limits.for_method(&Method::POST).unwrap();
+        assert!(post.check_and_record(ip).await.unwrap());
+        assert!(!post.check_and_record(ip).await.unwrap());
+
+        // Writes are used up, reads aren't
+        let get = limits.for_method(&Method::GET).unwrap();
+        assert!(get.check_and_record(ip).await.unwrap());
+        assert!(get.check_and_record(ip).await.unwrap());
+        assert!(!get.check_and_record(ip).await.unwrap());
+
+        let exempt = IpRateLimits { reads: ReadLimit::Exempt, ..limits };
+        assert!(exempt.for_method(&Method::HEAD).is_none());
+        assert!(exempt.for_method(&Method::DELETE).is_some());
+    }
+}
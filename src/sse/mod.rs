@@ -4,7 +4,14 @@ use axum::{
 };
 use futures::stream::Stream;
 use serde::Serialize;
-use std::{convert::Infallible, time::Duration};
+use std::{
+    convert::Infallible,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 use tokio::sync::broadcast;
 
 use crate::AppState;
@@ -26,6 +33,18 @@ pub enum SseEvent {
         board_id: i32,
         thread_id: i64,
     },
+    /// Thread locked on reaching its board's reply limit
+    ThreadLocked {
+        board_id: i32,
+        thread_id: i64,
+    },
+    /// Reply pinned or unpinned by a moderator
+    ReplyPinned {
+        board_id: i32,
+        thread_id: i64,
+        post_id: i64,
+        pinned: bool,
+    },
     /// Agent mentioned
     Mention {
         agent_id: String,
@@ -34,6 +53,14 @@ pub enum SseEvent {
         thread_id: i64,
         by_agent: String,
     },
+    /// New reply in a thread the agent watches
+    WatchedReply {
+        agent_id: String,
+        post_id: i64,
+        board_dir: String,
+        thread_id: i64,
+        by_agent: String,
+    },
     /// Heartbeat
     Ping,
 }
@@ -44,6 +71,12 @@ pub struct SseState {
     sender: broadcast::Sender<SseEvent>,
 }
 
+impl Default for SseState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SseState {
     pub fn new() -> Self {
         let (sender, _) = broadcast::channel(1024);
@@ -61,13 +94,35 @@ impl SseState {
     }
 }
 
+/// Counts an open SSE stream until the stream is dropped
+struct ConnectionGuard(Arc<AtomicUsize>);
+
+impl ConnectionGuard {
+    fn new(connections: Arc<AtomicUsize>) -> Self {
+        connections.fetch_add(1, Ordering::Relaxed);
+        Self(connections)
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
 /// SSE stream handler
 pub async fn stream_handler(
     State(state): State<AppState>,
 ) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
     let mut rx = state.sse.subscribe();
+    let security = &state.config.security;
+    let ping_interval = (security.sse_ping_interval_secs > 0)
+        .then(|| Duration::from_secs(security.sse_ping_interval_secs));
+    let keep_alive = KeepAlive::new().interval(Duration::from_secs(security.sse_keepalive_secs.max(1)));
+    let connection = ConnectionGuard::new(state.sse_connections.clone());
 
     let stream = async_stream::stream! {
+        let _connection = connection;
         loop {
             tokio::select! {
                 // Receive broadcast events
@@ -92,8 +147,13 @@ pub async fn stream_handler(
                         }
                     }
                 }
-                // Send periodic pings
-                _ = tokio::time::sleep(Duration::from_secs(30)) => {
+                // Send a ping after an idle interval (unless disabled)
+                _ = async {
+                    match ping_interval {
+                        Some(interval) => tokio::time::sleep(interval).await,
+                        None => std::future::pending().await,
+                    }
+                } => {
                     match serde_json::to_string(&SseEvent::Ping) {
                         Ok(json) => yield Ok(Event::default().data(json)),
                         Err(e) => {
@@ -105,5 +165,5 @@ pub async fn stream_handler(
         }
     };
 
-    Sse::new(stream).keep_alive(KeepAlive::default())
+    Sse::new(stream).keep_alive(keep_alive)
 }
@@ -0,0 +1,113 @@
+//! Canonical timestamp serialization for API responses
+//!
+//! All timestamps are emitted as RFC3339 in UTC with exactly millisecond
+//! precision and a `Z` suffix, e.g. `2024-01-01T00:00:00.000Z`.
+//!
+//! Usage:
+//! - `#[serde(with = "crate::timestamp")]` for `DateTime<Utc>`
+//! - `#[serde(with = "crate::timestamp::option")]` for `Option<DateTime<Utc>>`
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Deserializer, Serializer};
+
+/// Format a timestamp in the canonical wire format
+pub fn format(dt: &DateTime<Utc>) -> String {
+    dt.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+pub fn serialize<S>(dt: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&format(dt))
+}
+
+/// Accepts any RFC3339 timestamp (any precision/offset) and converts to UTC
+pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    DateTime::parse_from_rfc3339(&s)
+        .map(|dt| dt.with_timezone(&Utc))
+        .map_err(serde::de::Error::custom)
+}
+
+pub mod option {
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(dt: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match dt {
+            Some(dt) => serializer.serialize_some(&super::format(dt)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s: Option<String> = Option::deserialize(deserializer)?;
+        s.map(|s| {
+            DateTime::parse_from_rfc3339(&s)
+                .map(|dt| dt.with_timezone(&Utc))
+                .map_err(serde::de::Error::custom)
+        })
+        .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use serde::Serialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Wrapper {
+        #[serde(with = "crate::timestamp")]
+        at: DateTime<Utc>,
+        #[serde(with = "crate::timestamp::option")]
+        maybe: Option<DateTime<Utc>>,
+    }
+
+    #[test]
+    fn test_canonical_format() {
+        let dt = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(format(&dt), "2024-01-01T00:00:00.000Z");
+
+        // Sub-millisecond precision is truncated
+        let dt = dt + chrono::Duration::nanoseconds(123_456_789);
+        assert_eq!(format(&dt), "2024-01-01T00:00:00.123Z");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let at = Utc.with_ymd_and_hms(2024, 6, 15, 12, 30, 45).unwrap()
+            + chrono::Duration::milliseconds(250);
+        let w = Wrapper { at, maybe: Some(at) };
+
+        let json = serde_json::to_string(&w).unwrap();
+        assert_eq!(
+            json,
+            r#"{"at":"2024-06-15T12:30:45.250Z","maybe":"2024-06-15T12:30:45.250Z"}"#
+        );
+        assert_eq!(serde_json::from_str::<Wrapper>(&json).unwrap(), w);
+
+        let none = Wrapper { at, maybe: None };
+        let json = serde_json::to_string(&none).unwrap();
+        assert_eq!(json, r#"{"at":"2024-06-15T12:30:45.250Z","maybe":null}"#);
+        assert_eq!(serde_json::from_str::<Wrapper>(&json).unwrap(), none);
+    }
+
+    #[test]
+    fn test_deserialize_offset_normalized_to_utc() {
+        let w: Wrapper =
+            serde_json::from_str(r#"{"at":"2024-01-01T02:00:00+02:00","maybe":null}"#).unwrap();
+        assert_eq!(format(&w.at), "2024-01-01T00:00:00.000Z");
+    }
+}
@@ -179,6 +179,12 @@ pub fn generate_state() -> String {
     hex::encode(bytes)
 }
 
+/// Whether a string has the shape produced by [`generate_state`]
+/// (64 lowercase hex chars)
+pub fn is_valid_state(state: &str) -> bool {
+    state.len() == 64 && state.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -196,4 +202,13 @@ mod tests {
         // Hash should be 64 hex chars (256 bits)
         assert_eq!(hash1.len(), 64);
     }
+
+    #[test]
+    fn test_is_valid_state() {
+        assert!(is_valid_state(&generate_state()));
+        assert!(!is_valid_state(""));
+        assert!(!is_valid_state(&"a".repeat(65)));
+        assert!(!is_valid_state(&"A".repeat(64)));
+        assert!(!is_valid_state(&"g".repeat(64)));
+    }
 }
@@ -16,6 +16,7 @@ export interface FileInfo {
   height: number | null;
   thumb_width: number | null;
   thumb_height: number | null;
+  alt: string | null;
 }
 
 export interface PostResponse {